@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use rayon::slice::ParallelSliceMut;
+
+use crate::{color::HdrColor, matrix::Dynamic2D};
+
+/// One see-through surface covering a sample.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fragment {
+    pub z: f64,
+    pub color: HdrColor,
+    pub alpha: f64,
+    /// The draw and the triangle within it that left the fragment. Fragments at the same depth
+    /// are blended in this order, whichever thread got to them first.
+    pub order: (usize, usize),
+}
+
+/// Collects the fragments of transparent surfaces instead of letting them fight over the
+/// z-buffer. Triangles are rasterized in whatever order the thread pool gets to them, so
+/// blending can only happen once everything is in and each sample's list can be sorted.
+#[derive(Clone, Debug)]
+pub struct ABuffer {
+    width: usize,
+    draws: usize,
+    fragments: Vec<(usize, Fragment)>,
+}
+
+impl ABuffer {
+    pub fn new(width: usize) -> Self {
+        Self {
+            width,
+            draws: 0,
+            fragments: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, x: usize, y: usize, fragment: Fragment) {
+        self.fragments.push((y * self.width + x, fragment));
+    }
+
+    pub fn clear(&mut self) {
+        self.draws = 0;
+        self.fragments.clear();
+    }
+
+    /// Numbers a new draw, for the `order` of the fragments it leaves.
+    pub fn start_draw(&mut self) -> usize {
+        self.draws += 1;
+        self.draws
+    }
+
+    /// Blends every sample that has fragments in front of the opaque surface behind it,
+    /// back to front. `under` gives the color of whatever is behind the fragments at a sample,
+    /// and `shade` the color a fragment contributes.
+    pub fn resolve(
+        &self,
+        zbuffer: &Dynamic2D<f64>,
+        under: impl Fn(usize, usize) -> HdrColor,
+        shade: impl Fn(&Fragment) -> HdrColor,
+    ) -> HashMap<(usize, usize), HdrColor> {
+        let mut visible: Vec<(usize, Fragment)> = self
+            .fragments
+            .iter()
+            .filter(|(index, fragment)| {
+                fragment.z > zbuffer[index / self.width][index % self.width]
+            })
+            .copied()
+            .collect();
+        visible.par_sort_unstable_by(|(a_index, a), (b_index, b)| {
+            a_index
+                .cmp(b_index)
+                .then(a.z.total_cmp(&b.z))
+                .then(a.order.cmp(&b.order))
+        });
+
+        visible
+            .chunk_by(|(a, _), (b, _)| a == b)
+            .map(|layers| {
+                let (y, x) = (layers[0].0 / self.width, layers[0].0 % self.width);
+                let color = layers.iter().fold(under(y, x), |behind, (_, fragment)| {
+                    shade(fragment).scale(fragment.alpha) + behind.scale(1.0 - fragment.alpha)
+                });
+                ((y, x), color)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ABuffer, Fragment};
+    use crate::{color::HdrColor, matrix::Dynamic2D};
+
+    fn fragment(z: f64, red: f32) -> Fragment {
+        Fragment {
+            z,
+            color: HdrColor::new(red, 0.0, 0.0),
+            alpha: 0.5,
+            order: (0, 0),
+        }
+    }
+
+    #[test]
+    fn blending_ignores_submission_order() {
+        let zbuffer = Dynamic2D::fill(f64::NEG_INFINITY, 2, 2);
+        let layers = [fragment(1.0, 1.0), fragment(2.0, 0.0), fragment(3.0, 0.5)];
+        let resolve = |order: [usize; 3]| {
+            let mut abuffer = ABuffer::new(2);
+            for i in order {
+                abuffer.push(1, 0, layers[i]);
+            }
+            abuffer.resolve(&zbuffer, |_, _| HdrColor::default(), |f| f.color)[&(0, 1)]
+        };
+        let expected = resolve([0, 1, 2]);
+        assert_eq!(resolve([2, 0, 1]), expected);
+        assert_eq!(resolve([1, 2, 0]), expected);
+        // Nearest layer last: 0.5 * 0.5 + 0.5 * (0.5 * 0 + 0.5 * (0.5 * 1))
+        assert!((expected.red - 0.375).abs() < 1e-6);
+
+        // At equal depths the later draw goes on top, whichever arrives first
+        let tied = |first: Fragment, second: Fragment| {
+            let mut abuffer = ABuffer::new(2);
+            abuffer.push(0, 0, first);
+            abuffer.push(0, 0, second);
+            abuffer.resolve(&zbuffer, |_, _| HdrColor::default(), |f| f.color)[&(0, 0)]
+        };
+        let earlier = fragment(1.0, 1.0);
+        let later = Fragment {
+            order: (1, 0),
+            ..fragment(1.0, 0.0)
+        };
+        assert_eq!(tied(earlier, later), tied(later, earlier));
+        assert!((tied(earlier, later).red - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fragments_behind_opaque_surfaces_are_dropped() {
+        let mut zbuffer = Dynamic2D::fill(f64::NEG_INFINITY, 2, 2);
+        zbuffer[0][0] = 5.0;
+        let mut abuffer = ABuffer::new(2);
+        abuffer.push(0, 0, fragment(1.0, 1.0));
+        let resolved = abuffer.resolve(&zbuffer, |_, _| HdrColor::default(), |f| f.color);
+        assert!(resolved.is_empty());
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    collections::HashMap,
+    io::{self, ErrorKind},
+    process::Command,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    color::HdrColor,
+    matrix::{Dynamic2D, ParallelGrid},
+    parser, Color,
+};
+
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum BackdropFit {
+    /// Scaled to cover the whole screen, ignoring its aspect ratio.
+    #[default]
+    Stretch,
+    /// Repeated at its own size from the top left corner.
+    Tile,
+}
+
+/// What shows through wherever nothing was drawn. Positions are in screen pixels, with y up.
+#[derive(Clone, Debug)]
+pub enum Background {
+    Solid(HdrColor),
+    /// Blends from `top` at the top edge of the screen to `bottom` at the bottom edge.
+    Gradient {
+        top: HdrColor,
+        bottom: HdrColor,
+    },
+    /// An image file, with rows stored top first as they were read.
+    Image {
+        pixels: Arc<Dynamic2D<HdrColor>>,
+        fit: BackdropFit,
+    },
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Solid(Default::default())
+    }
+}
+
+impl Background {
+    pub fn gradient(top: Color, bottom: Color) -> Self {
+        Background::Gradient {
+            top: top.into(),
+            bottom: bottom.into(),
+        }
+    }
+
+    /// Reads any format `convert` understands, unless `cache` already holds the file.
+    pub fn load(filename: &str, fit: BackdropFit, cache: &ImageCache) -> io::Result<Self> {
+        let mut images = cache.0.lock().unwrap();
+        let pixels = match images.get(filename) {
+            Some(pixels) => pixels.clone(),
+            None => {
+                let pixels = Arc::new(decode(filename)?);
+                images.insert(filename.to_string(), pixels.clone());
+                pixels
+            }
+        };
+        Ok(Background::Image { pixels, fit })
+    }
+
+    pub fn sample(&self, x: f64, y: f64) -> HdrColor {
+        let size = parser::SCREEN_SIZE as f64;
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { top, bottom } => {
+                let t = (y / size).clamp(0.0, 1.0);
+                bottom.scale(1.0 - t) + top.scale(t)
+            }
+            Background::Image { pixels, fit } => {
+                let (width, height) = (pixels.get_width(), pixels.get_height());
+                let from_top = size - y;
+                let (col, row) = match fit {
+                    BackdropFit::Stretch => (
+                        (x / size * width as f64) as usize,
+                        (from_top / size * height as f64) as usize,
+                    ),
+                    BackdropFit::Tile => (
+                        x.rem_euclid(width as f64) as usize,
+                        from_top.rem_euclid(height as f64) as usize,
+                    ),
+                };
+                pixels[row.min(height - 1)][col.min(width - 1)]
+            }
+        }
+    }
+}
+
+/// Images already decoded for backdrops, by filename. Clones share the same images, so the
+/// frames of an animation run `convert` once per file rather than once each.
+#[derive(Clone, Debug, Default)]
+pub struct ImageCache(Arc<Mutex<HashMap<String, Arc<Dynamic2D<HdrColor>>>>>);
+
+fn decode(filename: &str) -> io::Result<Dynamic2D<HdrColor>> {
+    let output = Command::new("convert")
+        .args([filename, "-depth", "8", "-compress", "none", "ppm:-"])
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "convert could not read {}",
+            filename
+        )));
+    }
+    parse_ppm(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parses a plain-text (P3) portable pixmap.
+fn parse_ppm(text: &str) -> io::Result<Dynamic2D<HdrColor>> {
+    let invalid = |message: &str| io::Error::new(ErrorKind::InvalidData, message.to_string());
+    let mut tokens = text
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .flat_map(str::split_whitespace);
+    if tokens.next() != Some("P3") {
+        return Err(invalid("Expected a P3 pixmap"));
+    }
+    let mut header = tokens.by_ref().take(3).map(str::parse::<usize>);
+    let (Some(Ok(width)), Some(Ok(height)), Some(Ok(max))) =
+        (header.next(), header.next(), header.next())
+    else {
+        return Err(invalid("Malformed pixmap header"));
+    };
+    if width == 0 || height == 0 || max == 0 {
+        return Err(invalid("Empty pixmap"));
+    }
+
+    let values: Vec<usize> = tokens
+        .take(width * height * 3)
+        .map(str::parse::<usize>)
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid("Malformed pixmap data"))?;
+    if values.len() < width * height * 3 {
+        return Err(invalid("Pixmap ended early"));
+    }
+    let channel = |v: usize| (v * 255 / max).min(255) as u8;
+    Ok(Dynamic2D::from(
+        values
+            .chunks(width * 3)
+            .map(|row| {
+                row.chunks(3)
+                    .map(|rgb| Color::new(channel(rgb[0]), channel(rgb[1]), channel(rgb[2])).into())
+                    .collect()
+            })
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{parse_ppm, BackdropFit, Background};
+    use crate::{
+        color::{color_constants, HdrColor},
+        parser,
+    };
+
+    #[test]
+    fn gradient_runs_top_to_bottom() {
+        let background = Background::gradient(color_constants::WHITE, color_constants::BLACK);
+        let size = parser::SCREEN_SIZE as f64;
+        assert_eq!(background.sample(0.0, size), color_constants::WHITE.into());
+        assert_eq!(background.sample(0.0, 0.0), HdrColor::default());
+    }
+
+    #[test]
+    fn image_backdrop_is_upright() {
+        // Red over blue, as a 1x2 image
+        let pixels = parse_ppm("P3\n# comment\n1 2\n255\n255 0 0\n0 0 255\n").unwrap();
+        let size = parser::SCREEN_SIZE as f64;
+        for (fit, (upper, lower)) in [
+            (BackdropFit::Stretch, (size * 0.75, size * 0.25)),
+            (BackdropFit::Tile, (size - 0.5, size - 1.5)),
+        ] {
+            let background = Background::Image {
+                pixels: Arc::new(pixels.clone()),
+                fit,
+            };
+            assert_eq!(background.sample(0.0, upper).red, 1.0, "{:?}", fit);
+            assert_eq!(background.sample(0.0, lower).blue, 1.0, "{:?}", fit);
+        }
+        assert!(parse_ppm("P3 2 2 255 0 0 0").is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::Vector3D;
+
+/// Smallest w a vertex may have before it is considered to be behind the eye.
+const NEAR_W: f64 = 1e-5;
+
+/// A vertex in homogeneous clip space, along with the attributes that have to be
+/// interpolated when an edge is cut by a clipping plane.
+#[derive(Clone, Copy, Debug)]
+pub struct ClipVertex {
+    pub position: (f64, f64, f64, f64),
+    pub normal: Vector3D,
+    /// The position after the perspective divide. Lights are placed in these screen
+    /// coordinates, so those that depend on distance measure from here.
+    pub projected: Vector3D,
+    /// Where the vertex sat before its shape was transformed, for procedural textures.
+    pub object: Vector3D,
+}
+
+impl ClipVertex {
+    pub fn new(position: (f64, f64, f64, f64), normal: Vector3D) -> Self {
+        let (x, y, z, w) = position;
+        let projected = Vector3D::new(x / w, y / w, z / w);
+        Self {
+            position,
+            normal,
+            projected,
+            object: projected,
+        }
+    }
+
+    fn lerp(&self, other: &Self, t: f64) -> Self {
+        let (p0, p1) = (self.position, other.position);
+        Self {
+            position: (
+                p0.0 + (p1.0 - p0.0) * t,
+                p0.1 + (p1.1 - p0.1) * t,
+                p0.2 + (p1.2 - p0.2) * t,
+                p0.3 + (p1.3 - p0.3) * t,
+            ),
+            normal: self.normal.scale(1.0 - t) + other.normal.scale(t),
+            projected: self.projected.scale(1.0 - t) + other.projected.scale(t),
+            object: self.object.scale(1.0 - t) + other.object.scale(t),
+        }
+    }
+}
+
+/// The visible volume of an image: `0 <= x / w <= width`, `0 <= y / w <= height` and `w > 0`.
+/// Depth is left unbounded, matching the z-buffer.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    width: f64,
+    height: f64,
+}
+
+impl Frustum {
+    pub fn new(width: f64, height: f64) -> Self {
+        Self { width, height }
+    }
+
+    /// Signed distances to each plane; a vertex is inside when all of them are non-negative.
+    fn distances(&self, v: &ClipVertex) -> [f64; 5] {
+        let (x, y, _z, w) = v.position;
+        [w - NEAR_W, x, self.width * w - x, y, self.height * w - y]
+    }
+
+    /// Sutherland–Hodgman clipping of a triangle, returned as a convex polygon fan.
+    /// An empty result means the triangle is entirely outside.
+    pub fn clip_triangle(&self, triangle: [ClipVertex; 3]) -> Vec<ClipVertex> {
+        let distances = triangle.map(|v| self.distances(&v));
+        if (0..5).any(|plane| distances.iter().all(|d| d[plane] < 0.0)) {
+            return Vec::new();
+        }
+        if distances.iter().all(|d| d.iter().all(|dist| *dist >= 0.0)) {
+            return triangle.to_vec();
+        }
+
+        let mut polygon = triangle.to_vec();
+        for plane in 0..5 {
+            if polygon.is_empty() {
+                break;
+            }
+            let input = std::mem::take(&mut polygon);
+            for (i, curr) in input.iter().enumerate() {
+                let next = &input[(i + 1) % input.len()];
+                let d_curr = self.distances(curr)[plane];
+                let d_next = self.distances(next)[plane];
+
+                if d_curr >= 0.0 {
+                    polygon.push(*curr);
+                }
+                if (d_curr >= 0.0) != (d_next >= 0.0) {
+                    polygon.push(curr.lerp(next, d_curr / (d_curr - d_next)));
+                }
+            }
+        }
+        polygon
+    }
+
+    /// Liang–Barsky clipping of a segment, carried out in homogeneous space so that the near
+    /// plane is handled the same way as the image edges.
+    pub fn clip_line(&self, p0: ClipVertex, p1: ClipVertex) -> Option<(ClipVertex, ClipVertex)> {
+        let d0 = self.distances(&p0);
+        let d1 = self.distances(&p1);
+
+        let mut t_enter: f64 = 0.0;
+        let mut t_exit: f64 = 1.0;
+        for plane in 0..5 {
+            let (a, b) = (d0[plane], d1[plane]);
+            if a < 0.0 && b < 0.0 {
+                return None;
+            }
+            if a < 0.0 {
+                t_enter = t_enter.max(a / (a - b));
+            } else if b < 0.0 {
+                t_exit = t_exit.min(a / (a - b));
+            }
+        }
+
+        if t_enter > t_exit {
+            None
+        } else {
+            Some((p0.lerp(&p1, t_enter), p0.lerp(&p1, t_exit)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClipVertex, Frustum};
+    use crate::Vector3D;
+
+    fn vertex(x: f64, y: f64, w: f64) -> ClipVertex {
+        ClipVertex::new((x, y, 0.0, w), Vector3D::new(0.0, 0.0, 1.0))
+    }
+
+    #[test]
+    fn triangle_crossing_edge_becomes_quad() {
+        let frustum = Frustum::new(100.0, 100.0);
+        let clipped = frustum.clip_triangle([
+            vertex(50.0, 10.0, 1.0),
+            vertex(150.0, 10.0, 1.0),
+            vertex(50.0, 60.0, 1.0),
+        ]);
+        assert_eq!(clipped.len(), 4);
+        assert!(clipped.iter().all(|v| v.position.0 <= 100.0 + 1e-9));
+    }
+
+    #[test]
+    fn triangle_outside_is_dropped() {
+        let frustum = Frustum::new(100.0, 100.0);
+        let clipped = frustum.clip_triangle([
+            vertex(-50.0, 10.0, 1.0),
+            vertex(-10.0, 10.0, 1.0),
+            vertex(-30.0, 60.0, 1.0),
+        ]);
+        assert!(clipped.is_empty());
+    }
+
+    #[test]
+    fn line_behind_eye_is_cut_at_near_plane() {
+        let frustum = Frustum::new(100.0, 100.0);
+        let (p0, p1) = frustum
+            .clip_line(vertex(0.0, 0.0, 1.0), vertex(0.0, 0.0, -1.0))
+            .expect("Line partially in front of the eye was rejected");
+        assert_eq!(p0.position.3, 1.0);
+        assert!(p1.position.3 > 0.0 && p1.position.3 < 1e-3);
+        assert!(frustum
+            .clip_line(vertex(10.0, 10.0, -1.0), vertex(20.0, 10.0, -2.0))
+            .is_none());
+    }
+}
@@ -144,6 +144,104 @@ impl MulAssign<(f64, f64, f64)> for Color {
     }
 }
 
+/// Decodes an sRGB channel in `[0, 1]` into linear light.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a linear channel in `[0, 1]` with the sRGB transfer curve.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// A linear color with unbounded channels, where 1.0 is the brightest value an 8-bit
+/// `Color` can show. Light contributions are summed in this form and only squeezed back
+/// into a `Color` by a `ToneMapper` when the image is written out.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HdrColor {
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+}
+
+impl HdrColor {
+    pub fn new(red: f32, green: f32, blue: f32) -> Self {
+        Self { red, green, blue }
+    }
+
+    pub fn scale(&self, factor: f64) -> Self {
+        Self {
+            red: self.red * factor as f32,
+            green: self.green * factor as f32,
+            blue: self.blue * factor as f32,
+        }
+    }
+}
+
+/// 8-bit colors are treated as sRGB, the way every other tool stores them, and are
+/// decoded to linear light so they can be lit, blended and filtered correctly.
+impl From<Color> for HdrColor {
+    fn from(color: Color) -> Self {
+        Self {
+            red: srgb_to_linear(color.red as f32 / 255.0),
+            green: srgb_to_linear(color.green as f32 / 255.0),
+            blue: srgb_to_linear(color.blue as f32 / 255.0),
+        }
+    }
+}
+
+impl fmt::Display for HdrColor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.red, self.green, self.blue)
+    }
+}
+
+impl Add for HdrColor {
+    type Output = HdrColor;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        HdrColor {
+            red: self.red + rhs.red,
+            green: self.green + rhs.green,
+            blue: self.blue + rhs.blue,
+        }
+    }
+}
+
+impl AddAssign for HdrColor {
+    fn add_assign(&mut self, rhs: Self) {
+        self.red += rhs.red;
+        self.green += rhs.green;
+        self.blue += rhs.blue;
+    }
+}
+
+impl Sum for HdrColor {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Default::default(), |sum, x| sum + x)
+    }
+}
+
+impl Mul<(f64, f64, f64)> for HdrColor {
+    type Output = HdrColor;
+
+    fn mul(self, rhs: (f64, f64, f64)) -> Self::Output {
+        HdrColor {
+            red: self.red * rhs.0 as f32,
+            green: self.green * rhs.1 as f32,
+            blue: self.blue * rhs.2 as f32,
+        }
+    }
+}
+
 pub mod color_constants {
     use super::Color;
 
@@ -5,10 +5,26 @@ use crate::matrix::Const2D;
 pub struct Bezier {
     coeff_x: Const2D<f64, 1, 4>,
     coeff_y: Const2D<f64, 1, 4>,
+    coeff_z: Const2D<f64, 1, 4>,
 }
 
 impl Bezier {
+    /// A cubic in the xy plane.
     pub fn new(p0: (f64, f64), p1: (f64, f64), p2: (f64, f64), p3: (f64, f64)) -> Self {
+        Self::new_3d(
+            (p0.0, p0.1, 0.0),
+            (p1.0, p1.1, 0.0),
+            (p2.0, p2.1, 0.0),
+            (p3.0, p3.1, 0.0),
+        )
+    }
+
+    pub fn new_3d(
+        p0: (f64, f64, f64),
+        p1: (f64, f64, f64),
+        p2: (f64, f64, f64),
+        p3: (f64, f64, f64),
+    ) -> Self {
         let bezier_mul = Const2D::from([
             [-1.0, 3.0, -3.0, 1.0],
             [3.0, -6.0, 3.0, 0.0],
@@ -19,6 +35,7 @@ impl Bezier {
         Self {
             coeff_x: &bezier_mul * &Const2D::from([[p0.0], [p1.0], [p2.0], [p3.0]]),
             coeff_y: &bezier_mul * &Const2D::from([[p0.1], [p1.1], [p2.1], [p3.1]]),
+            coeff_z: &bezier_mul * &Const2D::from([[p0.2], [p1.2], [p2.2], [p3.2]]),
         }
     }
 }
@@ -38,7 +55,10 @@ impl Parametric for Bezier {
             + self.coeff_y[3][0]
     }
 
-    fn z(&self, _t: f64) -> f64 {
-        0.0
+    fn z(&self, t: f64) -> f64 {
+        self.coeff_z[0][0] * t * t * t
+            + self.coeff_z[1][0] * t * t
+            + self.coeff_z[2][0] * t
+            + self.coeff_z[3][0]
     }
 }
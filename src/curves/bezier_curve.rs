@@ -0,0 +1,54 @@
+use super::Parametric;
+use crate::Vector3D;
+
+/// A Bezier curve of any degree, one less than its number of control points, evaluated by
+/// de Casteljau's repeated interpolation.
+#[derive(Clone, Debug)]
+pub struct BezierCurve {
+    points: Vec<Vector3D>,
+}
+
+impl BezierCurve {
+    pub fn new(points: Vec<(f64, f64, f64)>) -> Self {
+        assert!(
+            points.len() >= 2,
+            "A Bezier curve needs at least two control points"
+        );
+        Self {
+            points: points.into_iter().map(Vector3D::from_point).collect(),
+        }
+    }
+
+    pub fn degree(&self) -> usize {
+        self.points.len() - 1
+    }
+
+    fn at(&self, t: f64) -> Vector3D {
+        let mut points = self.points.clone();
+        for level in (1..points.len()).rev() {
+            for i in 0..level {
+                points[i] = points[i].scale(1.0 - t) + points[i + 1].scale(t);
+            }
+        }
+        points[0]
+    }
+}
+
+impl Parametric for BezierCurve {
+    fn x(&self, t: f64) -> f64 {
+        self.at(t).x
+    }
+
+    fn y(&self, t: f64) -> f64 {
+        self.at(t).y
+    }
+
+    fn z(&self, t: f64) -> f64 {
+        self.at(t).z
+    }
+
+    fn f(&self, t: f64) -> (f64, f64, f64) {
+        let point = self.at(t);
+        (point.x, point.y, point.z)
+    }
+}
@@ -0,0 +1,136 @@
+use super::Parametric;
+use crate::Vector3D;
+
+/// A B-spline of any degree over a knot vector, evaluated by de Boor's algorithm. The
+/// parameter runs over the span of knots where the curve is fully defined.
+#[derive(Clone, Debug)]
+pub struct BSpline {
+    degree: usize,
+    points: Vec<Vector3D>,
+    knots: Vec<f64>,
+}
+
+impl BSpline {
+    /// A spline over any non-decreasing knot vector, which needs `degree + 1` more knots than
+    /// there are control points. Repeating the end knots `degree + 1` times pins the curve to
+    /// the first and last points.
+    pub fn new(degree: usize, points: Vec<(f64, f64, f64)>, knots: Vec<f64>) -> Self {
+        if let Err(problem) = Self::check(degree, points.len(), &knots) {
+            panic!("{}", problem);
+        }
+        Self {
+            degree,
+            points: points.into_iter().map(Vector3D::from_point).collect(),
+            knots,
+        }
+    }
+
+    /// A spline with evenly spaced knots. It runs near the control points without reaching the
+    /// first and last ones.
+    pub fn uniform(degree: usize, points: Vec<(f64, f64, f64)>) -> Self {
+        let knots = Self::uniform_knots(degree, points.len());
+        Self::new(degree, points, knots)
+    }
+
+    /// Evenly spaced knots for `points` control points.
+    pub fn uniform_knots(degree: usize, points: usize) -> Vec<f64> {
+        (0..points + degree + 1).map(|i| i as f64).collect()
+    }
+
+    /// Why a spline of this degree, number of control points and knots cannot be built, if it
+    /// cannot.
+    pub fn check(degree: usize, points: usize, knots: &[f64]) -> Result<(), String> {
+        if degree < 1 {
+            return Err("A B-spline needs a degree of at least 1".to_string());
+        }
+        if points <= degree {
+            return Err(format!(
+                "A B-spline of degree {} needs at least {} control points",
+                degree,
+                degree + 1
+            ));
+        }
+        if knots.len() != points + degree + 1 {
+            return Err(format!(
+                "A B-spline of degree {} with {} control points needs {} knots, not {}",
+                degree,
+                points,
+                points + degree + 1,
+                knots.len()
+            ));
+        }
+        if knots.windows(2).any(|pair| pair[0] > pair[1]) {
+            return Err("B-spline knots must never decrease".to_string());
+        }
+        if knots[degree] >= knots[points] {
+            return Err("A B-spline needs room between its knots".to_string());
+        }
+        Ok(())
+    }
+
+    /// Knot values where the parameter starts and ends.
+    fn domain(&self) -> (f64, f64) {
+        (self.knots[self.degree], self.knots[self.points.len()])
+    }
+
+    fn at(&self, t: f64) -> Vector3D {
+        let (start, end) = self.domain();
+        let u = start + (end - start) * t.clamp(0.0, 1.0);
+        let p = self.degree;
+        // The last knot span that starts at or before u, and is not empty
+        let span = (p..self.points.len())
+            .rev()
+            .find(|&k| self.knots[k] <= u && self.knots[k] < self.knots[k + 1])
+            .unwrap_or(p);
+
+        let mut d: Vec<Vector3D> = (0..=p).map(|j| self.points[j + span - p]).collect();
+        for r in 1..=p {
+            for j in (r..=p).rev() {
+                let i = j + span - p;
+                let (low, high) = (self.knots[i], self.knots[i + 1 + p - r]);
+                let alpha = if high > low {
+                    (u - low) / (high - low)
+                } else {
+                    0.0
+                };
+                d[j] = d[j - 1].scale(1.0 - alpha) + d[j].scale(alpha);
+            }
+        }
+        d[p]
+    }
+}
+
+impl Parametric for BSpline {
+    fn x(&self, t: f64) -> f64 {
+        self.at(t).x
+    }
+
+    fn y(&self, t: f64) -> f64 {
+        self.at(t).y
+    }
+
+    fn z(&self, t: f64) -> f64 {
+        self.at(t).z
+    }
+
+    fn f(&self, t: f64) -> (f64, f64, f64) {
+        let point = self.at(t);
+        (point.x, point.y, point.z)
+    }
+
+    /// Knots repeated `degree` times inside the domain, where the curve may turn sharply.
+    fn corners(&self) -> Vec<f64> {
+        let (start, end) = self.domain();
+        let mut corners: Vec<f64> = self
+            .knots
+            .iter()
+            .filter(|&&knot| knot > start && knot < end)
+            .filter(|&&knot| {
+                self.knots.iter().filter(|&&other| other == knot).count() >= self.degree
+            })
+            .map(|knot| (knot - start) / (end - start))
+            .collect();
+        corners.dedup();
+        corners
+    }
+}
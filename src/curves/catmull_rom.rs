@@ -0,0 +1,66 @@
+use super::Parametric;
+use crate::Vector3D;
+
+/// A smooth curve through every one of its points, leaving each along the line joining its
+/// neighbours. Each piece between points takes an equal share of the parameter.
+#[derive(Clone, Debug)]
+pub struct CatmullRom {
+    points: Vec<Vector3D>,
+}
+
+impl CatmullRom {
+    pub fn new(points: Vec<(f64, f64, f64)>) -> Self {
+        assert!(
+            points.len() >= 2,
+            "A Catmull-Rom spline needs at least two points"
+        );
+        Self {
+            points: points.into_iter().map(Vector3D::from_point).collect(),
+        }
+    }
+
+    fn at(&self, t: f64) -> Vector3D {
+        let last = self.points.len() - 1;
+        let pieces = last as f64;
+        let piece = ((t * pieces).floor().max(0.0) as usize).min(last - 1);
+        let u = t * pieces - piece as f64;
+
+        // Past either end, the curve carries on as if mirrored through its end point
+        let point = |i: isize| {
+            if i < 0 {
+                self.points[0].scale(2.0) - self.points[1]
+            } else if i as usize > last {
+                self.points[last].scale(2.0) - self.points[last - 1]
+            } else {
+                self.points[i as usize]
+            }
+        };
+        let i = piece as isize;
+        let (p0, p1, p2, p3) = (point(i - 1), point(i), point(i + 1), point(i + 2));
+        let (u2, u3) = (u * u, u * u * u);
+        (p1.scale(2.0)
+            + (p2 - p0).scale(u)
+            + (p0.scale(2.0) - p1.scale(5.0) + p2.scale(4.0) - p3).scale(u2)
+            + (p1.scale(3.0) - p0 - p2.scale(3.0) + p3).scale(u3))
+        .scale(0.5)
+    }
+}
+
+impl Parametric for CatmullRom {
+    fn x(&self, t: f64) -> f64 {
+        self.at(t).x
+    }
+
+    fn y(&self, t: f64) -> f64 {
+        self.at(t).y
+    }
+
+    fn z(&self, t: f64) -> f64 {
+        self.at(t).z
+    }
+
+    fn f(&self, t: f64) -> (f64, f64, f64) {
+        let point = self.at(t);
+        (point.x, point.y, point.z)
+    }
+}
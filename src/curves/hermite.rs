@@ -5,10 +5,26 @@ use crate::matrix::Const2D;
 pub struct Hermite {
     coeff_x: Const2D<f64, 1, 4>,
     coeff_y: Const2D<f64, 1, 4>,
+    coeff_z: Const2D<f64, 1, 4>,
 }
 
 impl Hermite {
+    /// A cubic in the xy plane, from `p0` to `p1`, leaving along `r0` and arriving along `r1`.
     pub fn new(p0: (f64, f64), p1: (f64, f64), r0: (f64, f64), r1: (f64, f64)) -> Self {
+        Self::new_3d(
+            (p0.0, p0.1, 0.0),
+            (p1.0, p1.1, 0.0),
+            (r0.0, r0.1, 0.0),
+            (r1.0, r1.1, 0.0),
+        )
+    }
+
+    pub fn new_3d(
+        p0: (f64, f64, f64),
+        p1: (f64, f64, f64),
+        r0: (f64, f64, f64),
+        r1: (f64, f64, f64),
+    ) -> Self {
         let hermite_inverse_solver = Const2D::from([
             [2.0, -2.0, 1.0, 1.0],
             [-3.0, 3.0, -2.0, -1.0],
@@ -19,6 +35,7 @@ impl Hermite {
         Self {
             coeff_x: &hermite_inverse_solver * &Const2D::from([[p0.0], [p1.0], [r0.0], [r1.0]]),
             coeff_y: &hermite_inverse_solver * &Const2D::from([[p0.1], [p1.1], [r0.1], [r1.1]]),
+            coeff_z: &hermite_inverse_solver * &Const2D::from([[p0.2], [p1.2], [r0.2], [r1.2]]),
         }
     }
 }
@@ -38,7 +55,10 @@ impl Parametric for Hermite {
             + self.coeff_y[3][0]
     }
 
-    fn z(&self, _t: f64) -> f64 {
-        0.0
+    fn z(&self, t: f64) -> f64 {
+        self.coeff_z[0][0] * t * t * t
+            + self.coeff_z[1][0] * t * t
+            + self.coeff_z[2][0] * t
+            + self.coeff_z[3][0]
     }
 }
@@ -9,3 +9,96 @@ pub use circle::Circle;
 
 mod hermite;
 pub use hermite::Hermite;
+
+mod polyline;
+pub use polyline::Polyline;
+
+mod bezier_curve;
+pub use bezier_curve::BezierCurve;
+
+mod bspline;
+pub use bspline::BSpline;
+
+mod catmull_rom;
+pub use catmull_rom::CatmullRom;
+
+#[cfg(test)]
+mod tests {
+    use super::{BSpline, Bezier, BezierCurve, CatmullRom, Parametric, Polyline};
+    use crate::Vector3D;
+
+    fn assert_close(a: (f64, f64, f64), b: (f64, f64, f64)) {
+        let distance = ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2) + (a.2 - b.2).powi(2)).sqrt();
+        assert!(distance < 1e-9, "{:?} {:?}", a, b);
+    }
+
+    #[test]
+    fn splines_agree_with_each_other() {
+        let points = vec![
+            (0.0, 0.0, 0.0),
+            (1.0, 2.0, -1.0),
+            (3.0, 2.0, 1.0),
+            (4.0, 0.0, 2.0),
+        ];
+        let cubic = Bezier::new_3d(points[0], points[1], points[2], points[3]);
+        let casteljau = BezierCurve::new(points.clone());
+        // A clamped B-spline with no inner knots is the Bezier curve on the same points
+        let clamped = BSpline::new(
+            3,
+            points.clone(),
+            vec![0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0],
+        );
+        for t in [0.0, 0.2, 0.5, 0.9, 1.0] {
+            assert_close(cubic.f(t), casteljau.f(t));
+            assert_close(cubic.f(t), clamped.f(t));
+        }
+
+        // Uniform cubic B-splines meet their knots at a sixth, two thirds and a sixth of the
+        // nearest control points
+        let uniform = BSpline::uniform(3, points.clone());
+        let blend = |a: f64, b: f64, c: f64| (a + 4.0 * b + c) / 6.0;
+        let (p0, p1, p2) = (points[0], points[1], points[2]);
+        assert_close(
+            uniform.f(0.0),
+            (
+                blend(p0.0, p1.0, p2.0),
+                blend(p0.1, p1.1, p2.1),
+                blend(p0.2, p1.2, p2.2),
+            ),
+        );
+
+        // Degree one with repeated end knots is the polyline through the points, corners and all
+        let linear = BSpline::new(1, points.clone(), vec![0.0, 0.0, 1.0, 2.0, 3.0, 3.0]);
+        assert_eq!(linear.corners(), vec![1.0 / 3.0, 2.0 / 3.0]);
+        let flat = Polyline::new(points.iter().map(|p| (p.0, p.1)).collect());
+        for t in [0.1, 0.5, 0.8] {
+            assert_close(linear.f(t), (flat.x(t), flat.y(t), linear.z(t)));
+        }
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_its_points() {
+        let points = vec![
+            (0.0, 0.0, 0.0),
+            (1.0, 2.0, 3.0),
+            (2.0, -1.0, 1.0),
+            (4.0, 0.0, 0.0),
+        ];
+        let spline = CatmullRom::new(points.clone());
+        for (i, point) in points.iter().enumerate() {
+            assert_close(spline.f(i as f64 / 3.0), *point);
+        }
+
+        // Flattening keeps the chords close to the curve between them
+        let tolerance = 0.01;
+        let parameters = spline.flatten(tolerance);
+        for pair in parameters.windows(2) {
+            let [a, b, middle] = [pair[0], pair[1], (pair[0] + pair[1]) / 2.0]
+                .map(|t| Vector3D::from_point(spline.f(t)));
+            let (chord, offset) = (b - a, middle - a);
+            let along = (offset.dot(&chord) / chord.dot(&chord)).clamp(0.0, 1.0);
+            let gap = (offset - chord.scale(along)).magnitude();
+            assert!(gap <= tolerance, "{}", gap);
+        }
+    }
+}
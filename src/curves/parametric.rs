@@ -1,3 +1,5 @@
+use crate::Vector3D;
+
 pub trait Parametric {
     fn x(&self, t: f64) -> f64;
     fn y(&self, t: f64) -> f64;
@@ -12,4 +14,55 @@ pub trait Parametric {
             .map(|i| -> (f64, f64, f64) { self.f(i as f64 / (n - 1) as f64) })
             .collect()
     }
+
+    /// Parameters where the curve turns sharply, which sampling should land on exactly.
+    fn corners(&self) -> Vec<f64> {
+        Vec::new()
+    }
+
+    /// Parameters from 0 to 1, close enough together that straight lines between their points
+    /// stay within `tolerance` of the curve. Corners are always among them.
+    fn flatten(&self, tolerance: f64) -> Vec<f64> {
+        // Every span is split a few times before trusting that it is straight, so an S bend
+        // whose middle happens to sit on its chord is not mistaken for a line
+        const MIN_DEPTH: usize = 2;
+        const MAX_DEPTH: usize = 10;
+
+        fn split(
+            curve: &(impl Parametric + ?Sized),
+            (t0, t1): (f64, f64),
+            tolerance: f64,
+            depth: usize,
+            out: &mut Vec<f64>,
+        ) {
+            let middle = (t0 + t1) / 2.0;
+            let (start, end) = (
+                Vector3D::from_point(curve.f(t0)),
+                Vector3D::from_point(curve.f(t1)),
+            );
+            let chord = end - start;
+            let offset = Vector3D::from_point(curve.f(middle)) - start;
+            let along = if chord.magnitude() > 0.0 {
+                offset.dot(&chord) / chord.dot(&chord)
+            } else {
+                0.0
+            };
+            let deviation = (offset - chord.scale(along.clamp(0.0, 1.0))).magnitude();
+            if depth < MIN_DEPTH || (deviation > tolerance && depth < MAX_DEPTH) {
+                split(curve, (t0, middle), tolerance, depth + 1, out);
+                split(curve, (middle, t1), tolerance, depth + 1, out);
+            } else {
+                out.push(t1);
+            }
+        }
+
+        let mut stops = vec![0.0];
+        stops.extend(self.corners().into_iter().filter(|t| *t > 0.0 && *t < 1.0));
+        stops.push(1.0);
+        let mut parameters = vec![0.0];
+        stops
+            .windows(2)
+            .for_each(|span| split(self, (span[0], span[1]), tolerance, 0, &mut parameters));
+        parameters
+    }
 }
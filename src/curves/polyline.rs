@@ -0,0 +1,49 @@
+use super::Parametric;
+
+/// Straight pieces through a list of points, each piece taking an equal share of the
+/// parameter.
+#[derive(Clone, Debug)]
+pub struct Polyline {
+    points: Vec<(f64, f64)>,
+}
+
+impl Polyline {
+    pub fn new(points: Vec<(f64, f64)>) -> Self {
+        assert!(points.len() >= 2, "A polyline needs at least two points");
+        Self { points }
+    }
+
+    /// The piece a parameter falls on, and how far along that piece it is.
+    fn locate(&self, t: f64) -> (usize, f64) {
+        let pieces = (self.points.len() - 1) as f64;
+        let piece = ((t * pieces).floor().max(0.0) as usize).min(self.points.len() - 2);
+        (piece, t * pieces - piece as f64)
+    }
+}
+
+impl Parametric for Polyline {
+    fn x(&self, t: f64) -> f64 {
+        let (piece, u) = self.locate(t);
+        self.points[piece].0 + (self.points[piece + 1].0 - self.points[piece].0) * u
+    }
+
+    fn y(&self, t: f64) -> f64 {
+        let (piece, u) = self.locate(t);
+        self.points[piece].1 + (self.points[piece + 1].1 - self.points[piece].1) * u
+    }
+
+    fn z(&self, _t: f64) -> f64 {
+        0.0
+    }
+
+    /// Every point between the ends, and the start too when the polyline closes on itself.
+    fn corners(&self) -> Vec<f64> {
+        let pieces = (self.points.len() - 1) as f64;
+        let closed = self.points.first() == self.points.last();
+        closed
+            .then_some(0.0)
+            .into_iter()
+            .chain((1..self.points.len() - 1).map(|i| i as f64 / pieces))
+            .collect()
+    }
+}
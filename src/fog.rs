@@ -0,0 +1,80 @@
+use crate::{color::HdrColor, Color};
+
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum FogMode {
+    /// Thickens evenly from `start` until nothing shows through at `end`.
+    #[default]
+    Linear,
+    /// Beer–Lambert falloff, letting about 5% through at `end` and thinning out from there.
+    Exponential,
+    /// Like `Exponential`, but stays clear for longer before closing in.
+    ExponentialSquared,
+}
+
+/// Blends surfaces towards a flat color by how far they sit behind the screen plane.
+///
+/// Depth is measured as `-z`, since larger z is closer to the viewer. Samples nothing was
+/// drawn to are left to the background.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fog {
+    pub mode: FogMode,
+    pub color: HdrColor,
+    pub start: f64,
+    pub end: f64,
+}
+
+impl Fog {
+    pub fn new(mode: FogMode, color: Color, start: f64, end: f64) -> Self {
+        Self {
+            mode,
+            color: color.into(),
+            start,
+            end,
+        }
+    }
+
+    /// How much of a surface at screen-space `z` makes it through the fog, from 0 to 1.
+    pub fn visibility(&self, z: f64) -> f64 {
+        let t = ((-z - self.start) / (self.end - self.start).max(f64::EPSILON)).max(0.0);
+        // -ln(0.05), so exponential fog is 95% opaque at the end distance
+        const DENSITY: f64 = 3.0;
+        match self.mode {
+            FogMode::Linear => 1.0 - t.min(1.0),
+            FogMode::Exponential => (-DENSITY * t).exp(),
+            FogMode::ExponentialSquared => (-DENSITY * t * t).exp(),
+        }
+    }
+
+    pub fn apply(&self, color: HdrColor, z: f64) -> HdrColor {
+        let visibility = self.visibility(z);
+        color.scale(visibility) + self.color.scale(1.0 - visibility)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fog, FogMode};
+    use crate::color::color_constants;
+
+    #[test]
+    fn clear_before_start() {
+        for mode in [
+            FogMode::Linear,
+            FogMode::Exponential,
+            FogMode::ExponentialSquared,
+        ] {
+            let fog = Fog::new(mode, color_constants::WHITE, 100.0, 300.0);
+            assert_eq!(fog.visibility(-50.0), 1.0);
+            assert_eq!(fog.visibility(f64::NEG_INFINITY), 0.0);
+            assert!(fog.visibility(-150.0) > fog.visibility(-250.0));
+        }
+    }
+
+    #[test]
+    fn linear_fog_is_opaque_at_end() {
+        let fog = Fog::new(FogMode::Linear, color_constants::WHITE, 100.0, 300.0);
+        assert!((fog.visibility(-200.0) - 0.5).abs() < 1e-9);
+        assert_eq!(fog.visibility(-300.0), 0.0);
+        assert_eq!(fog.visibility(-1000.0), 0.0);
+    }
+}
@@ -7,59 +7,143 @@ use std::{
     sync::RwLock,
 };
 
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
 
 use crate::{
-    lighter::LightingConfig,
-    matrix::{Const2D, Dynamic2D, EdgeMatrix, ParallelGrid, PolygonMatrix},
-    parser, Color, Lighter, Vector3D,
+    abuffer::{ABuffer, Fragment},
+    background::Background,
+    clip::{ClipVertex, Frustum},
+    color::HdrColor,
+    fog::Fog,
+    lighter::Material,
+    matrix::{Dynamic2D, EdgeMatrix, Mesh, ParallelGrid},
+    outline::OutlineSettings,
+    parser,
+    sampling::Sampler,
+    ssao::{Ssao, SsaoSettings},
+    Color, Lighter, ToneMapper, Vector3D,
 };
 
 const TESTDIR: &str = "test_images/";
 #[derive(Clone, Debug)]
 pub struct Image<const WIDTH: usize, const HEIGHT: usize> {
     name: Option<String>,
-    data: Box<Const2D<Color, WIDTH, HEIGHT>>,
+    data: Dynamic2D<HdrColor>,
     zbuffer: Dynamic2D<f64>,
     lighter: Lighter,
+    tone_mapper: ToneMapper,
+    sampler: Sampler,
+    fog: Option<Fog>,
+    background: Background,
+    ssao: Option<Ssao>,
+    outline: Option<OutlineSettings>,
+    /// Unit normal of every opaque sample, only kept while SSAO or outlines need it.
+    normals: Vec<Vector3D>,
+    abuffer: ABuffer,
 }
 
 impl Image<{ parser::FINAL_SCREEN_SIZE }, { parser::FINAL_SCREEN_SIZE }> {
+    /// Resolves the supersampled image to screen size, weighting every sample by the
+    /// reconstruction filter centered on the output pixel it contributes to. Ambient occlusion,
+    /// outlines and fog are laid over each sample beforehand, while its depth is still known, the
+    /// background fills in every sample nothing was drawn to, and transparent surfaces are
+    /// blended over the result.
     pub fn downsample(&self) -> Image<{ parser::SCREEN_SIZE }, { parser::SCREEN_SIZE }> {
         let mut result: Image<{ parser::SCREEN_SIZE }, { parser::SCREEN_SIZE }> =
             Default::default();
+        let scale = parser::SAMPLE_SCALE as usize;
+        let reach = self.sampler.get_radius().ceil() as usize;
+        let occluded = self
+            .ssao
+            .as_ref()
+            .map(|ssao| ssao.occluded_ambient(&self.zbuffer, &self.normals));
+        let edges = self
+            .outline
+            .as_ref()
+            .map(|outline| outline.edges(&self.zbuffer, &self.normals));
+        let opaque = |i, j| self.opaque_sample(i, j, occluded.as_ref(), edges.as_ref());
+        let layered = self.abuffer.resolve(&self.zbuffer, opaque, |fragment| {
+            self.fogged(fragment.color, fragment.z)
+        });
 
-        for r in 0..parser::SCREEN_SIZE {
-            for c in 0..parser::SCREEN_SIZE {
-                let mut ravg = 0.0;
-                let mut gavg = 0.0;
-                let mut bavg = 0.0;
-                for i in
-                    (r * parser::SAMPLE_SCALE as usize)..((r + 1) * parser::SAMPLE_SCALE as usize)
-                {
-                    for j in (c * parser::SAMPLE_SCALE as usize)
-                        ..((c + 1) * parser::SAMPLE_SCALE as usize)
+        (&mut result.data)
+            .into_par_iter()
+            .enumerate()
+            .for_each(|(r, row)| {
+                for (c, pixel) in row.iter_mut().enumerate() {
+                    let center = (
+                        (c as f64 + 0.5) * parser::SAMPLE_SCALE,
+                        (r as f64 + 0.5) * parser::SAMPLE_SCALE,
+                    );
+                    let mut sum: HdrColor = Default::default();
+                    let mut total_weight = 0.0;
+                    for i in r.saturating_sub(reach) * scale
+                        ..cmp::min(r + reach + 1, parser::SCREEN_SIZE) * scale
                     {
-                        ravg += self[i][j].red as f64;
-                        gavg += self[i][j].green as f64;
-                        bavg += self[i][j].blue as f64;
+                        for j in c.saturating_sub(reach) * scale
+                            ..cmp::min(c + reach + 1, parser::SCREEN_SIZE) * scale
+                        {
+                            let (sx, sy) = self.sampler.position(j, i);
+                            let weight = self.sampler.weight(
+                                (sx - center.0) / parser::SAMPLE_SCALE,
+                                (sy - center.1) / parser::SAMPLE_SCALE,
+                            );
+                            if weight != 0.0 {
+                                // Most scenes have nothing transparent, so skip the lookup
+                                let color = if layered.is_empty() {
+                                    opaque(i, j)
+                                } else {
+                                    match layered.get(&(i, j)) {
+                                        Some(color) => *color,
+                                        None => opaque(i, j),
+                                    }
+                                };
+                                sum += color.scale(weight);
+                                total_weight += weight;
+                            }
+                        }
+                    }
+                    if total_weight != 0.0 {
+                        *pixel = sum.scale(1.0 / total_weight);
                     }
                 }
-                ravg /= parser::SAMPLE_SCALE * parser::SAMPLE_SCALE;
-                gavg /= parser::SAMPLE_SCALE * parser::SAMPLE_SCALE;
-                bavg /= parser::SAMPLE_SCALE * parser::SAMPLE_SCALE;
-
-                ravg = f64::min(ravg, 255.0);
-                gavg = f64::min(gavg, 255.0);
-                bavg = f64::min(bavg, 255.0);
-                result[r][c] = Color {
-                    red: ravg as u8,
-                    green: gavg as u8,
-                    blue: bavg as u8,
-                };
+            });
+        result.tone_mapper = self.tone_mapper;
+        result
+    }
+
+    /// The color of sample `(j, i)` before any transparent surfaces go over it.
+    fn opaque_sample(
+        &self,
+        i: usize,
+        j: usize,
+        occluded: Option<&Dynamic2D<HdrColor>>,
+        edges: Option<&Dynamic2D<bool>>,
+    ) -> HdrColor {
+        let z = self.zbuffer[i][j];
+        if z == f64::NEG_INFINITY {
+            let (sx, sy) = self.sampler.position(j, i);
+            return self
+                .background
+                .sample(sx / parser::SAMPLE_SCALE, sy / parser::SAMPLE_SCALE);
+        }
+        let mut color = self[i][j];
+        if let Some(occluded) = occluded {
+            color += occluded[i][j].scale(-1.0);
+        }
+        if let (Some(edges), Some(outline)) = (edges, &self.outline) {
+            if edges[i][j] {
+                color = outline.color;
             }
         }
-        result
+        self.fogged(color, z)
+    }
+
+    fn fogged(&self, color: HdrColor, z: f64) -> HdrColor {
+        match &self.fog {
+            Some(fog) => fog.apply(color, z / parser::SAMPLE_SCALE),
+            None => color,
+        }
     }
 }
 
@@ -67,31 +151,152 @@ impl<const WIDTH: usize, const HEIGHT: usize> Default for Image<WIDTH, HEIGHT> {
     fn default() -> Self {
         Self {
             name: None,
-            data: Default::default(),
+            data: Dynamic2D::new(WIDTH, HEIGHT),
             zbuffer: Dynamic2D::fill(f64::NEG_INFINITY, WIDTH, HEIGHT),
             lighter: Default::default(),
+            tone_mapper: Default::default(),
+            sampler: Default::default(),
+            fog: None,
+            background: Default::default(),
+            ssao: None,
+            outline: None,
+            normals: Vec::new(),
+            abuffer: ABuffer::new(WIDTH),
         }
     }
 }
 
-fn dist(p0: (f64, f64, f64), p1: (f64, f64, f64)) -> f64 {
-    ((p1.0 - p0.0) * (p1.0 - p0.0) + (p1.1 - p0.1) * (p1.1 - p0.1) + (p1.2 - p0.2) * (p1.2 - p0.2))
-        .sqrt()
+/// A triangle corner after the perspective divide. The reciprocal of the clip-space w is kept
+/// so attributes can be interpolated linearly in world space rather than in screen space.
+#[derive(Clone, Copy, Debug)]
+struct ScreenVertex {
+    x: f64,
+    y: f64,
+    z: f64,
+    inv_w: f64,
+    normal: Vector3D,
+    projected: Vector3D,
+    object: Vector3D,
+}
+
+impl ScreenVertex {
+    fn from_clip(v: &ClipVertex) -> Self {
+        let (x, y, z, w) = v.position;
+        let inv_w = 1.0 / w;
+        Self {
+            x: x * inv_w * parser::SAMPLE_SCALE,
+            y: y * inv_w * parser::SAMPLE_SCALE,
+            z: z * parser::SAMPLE_SCALE,
+            inv_w,
+            normal: v.normal,
+            projected: v.projected,
+            object: v.object,
+        }
+    }
+
+    /// Converts screen-space barycentric coordinates into weights for interpolating
+    /// attributes that vary linearly before projection.
+    fn perspective_weights(v: &[ScreenVertex], bary: [f64; 3]) -> [f64; 3] {
+        let corrected = [
+            bary[0] * v[0].inv_w,
+            bary[1] * v[1].inv_w,
+            bary[2] * v[2].inv_w,
+        ];
+        let total: f64 = corrected.iter().sum();
+        corrected.map(|b| b / total)
+    }
+}
+
+fn edge_function(a: &ScreenVertex, b: &ScreenVertex, p: (f64, f64)) -> f64 {
+    (b.x - a.x) * (p.1 - a.y) - (b.y - a.y) * (p.0 - a.x)
+}
+
+/// Rasterizes a triangle into a bare z-buffer, keeping the largest `z / w` at each texel
+/// center. Used by passes that need visibility from somewhere other than the screen.
+pub(crate) fn rasterize_depth(depth: &mut Dynamic2D<f64>, triangle: [ClipVertex; 3]) {
+    let (width, height) = (depth.get_width(), depth.get_height());
+    let v: Vec<ScreenVertex> = Frustum::new(width as f64, height as f64)
+        .clip_triangle(triangle)
+        .iter()
+        .map(|c| {
+            let (x, y, z, w) = c.position;
+            ScreenVertex {
+                x: x / w,
+                y: y / w,
+                z: z / w,
+                inv_w: 1.0 / w,
+                normal: c.normal,
+                projected: c.projected,
+                object: c.object,
+            }
+        })
+        .collect();
+
+    (1..v.len().saturating_sub(1)).for_each(|i| {
+        let tri = [v[0], v[i], v[i + 1]];
+        let area = edge_function(&tri[0], &tri[1], (tri[2].x, tri[2].y));
+        if area == 0.0 {
+            return;
+        }
+        let xmin = tri
+            .iter()
+            .map(|p| p.x)
+            .fold(f64::INFINITY, f64::min)
+            .max(0.0) as usize;
+        let xmax =
+            (tri.iter().map(|p| p.x).fold(f64::NEG_INFINITY, f64::max) as usize).min(width - 1);
+        let ymin = tri
+            .iter()
+            .map(|p| p.y)
+            .fold(f64::INFINITY, f64::min)
+            .max(0.0) as usize;
+        let ymax =
+            (tri.iter().map(|p| p.y).fold(f64::NEG_INFINITY, f64::max) as usize).min(height - 1);
+
+        for y in ymin..=ymax {
+            for x in xmin..=xmax {
+                let sample = (x as f64 + 0.5, y as f64 + 0.5);
+                let bary = [
+                    edge_function(&tri[1], &tri[2], sample) / area,
+                    edge_function(&tri[2], &tri[0], sample) / area,
+                    edge_function(&tri[0], &tri[1], sample) / area,
+                ];
+                if bary.iter().any(|b| *b < 0.0) {
+                    continue;
+                }
+                // z / w is affine in screen space, so plain barycentrics are exact here
+                let z = tri.iter().zip(bary).map(|(p, b)| p.z * b).sum::<f64>();
+                if z > depth[y][x] {
+                    depth[y][x] = z;
+                }
+            }
+        }
+    });
 }
 
 #[derive(Clone, Copy, Debug, Hash)]
 pub enum ShadingMethod {
     Flat,
     Phong,
+    /// Cel shading with this many lit steps; see `Lighter::calculate_toon`.
+    Toon(usize),
 }
 
 impl<const WIDTH: usize, const HEIGHT: usize> Image<WIDTH, HEIGHT> {
     pub fn new(name: String) -> Self {
         Image {
             name: Some(name),
-            data: Default::default(),
+            data: Dynamic2D::new(WIDTH, HEIGHT),
             zbuffer: Dynamic2D::fill(f64::NEG_INFINITY, WIDTH, HEIGHT),
             lighter: Default::default(),
+            tone_mapper: Default::default(),
+            sampler: Default::default(),
+            fog: None,
+            background: Default::default(),
+            ssao: None,
+            outline: None,
+            normals: Vec::new(),
+            abuffer: ABuffer::new(WIDTH),
         }
     }
 
@@ -113,8 +318,51 @@ impl<const WIDTH: usize, const HEIGHT: usize> Image<WIDTH, HEIGHT> {
     }
 
     pub fn clear_shapes_only(&mut self) {
-        self.data = Default::default();
+        self.data = Dynamic2D::new(WIDTH, HEIGHT);
         self.zbuffer = Dynamic2D::fill(f64::NEG_INFINITY, WIDTH, HEIGHT);
+        if let Some(ssao) = &mut self.ssao {
+            ssao.clear();
+        }
+        self.normals.fill(Vector3D::new(0.0, 0.0, 1.0));
+        self.abuffer.clear();
+    }
+
+    pub fn set_tone_mapper(&mut self, tone_mapper: ToneMapper) {
+        self.tone_mapper = tone_mapper;
+    }
+
+    pub fn set_fog(&mut self, fog: Option<Fog>) {
+        self.fog = fog;
+    }
+
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
+    }
+
+    /// Turns screen-space ambient occlusion on or off. Only samples drawn while it is on
+    /// record what occlusion needs, so it should be set before any geometry.
+    pub fn set_ssao(&mut self, settings: Option<SsaoSettings>) {
+        self.ssao = settings.map(|settings| Ssao::new(settings, WIDTH, HEIGHT));
+        self.keep_normals();
+    }
+
+    /// Turns silhouette and crease lines on or off. Like SSAO, it only sees samples drawn
+    /// after it is turned on.
+    pub fn set_outline(&mut self, settings: Option<OutlineSettings>) {
+        self.outline = settings;
+        self.keep_normals();
+    }
+
+    fn keep_normals(&mut self) {
+        if self.ssao.is_none() && self.outline.is_none() {
+            self.normals = Vec::new();
+        } else if self.normals.is_empty() {
+            self.normals = vec![Vector3D::new(0.0, 0.0, 1.0); WIDTH * HEIGHT];
+        }
+    }
+
+    pub fn get_sampler(&mut self) -> &mut Sampler {
+        &mut self.sampler
     }
 
     pub fn clear_lighter(&mut self) {
@@ -140,7 +388,7 @@ impl<const WIDTH: usize, const HEIGHT: usize> Image<WIDTH, HEIGHT> {
     }
 
     pub fn save_name(&self, filename: &str) -> io::Result<()> {
-        fs::create_dir_all(&filename.rsplit_once('/').unwrap_or((".", "")).0)?;
+        fs::create_dir_all(filename.rsplit_once('/').unwrap_or((".", "")).0)?;
 
         let convert_syntax = format!("convert -resize 500x500 - {}", &filename);
         let mut convert_command = Command::new("sh")
@@ -175,221 +423,220 @@ impl<const WIDTH: usize, const HEIGHT: usize> Image<WIDTH, HEIGHT> {
     }
 
     pub fn draw_matrix(&mut self, matrix: &EdgeMatrix, c: Color) {
+        let frustum = Frustum::new(
+            (self.get_width() - 1) as f64,
+            (self.get_height() - 1) as f64,
+        );
         matrix.into_iter().for_each(|(p0, p1)| {
-            self.draw_line(
-                (p0.0 as i32, p0.1 as i32, 0.0),
-                (p1.0 as i32, p1.1 as i32, 0.0),
-                c,
-            );
+            if let Some((p0, p1)) = frustum.clip_line(
+                ClipVertex::new(p0, Vector3D::new(0.0, 0.0, 0.0)),
+                ClipVertex::new(p1, Vector3D::new(0.0, 0.0, 0.0)),
+            ) {
+                let (x0, y0, _z0, w0) = p0.position;
+                let (x1, y1, _z1, w1) = p1.position;
+                self.walk_line(
+                    ((x0 / w0).round() as i32, (y0 / w0).round() as i32, 0.0),
+                    ((x1 / w1).round() as i32, (y1 / w1).round() as i32, 0.0),
+                    c,
+                );
+            }
         });
     }
 
-    pub fn draw_polygons(
-        &mut self,
-        matrix: &PolygonMatrix,
-        light_conf: &LightingConfig,
-        shading: ShadingMethod,
-    ) {
+    pub fn draw_polygons(&mut self, mesh: &Mesh, material: &Material, shading: ShadingMethod) {
         let lighter = self.lighter.clone();
+        let frustum = Frustum::new(
+            self.get_width() as f64 / parser::SAMPLE_SCALE,
+            self.get_height() as f64 / parser::SAMPLE_SCALE,
+        );
+        let draw = self.abuffer.start_draw();
         let image_rwlock = RwLock::new(self);
-        matrix
-            .into_par_iter()
-            .filter(|(_points, normal)| -> bool {
+        mesh.par_triangles()
+            .enumerate()
+            .filter(|(_, (_corners, normal))| -> bool {
                 normal.dot(&Vector3D::new(0.0, 0.0, 1.0)) >= 0.0
             })
-            .for_each(|(points, normal)| {
-                let c = lighter.calculate(&normal, light_conf);
-
-                let mut v = points;
-                for mut point in &mut v {
-                    point.0 *= parser::SAMPLE_SCALE;
-                    point.1 *= parser::SAMPLE_SCALE;
-                    point.2 *= parser::SAMPLE_SCALE;
-                }
-                // Sort by y value
-                v.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
-
-                let mut x_straight_top = v[0].0;
-                let mut x_two_part = v[0].0;
-
-                let mut z_straight_top = v[0].2;
-                let mut z_two_part = v[0].2;
-
-                let dx_straight_top =
-                    (v[2].0 - v[0].0) / (v[2].1 as i32 - v[0].1 as i32 + 1) as f64;
-                let dx_bot_to_mid = (v[1].0 - v[0].0) / (v[1].1 as i32 - v[0].1 as i32 + 1) as f64;
-                let dx_mid_to_top = (v[2].0 - v[1].0) / (v[2].1 as i32 - v[1].1 as i32 + 1) as f64;
-
-                let dz_straight_top =
-                    (v[2].2 - v[0].2) / (v[2].1 as i32 - v[0].1 as i32 + 1) as f64;
-                let dz_bot_to_mid = (v[1].2 - v[0].2) / (v[1].1 as i32 - v[0].1 as i32 + 1) as f64;
-                let dz_mid_to_top = (v[2].2 - v[1].2) / (v[2].1 as i32 - v[1].1 as i32 + 1) as f64;
-
-                let mut curr_two_part_dx = dx_bot_to_mid;
-                let mut curr_two_part_dz = dz_bot_to_mid;
-
-                let mut swapped = false;
-                (v[0].1 as i32..=v[2].1 as i32).for_each(|y| {
-                    if y == v[1].1 as i32 {
-                        x_two_part = v[1].0;
-                        curr_two_part_dx = dx_mid_to_top;
-
-                        z_two_part = v[1].2;
-                        curr_two_part_dz = dz_mid_to_top;
-
-                        swapped = true;
-                    }
-
-                    match shading {
-                        ShadingMethod::Flat => {
-                            image_rwlock.write().unwrap().draw_line(
-                                (x_straight_top as i32, y, z_straight_top),
-                                (x_two_part as i32, y, z_two_part),
-                                c,
-                            );
-                        }
-                        ShadingMethod::Phong => {
-                            let straight_top_normal = Vector3D::interpolate(
-                                [
-                                    (
-                                        v[0].3,
-                                        dist(
-                                            (x_straight_top, y as f64, z_straight_top),
-                                            (v[2].0, v[2].1, v[2].2),
-                                        ),
-                                    ),
-                                    (
-                                        v[2].3,
-                                        dist(
-                                            (x_straight_top, y as f64, z_straight_top),
-                                            (v[0].0, v[0].1, v[0].2),
-                                        ),
-                                    ),
-                                ]
-                                .into_iter(),
-                            );
-
-                            let two_part_normal = if swapped {
-                                Vector3D::interpolate(
-                                    [
-                                        (
-                                            v[1].3,
-                                            dist(
-                                                (x_two_part, y as f64, z_two_part),
-                                                (v[2].0, v[2].1, v[2].2),
-                                            ),
-                                        ),
-                                        (
-                                            v[2].3,
-                                            dist(
-                                                (x_two_part, y as f64, z_two_part),
-                                                (v[1].0, v[1].1, v[1].2),
-                                            ),
-                                        ),
-                                    ]
-                                    .into_iter(),
-                                )
-                            } else {
-                                Vector3D::interpolate(
-                                    [
-                                        (
-                                            v[0].3,
-                                            dist(
-                                                (x_two_part, y as f64, z_two_part),
-                                                (v[1].0, v[1].1, v[1].2),
-                                            ),
-                                        ),
-                                        (
-                                            v[1].3,
-                                            dist(
-                                                (x_two_part, y as f64, z_two_part),
-                                                (v[0].0, v[0].1, v[0].2),
-                                            ),
-                                        ),
-                                    ]
-                                    .into_iter(),
-                                )
-                            };
-
-                            image_rwlock.write().unwrap().scan_line_phong(
-                                y,
-                                (x_straight_top as i32, z_straight_top, straight_top_normal),
-                                (x_two_part as i32, z_two_part, two_part_normal),
-                                &lighter,
-                                light_conf,
-                            );
-                        }
-                    }
-
-                    x_straight_top += dx_straight_top;
-                    x_two_part += curr_two_part_dx;
-
-                    z_straight_top += dz_straight_top;
-                    z_two_part += curr_two_part_dz;
+            .for_each(|(triangle, (corners, normal))| {
+                let centroid = corners
+                    .iter()
+                    .map(|corner| {
+                        let (x, y, z, w) = corner.position;
+                        Vector3D::new(x / w, y / w, z / w)
+                    })
+                    .sum::<Vector3D>()
+                    .scale(1.0 / 3.0);
+                let c = lighter.calculate(&normal, &centroid, material);
+
+                let corners = corners.map(|corner| ClipVertex {
+                    object: corner.object,
+                    ..ClipVertex::new(corner.position, corner.normal)
+                });
+                let v: Vec<ScreenVertex> = frustum
+                    .clip_triangle(corners)
+                    .iter()
+                    .map(ScreenVertex::from_clip)
+                    .collect();
+
+                (1..v.len().saturating_sub(1)).for_each(|i| {
+                    Self::rasterize_triangle(
+                        &image_rwlock,
+                        [v[0], v[i], v[i + 1]],
+                        (c, normal),
+                        shading,
+                        &lighter,
+                        material,
+                        (draw, triangle),
+                    );
                 });
-                // self.draw_line((p0.0 as i32, p0.1 as i32, p0.2), (p1.0 as i32, p1.1 as i32, p1.2), c);
-                // self.draw_line((p1.0 as i32, p1.1 as i32, p1.2), (p2.0 as i32, p2.1 as i32, p2.2), c);
-                // self.draw_line((p2.0 as i32, p2.1 as i32, p2.2), (p0.0 as i32, p0.1 as i32, p0.2), c);
             });
     }
 
-    fn scan_line_phong(
-        &mut self,
-        y: i32,
-        mut leftdata: (i32, f64, Vector3D),
-        mut rightdata: (i32, f64, Vector3D),
+    fn rasterize_triangle(
+        image_rwlock: &RwLock<&mut Self>,
+        v: [ScreenVertex; 3],
+        (c, face_normal): (HdrColor, Vector3D),
+        shading: ShadingMethod,
         lighter: &Lighter,
-        light_conf: &LightingConfig,
+        material: &Material,
+        order: (usize, usize),
     ) {
-        if y < 0 || y >= self.get_height() as i32 {
+        let area = edge_function(&v[0], &v[1], (v[2].x, v[2].y));
+        if area == 0.0 {
             return;
         }
 
-        if leftdata.0 > rightdata.0 {
-            mem::swap(&mut leftdata, &mut rightdata);
-        }
+        // Only finding the samples in front takes the lock. They are shaded with it released,
+        // so other triangles keep rasterizing, and stored under one short write at the end
+        let image = image_rwlock.read().unwrap();
+        let margin = image.sampler.margin() as i32;
+        let material_varies = material.is_textured();
+        let opacity = material.opacity();
+        let occluded = image.ssao.is_some();
+        let xmin = cmp::max(
+            v.iter().map(|p| p.x).fold(f64::INFINITY, f64::min) as i32 - margin,
+            0,
+        );
+        let xmax = cmp::min(
+            v.iter().map(|p| p.x).fold(f64::NEG_INFINITY, f64::max) as i32 + margin,
+            WIDTH as i32 - 1,
+        );
+        let ymin = cmp::max(
+            v.iter().map(|p| p.y).fold(f64::INFINITY, f64::min) as i32 - margin,
+            0,
+        );
+        let ymax = cmp::min(
+            v.iter().map(|p| p.y).fold(f64::NEG_INFINITY, f64::max) as i32 + margin,
+            HEIGHT as i32 - 1,
+        );
 
-        let (leftx, leftz, leftnormal) = leftdata;
-        let (rightx, rightz, rightnormal) = rightdata;
-
-        let mut z = leftz;
-        let dz = rightz - leftz;
-        let dx = rightx - leftx;
-        let dzpp = dz / (dx as f64 + 1.0);
-
-        let casty = y as usize;
-        (leftx..=rightx).for_each(|x| {
-            if x >= 0 && x < self.get_width() as i32 {
-                let castx = x as usize;
-
-                if z > self.zbuffer[casty][castx] {
-                    self[casty][castx] = lighter.calculate(
-                        &Vector3D::interpolate(
-                            [
-                                (
-                                    leftnormal,
-                                    dist(
-                                        (x as f64, y as f64, z),
-                                        (rightx as f64, y as f64, rightz),
-                                    ),
-                                ),
-                                (
-                                    rightnormal,
-                                    dist((x as f64, y as f64, z), (leftx as f64, y as f64, leftz)),
-                                ),
-                            ]
-                            .into_iter(),
-                        ),
-                        light_conf,
-                    );
-                    self.zbuffer[casty][castx] = z;
+        let covered: Vec<(usize, usize, f64, [f64; 3])> = (ymin..=ymax)
+            .flat_map(|y| (xmin..=xmax).map(move |x| (x as usize, y as usize)))
+            .filter_map(|(x, y)| {
+                let sample = image.sampler.position(x, y);
+                let bary = [
+                    edge_function(&v[1], &v[2], sample) / area,
+                    edge_function(&v[2], &v[0], sample) / area,
+                    edge_function(&v[0], &v[1], sample) / area,
+                ];
+                if bary.iter().any(|b| *b < 0.0) {
+                    return None;
                 }
+
+                let weights = ScreenVertex::perspective_weights(&v, bary);
+                let z = v.iter().zip(weights).map(|(p, wt)| p.z * wt).sum::<f64>();
+                (z > image.zbuffer[y][x]).then_some((x, y, z, weights))
+            })
+            .collect();
+        drop(image);
+        if covered.is_empty() {
+            return;
+        }
+
+        let shaded: Vec<_> = covered
+            .into_iter()
+            .map(|(x, y, z, weights)| {
+                let position = || {
+                    v.iter()
+                        .zip(weights)
+                        .map(|(p, wt)| p.projected.scale(wt))
+                        .sum()
+                };
+                // The texture is looked up once here and shared by lighting and occlusion
+                let material = material.at(&v
+                    .iter()
+                    .zip(weights)
+                    .map(|(p, wt)| p.object.scale(wt))
+                    .sum());
+                let normal = match shading {
+                    ShadingMethod::Flat => face_normal,
+                    ShadingMethod::Phong | ShadingMethod::Toon(_) => {
+                        Vector3D::interpolate(v.iter().zip(weights).map(|(p, wt)| (p.normal, wt)))
+                    }
+                };
+                let color = match shading {
+                    // Shadows and textures can change across a face, so flat faces are
+                    // lit per sample whenever either is in play
+                    ShadingMethod::Flat if !(lighter.casts_shadows() || material_varies) => c,
+                    ShadingMethod::Toon(bands) => {
+                        lighter.calculate_toon(&normal, &position(), &material, bands)
+                    }
+                    _ => lighter.calculate(&normal, &position(), &material),
+                };
+                let ambient = occluded.then(|| lighter.calc_ambient(&material));
+                (x, y, z, color, normal, ambient)
+            })
+            .collect();
+
+        let mut image = image_rwlock.write().unwrap();
+        for (x, y, z, color, normal, ambient) in shaded {
+            // Another triangle may have drawn in front while this one was being shaded
+            if z <= image.zbuffer[y][x] {
+                continue;
             }
-            z += dzpp;
-        });
+            if opacity < 1.0 {
+                image.abuffer.push(
+                    x,
+                    y,
+                    Fragment {
+                        z,
+                        color,
+                        alpha: opacity,
+                        order,
+                    },
+                );
+                continue;
+            }
+            image[y][x] = color;
+            if !image.normals.is_empty() {
+                image.normals[y * WIDTH + x] = normal.normalize();
+            }
+            if let (Some(ssao), Some(ambient)) = (&mut image.ssao, ambient) {
+                ssao.record(x, y, ambient);
+            }
+            image.zbuffer[y][x] = z;
+        }
     }
 
-    pub fn draw_line(&mut self, mut p0: (i32, i32, f64), mut p1: (i32, i32, f64), c: Color) {
+    pub fn draw_line(&mut self, p0: (i32, i32, f64), p1: (i32, i32, f64), c: Color) {
+        // Clip to the image so off-screen stretches of the line are never walked
+        let frustum = Frustum::new(
+            (self.get_width() - 1) as f64,
+            (self.get_height() - 1) as f64,
+        );
+        let to_clip = |(x, y, z): (i32, i32, f64)| {
+            ClipVertex::new((x as f64, y as f64, z, 1.0), Vector3D::new(0.0, 0.0, 0.0))
+        };
+        let from_clip = |v: ClipVertex| {
+            let (x, y, z, _w) = v.position;
+            (x.round() as i32, y.round() as i32, z)
+        };
+        if let Some((p0, p1)) = frustum.clip_line(to_clip(p0), to_clip(p1)) {
+            self.walk_line(from_clip(p0), from_clip(p1), c);
+        }
+    }
+
+    /// Bresenham's walk between two endpoints already clipped to the image.
+    fn walk_line(&mut self, mut p0: (i32, i32, f64), mut p1: (i32, i32, f64), c: Color) {
         // Ensure p0 is the left point
         if p0.0 > p1.0 {
             mem::swap(&mut p0, &mut p1);
@@ -473,11 +720,11 @@ impl<const WIDTH: usize, const HEIGHT: usize> Image<WIDTH, HEIGHT> {
             if steep {
                 if z > self.zbuffer[faster_coord as usize][slower_coord as usize] {
                     self.zbuffer[faster_coord as usize][slower_coord as usize] = z;
-                    self[faster_coord as usize][slower_coord as usize] = c;
+                    self[faster_coord as usize][slower_coord as usize] = c.into();
                 }
             } else if z > self.zbuffer[slower_coord as usize][faster_coord as usize] {
                 self.zbuffer[slower_coord as usize][faster_coord as usize] = z;
-                self[slower_coord as usize][faster_coord as usize] = c;
+                self[slower_coord as usize][faster_coord as usize] = c.into();
             }
 
             if cmp_closure(error) {
@@ -492,7 +739,7 @@ impl<const WIDTH: usize, const HEIGHT: usize> Image<WIDTH, HEIGHT> {
 }
 
 impl<const WIDTH: usize, const HEIGHT: usize> Index<usize> for Image<WIDTH, HEIGHT> {
-    type Output = [Color];
+    type Output = [HdrColor];
     fn index(&self, index: usize) -> &Self::Output {
         &self.data[index]
     }
@@ -512,7 +759,7 @@ impl<const WIDTH: usize, const HEIGHT: usize> fmt::Display for Image<WIDTH, HEIG
 
         for r in (0..self.get_height()).rev() {
             for c in 0..self.get_width() {
-                write!(f, "{} ", self[r][c])?;
+                write!(f, "{} ", self.tone_mapper.map(self[r][c]))?;
             }
         }
         writeln!(f)?;
@@ -524,14 +771,15 @@ impl<const WIDTH: usize, const HEIGHT: usize> fmt::Display for Image<WIDTH, HEIG
 mod tests {
     use crate::color::color_constants;
 
-    use super::Image;
+    use super::{Image, ScreenVertex};
+    use crate::{clip::ClipVertex, parser, Color, Vector3D};
 
     #[test]
     fn one_x_four_brgb() {
         let mut one_x_four: Image<4, 1> = Image::new("one_x_four".to_string());
-        one_x_four[0][1] = color_constants::RED;
-        one_x_four[0][2] = color_constants::GREEN;
-        one_x_four[0][3] = color_constants::BLUE;
+        one_x_four[0][1] = color_constants::RED.into();
+        one_x_four[0][2] = color_constants::GREEN.into();
+        one_x_four[0][3] = color_constants::BLUE.into();
         assert_eq!(
             one_x_four.to_string(),
             "P3\n\
@@ -575,4 +823,39 @@ mod tests {
             .save_test()
             .expect("Octant 1 line image file write failed");
     }
+
+    #[test]
+    fn perspective_weights_follow_world_space() {
+        // A segment from w = 1 to w = 3 has its world-space midpoint a quarter of the way
+        // across the screen, so the screen-space midpoint must lean toward the far vertex.
+        let near = ScreenVertex::from_clip(&ClipVertex::new(
+            (0.0, 0.0, 0.0, 1.0),
+            Vector3D::new(0.0, 0.0, 1.0),
+        ));
+        let far = ScreenVertex::from_clip(&ClipVertex::new(
+            (3.0, 0.0, 0.0, 3.0),
+            Vector3D::new(0.0, 0.0, 1.0),
+        ));
+        let weights = ScreenVertex::perspective_weights(&[near, far, far], [0.5, 0.5, 0.0]);
+        assert!((weights[0] - 0.75).abs() < 1e-9);
+        assert!((weights[1] - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn downsample_averages_in_linear_light() {
+        // Half-covered pixels should look half as bright, which in sRGB is well above 128.
+        let mut img: Image<{ parser::FINAL_SCREEN_SIZE }, { parser::FINAL_SCREEN_SIZE }> =
+            Default::default();
+        for r in 0..parser::SAMPLE_SCALE as usize {
+            for c in 0..parser::SAMPLE_SCALE as usize / 2 {
+                img[r][c] = color_constants::WHITE.into();
+                img.zbuffer[r][c] = 0.0;
+            }
+        }
+        let small = img.downsample();
+        assert_eq!(
+            small.tone_mapper.map(small[0][0]),
+            Color::new(188, 188, 188)
+        );
+    }
 }
@@ -6,6 +6,8 @@ pub use image::Image;
 
 pub mod matrix;
 
+mod clip;
+
 mod transform;
 pub use transform::Axis;
 pub use transform::TStack;
@@ -20,7 +22,34 @@ pub use vector3d::Vector3D;
 mod parser;
 pub use parser::MDLParser;
 
+mod abuffer;
+
+mod background;
+pub use background::{BackdropFit, Background};
+
+mod fog;
+pub use fog::{Fog, FogMode};
+
 mod lighter;
-pub use lighter::Lighter;
+pub use lighter::{LightSource, Lighter, LightingConfig, Material, PbrConfig, SpecularModel};
+
+mod shadow;
+pub use shadow::{ShadowMap, ShadowSettings};
+
+mod texture;
+pub use texture::{Pattern, Texture};
+
+mod tone_map;
+pub use tone_map::{ToneMapOperator, ToneMapper};
+
+mod outline;
+pub use outline::OutlineSettings;
+
+mod ssao;
+pub use ssao::{Ssao, SsaoSettings};
+
+mod sampling;
+pub use sampling::{FilterKind, SamplePattern, Sampler};
+
 #[cfg(test)]
 mod tests;
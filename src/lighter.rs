@@ -1,11 +1,126 @@
-use crate::{color::color_constants, Color, Vector3D};
+use std::sync::Arc;
+
+use crate::{
+    color::{color_constants, HdrColor},
+    shadow::ShadowMap,
+    texture::Texture,
+    Color, Vector3D,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub enum LightSource {
+    /// Infinitely far away; every fragment sees it from the same direction at full strength.
+    Directional { direction: Vector3D, color: Color },
+    /// Radiates in all directions, falling off by `1 / (kc + kl * d + kq * d^2)`.
+    Point {
+        position: Vector3D,
+        color: Color,
+        attenuation: (f64, f64, f64),
+    },
+    /// A point light restricted to a cone around `direction`. The cone angles are stored as
+    /// cosines; light fades smoothly from the inner to the outer angle.
+    Spot {
+        position: Vector3D,
+        direction: Vector3D,
+        color: Color,
+        attenuation: (f64, f64, f64),
+        cos_inner: f64,
+        cos_outer: f64,
+    },
+}
+
+impl LightSource {
+    pub const NO_ATTENUATION: (f64, f64, f64) = (1.0, 0.0, 0.0);
+
+    pub fn point(position: Vector3D, color: Color, attenuation: (f64, f64, f64)) -> Self {
+        LightSource::Point {
+            position,
+            color,
+            attenuation,
+        }
+    }
+
+    /// Builds a spotlight from cone half-angles given in degrees.
+    pub fn spot(
+        position: Vector3D,
+        direction: Vector3D,
+        color: Color,
+        attenuation: (f64, f64, f64),
+        inner: f64,
+        outer: f64,
+    ) -> Self {
+        LightSource::Spot {
+            position,
+            direction: direction.normalize(),
+            color,
+            attenuation,
+            cos_inner: inner.to_radians().cos(),
+            cos_outer: outer.max(inner).to_radians().cos(),
+        }
+    }
+
+    /// Unit vector from `position` towards the light, and the light's intensity there.
+    fn illuminate(&self, position: &Vector3D) -> (Vector3D, HdrColor) {
+        match self {
+            LightSource::Directional { direction, color } => {
+                (direction.normalize(), HdrColor::from(*color))
+            }
+            LightSource::Point {
+                position: light,
+                color,
+                attenuation,
+            } => {
+                let to_light = *light - *position;
+                let falloff = Self::falloff(attenuation, to_light.magnitude());
+                (to_light.normalize(), HdrColor::from(*color).scale(falloff))
+            }
+            LightSource::Spot {
+                position: light,
+                direction,
+                color,
+                attenuation,
+                cos_inner,
+                cos_outer,
+            } => {
+                let to_light = *light - *position;
+                let l = to_light.normalize();
+                let cos_angle = -l.dot(direction);
+                let cone = if cos_inner - cos_outer > f64::EPSILON {
+                    let t = ((cos_angle - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0);
+                    t * t * (3.0 - 2.0 * t)
+                } else if cos_angle >= *cos_outer {
+                    1.0
+                } else {
+                    0.0
+                };
+                let falloff = Self::falloff(attenuation, to_light.magnitude());
+                (l, HdrColor::from(*color).scale(cone * falloff))
+            }
+        }
+    }
+
+    fn falloff(attenuation: &(f64, f64, f64), distance: f64) -> f64 {
+        let (kc, kl, kq) = *attenuation;
+        1.0 / (kc + kl * distance + kq * distance * distance).max(f64::EPSILON)
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Lighter {
-    sources: Vec<(Vector3D, Color)>,
-    spec_power: f64,
+    sources: Vec<LightSource>,
     ambient_color: Color,
     view_vector: Vector3D,
+    shadow_maps: Vec<Arc<ShadowMap>>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum SpecularModel {
+    /// Compares the reflected light direction with the view vector.
+    #[default]
+    Phong,
+    /// Compares the half-vector between light and view with the normal. Needs about four
+    /// times the Phong exponent for a highlight of the same size.
+    BlinnPhong,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -13,76 +128,387 @@ pub struct LightingConfig {
     pub ka: (f64, f64, f64),
     pub kd: (f64, f64, f64),
     pub ks: (f64, f64, f64),
+    /// Specular exponent; higher values give smaller, sharper highlights.
+    pub shininess: f64,
+    pub specular: SpecularModel,
+    /// Multiplies `ka` and `kd` wherever the surface is drawn.
+    pub texture: Option<Texture>,
+    /// How much of what lies behind the surface it hides, from 0 to 1.
+    pub opacity: f64,
 }
 
-impl Lighter {
-    const SPEC_POWER: f64 = 3.0;
+impl LightingConfig {
+    pub const DEFAULT_SHININESS: f64 = 3.0;
+}
+
+/// Metallic/roughness parameters as authored in most PBR tools.
+#[derive(Clone, Copy, Debug)]
+pub struct PbrConfig {
+    /// Linear albedo for dielectrics, or reflectance at normal incidence for metals.
+    pub base_color: (f64, f64, f64),
+    pub metallic: f64,
+    pub roughness: f64,
+    /// Multiplies the base color wherever the surface is drawn.
+    pub texture: Option<Texture>,
+    /// As in `LightingConfig`.
+    pub opacity: f64,
+}
+
+impl PbrConfig {
+    /// Fraction of the ambient color picked up, matching the usual Phong `ka`.
+    const AMBIENT: f64 = 0.1;
+    /// Reflectance at normal incidence shared by most dielectrics.
+    const DIELECTRIC_F0: f64 = 0.04;
+
+    pub fn new(base_color: HdrColor, metallic: f64, roughness: f64) -> Self {
+        Self {
+            base_color: (
+                base_color.red as f64,
+                base_color.green as f64,
+                base_color.blue as f64,
+            ),
+            metallic: metallic.clamp(0.0, 1.0),
+            roughness: roughness.clamp(0.0, 1.0),
+            texture: None,
+            opacity: 1.0,
+        }
+    }
+
+    fn diffuse_color(&self) -> (f64, f64, f64) {
+        let d = 1.0 - self.metallic;
+        (
+            self.base_color.0 * d,
+            self.base_color.1 * d,
+            self.base_color.2 * d,
+        )
+    }
+
+    fn f0(&self) -> (f64, f64, f64) {
+        let mix = |c: f64| Self::DIELECTRIC_F0 + (c - Self::DIELECTRIC_F0) * self.metallic;
+        (
+            mix(self.base_color.0),
+            mix(self.base_color.1),
+            mix(self.base_color.2),
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Material {
+    Phong(LightingConfig),
+    Pbr(PbrConfig),
+}
+
+impl Material {
+    pub fn is_textured(&self) -> bool {
+        match self {
+            Material::Phong(conf) => conf.texture.is_some(),
+            Material::Pbr(pbr) => pbr.texture.is_some(),
+        }
+    }
+
+    pub fn set_texture(&mut self, texture: Texture) {
+        match self {
+            Material::Phong(conf) => conf.texture = Some(texture),
+            Material::Pbr(pbr) => pbr.texture = Some(texture),
+        }
+    }
+
+    pub fn opacity(&self) -> f64 {
+        match self {
+            Material::Phong(conf) => conf.opacity,
+            Material::Pbr(pbr) => pbr.opacity,
+        }
+    }
+
+    pub fn set_opacity(&mut self, opacity: f64) {
+        let opacity = opacity.clamp(0.0, 1.0);
+        match self {
+            Material::Phong(conf) => conf.opacity = opacity,
+            Material::Pbr(pbr) => pbr.opacity = opacity,
+        }
+    }
+
+    /// The untextured material seen at `object`, a point in the shape's own coordinates.
+    pub fn at(&self, object: &Vector3D) -> Material {
+        let tint = |texture: &Texture, k: (f64, f64, f64)| {
+            let c = texture.sample(object);
+            (
+                k.0 * c.red as f64,
+                k.1 * c.green as f64,
+                k.2 * c.blue as f64,
+            )
+        };
+        match *self {
+            Material::Phong(mut conf) => {
+                if let Some(texture) = conf.texture.take() {
+                    conf.ka = tint(&texture, conf.ka);
+                    conf.kd = tint(&texture, conf.kd);
+                }
+                Material::Phong(conf)
+            }
+            Material::Pbr(mut pbr) => {
+                if let Some(texture) = pbr.texture.take() {
+                    pbr.base_color = tint(&texture, pbr.base_color);
+                }
+                Material::Pbr(pbr)
+            }
+        }
+    }
+}
+
+impl From<LightingConfig> for Material {
+    fn from(conf: LightingConfig) -> Self {
+        Material::Phong(conf)
+    }
+}
 
+impl Lighter {
     pub fn from_sources(sources: Vec<(Vector3D, Color)>) -> Self {
         Self {
-            sources,
-            spec_power: Lighter::SPEC_POWER,
+            sources: Self::directional(sources),
             ambient_color: color_constants::WHITE,
             view_vector: Vector3D::new(0.0, 0.0, 1.0),
+            shadow_maps: Vec::new(),
         }
     }
 
     pub fn from_sources_ambient(sources: Vec<(Vector3D, Color)>, ambient_color: Color) -> Self {
         Self {
-            sources,
-            spec_power: Lighter::SPEC_POWER,
+            sources: Self::directional(sources),
             ambient_color,
             view_vector: Vector3D::new(0.0, 0.0, 1.0),
+            shadow_maps: Vec::new(),
         }
     }
 
-    fn calc_ambient(&self, conf: &LightingConfig) -> Color {
-        self.ambient_color * conf.ka
+    fn directional(sources: Vec<(Vector3D, Color)>) -> Vec<LightSource> {
+        sources
+            .into_iter()
+            .map(|(direction, color)| LightSource::Directional { direction, color })
+            .collect()
     }
 
-    fn calc_diffuse(&self, normal: &Vector3D, conf: &LightingConfig) -> Color {
-        let normalized = normal.normalize();
-        let mut result = color_constants::BLACK;
-        for (source_vec, color) in &self.sources {
-            let normalized_source = source_vec.normalize();
-            let dotprod = normalized.dot(&normalized_source);
-            result += color
-                * (
-                    dotprod * conf.kd.0,
-                    dotprod * conf.kd.1,
-                    dotprod * conf.kd.2,
-                );
+    pub(crate) fn calc_ambient(&self, material: &Material) -> HdrColor {
+        match material {
+            Material::Phong(conf) => HdrColor::from(self.ambient_color) * conf.ka,
+            Material::Pbr(pbr) => {
+                let (diffuse, f0) = (pbr.diffuse_color(), pbr.f0());
+                HdrColor::from(self.ambient_color)
+                    * (
+                        (diffuse.0 + f0.0) * PbrConfig::AMBIENT,
+                        (diffuse.1 + f0.1) * PbrConfig::AMBIENT,
+                        (diffuse.2 + f0.2) * PbrConfig::AMBIENT,
+                    )
+            }
         }
-        result
     }
 
-    fn calc_specular(&self, normal: &Vector3D, conf: &LightingConfig) -> Color {
-        let normalized = normal.normalize();
-        let mut result = color_constants::BLACK;
-        for (source_vec, color) in &self.sources {
-            let normalized_source = source_vec.normalize();
-            let scale = (normalized.scale(2.0 * normalized.dot(&normalized_source))
-                - normalized_source)
-                .dot(&self.view_vector)
-                .powf(self.spec_power);
-            result += color * (scale * conf.ks.0, scale * conf.ks.1, scale * conf.ks.2);
+    fn calc_diffuse(
+        &self,
+        normal: &Vector3D,
+        to_light: &Vector3D,
+        intensity: HdrColor,
+        conf: &LightingConfig,
+    ) -> HdrColor {
+        let dotprod = normal.dot(to_light).max(0.0);
+        intensity
+            * (
+                dotprod * conf.kd.0,
+                dotprod * conf.kd.1,
+                dotprod * conf.kd.2,
+            )
+    }
+
+    fn calc_specular(
+        &self,
+        normal: &Vector3D,
+        to_light: &Vector3D,
+        intensity: HdrColor,
+        conf: &LightingConfig,
+    ) -> HdrColor {
+        let scale = self.specular_lobe(normal, to_light, conf.specular, conf.shininess);
+        intensity * (scale * conf.ks.0, scale * conf.ks.1, scale * conf.ks.2)
+    }
+
+    fn specular_lobe(
+        &self,
+        normal: &Vector3D,
+        to_light: &Vector3D,
+        model: SpecularModel,
+        shininess: f64,
+    ) -> f64 {
+        let n_dot_l = normal.dot(to_light);
+        // A surface facing away from the light has no highlight, whatever the view
+        if n_dot_l <= 0.0 {
+            return 0.0;
         }
-        result
+        let cos = match model {
+            SpecularModel::Phong => {
+                (normal.scale(2.0 * n_dot_l) - *to_light).dot(&self.view_vector)
+            }
+            SpecularModel::BlinnPhong => normal.dot(&(*to_light + self.view_vector).normalize()),
+        };
+        // Clamp before the power so even exponents cannot flip a negative lobe positive
+        cos.max(0.0).powf(shininess)
+    }
+
+    /// Lambert diffuse plus a Cook–Torrance specular lobe with the GGX distribution,
+    /// Smith–Schlick geometry term and Schlick's Fresnel approximation. Light colors are
+    /// treated as irradiance at normal incidence, so a white base color under a white light
+    /// comes out as bright as Phong with `kd` of 1.
+    fn calc_cook_torrance(
+        &self,
+        normal: &Vector3D,
+        to_light: &Vector3D,
+        intensity: HdrColor,
+        pbr: &PbrConfig,
+    ) -> HdrColor {
+        let n_dot_l = normal.dot(to_light);
+        if n_dot_l <= 0.0 {
+            return HdrColor::default();
+        }
+        let view = self.view_vector.normalize();
+        let n_dot_v = normal.dot(&view).max(1e-4);
+        let half = (*to_light + view).normalize();
+        let n_dot_h = normal.dot(&half).max(0.0);
+        let v_dot_h = view.dot(&half).max(0.0);
+
+        // Perceptual roughness is squared; the floor keeps mirror-like highlights finite
+        let alpha = pbr.roughness.max(0.045).powi(2);
+        let alpha2 = alpha * alpha;
+        let d_denom = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+        let distribution = alpha2 / (std::f64::consts::PI * d_denom * d_denom);
+
+        let k = (pbr.roughness + 1.0).powi(2) / 8.0;
+        let g1 = |x: f64| x / (x * (1.0 - k) + k);
+        let geometry = g1(n_dot_v) * g1(n_dot_l);
+
+        let f0 = pbr.f0();
+        let schlick = (1.0 - v_dot_h).powi(5);
+        let fresnel = |f: f64| f + (1.0 - f) * schlick;
+        let fresnel = (fresnel(f0.0), fresnel(f0.1), fresnel(f0.2));
+
+        let specular = distribution * geometry / (4.0 * n_dot_v * n_dot_l) * std::f64::consts::PI;
+        let diffuse = pbr.diffuse_color();
+        let channel = |f: f64, d: f64| ((1.0 - f) * d + f * specular) * n_dot_l;
+        intensity
+            * (
+                channel(fresnel.0, diffuse.0),
+                channel(fresnel.1, diffuse.1),
+                channel(fresnel.2, diffuse.2),
+            )
     }
 
     pub fn add_source(&mut self, direction: Vector3D, color: Color) {
-        self.sources.push((direction, color));
+        self.sources
+            .push(LightSource::Directional { direction, color });
+    }
+
+    pub fn add_light(&mut self, light: LightSource) {
+        self.sources.push(light);
     }
 
     pub fn set_ambient(&mut self, color: Color) {
         self.ambient_color = color;
     }
 
-    pub fn calculate(&self, normal: &Vector3D, conf: &LightingConfig) -> Color {
-        let mut result = color_constants::BLACK;
-        result += &self.calc_ambient(conf);
-        result += &self.calc_diffuse(normal, conf);
-        result += &self.calc_specular(normal, conf);
+    pub fn get_sources(&self) -> &[LightSource] {
+        &self.sources
+    }
+
+    /// Installs one shadow map per source, in the same order as the sources were added.
+    pub fn set_shadow_maps(&mut self, shadow_maps: Vec<Arc<ShadowMap>>) {
+        self.shadow_maps = shadow_maps;
+    }
+
+    pub fn casts_shadows(&self) -> bool {
+        !self.shadow_maps.is_empty()
+    }
+
+    /// Sums the ambient, diffuse and specular terms without clipping, so bright
+    /// multi-light scenes keep their detail until tone mapping. `position` is the
+    /// world-space point being lit, which positional lights measure from.
+    pub fn calculate(
+        &self,
+        normal: &Vector3D,
+        position: &Vector3D,
+        material: &Material,
+    ) -> HdrColor {
+        let normalized = normal.normalize();
+        let mut result = self.calc_ambient(material);
+        for (i, source) in self.sources.iter().enumerate() {
+            let (to_light, mut intensity) = source.illuminate(position);
+            if intensity == HdrColor::default() {
+                continue;
+            }
+            if let Some(shadow_map) = self.shadow_maps.get(i) {
+                intensity =
+                    intensity.scale(shadow_map.visibility(position, &normalized, &to_light));
+            }
+            result += match material {
+                Material::Phong(conf) => {
+                    self.calc_diffuse(&normalized, &to_light, intensity, conf)
+                        + self.calc_specular(&normalized, &to_light, intensity, conf)
+                }
+                Material::Pbr(pbr) => {
+                    self.calc_cook_torrance(&normalized, &to_light, intensity, pbr)
+                }
+            };
+        }
+        result
+    }
+
+    /// Cel shading. Each light's diffuse term is cut into `bands` flat steps above unlit, and
+    /// its highlight is either fully on or off. PBR materials are approximated with their
+    /// diffuse color, reflectance and a Blinn-Phong lobe of matching width.
+    pub fn calculate_toon(
+        &self,
+        normal: &Vector3D,
+        position: &Vector3D,
+        material: &Material,
+        bands: usize,
+    ) -> HdrColor {
+        let normalized = normal.normalize();
+        let (kd, ks, specular, shininess) = match material {
+            Material::Phong(conf) => (conf.kd, conf.ks, conf.specular, conf.shininess),
+            Material::Pbr(pbr) => {
+                let alpha = pbr.roughness.max(0.045).powi(2);
+                (
+                    pbr.diffuse_color(),
+                    pbr.f0(),
+                    SpecularModel::BlinnPhong,
+                    2.0 / (alpha * alpha) - 2.0,
+                )
+            }
+        };
+        let bands = bands.max(1) as f64;
+        let mut result = self.calc_ambient(material);
+        for (i, source) in self.sources.iter().enumerate() {
+            let (to_light, intensity) = source.illuminate(position);
+            if intensity == HdrColor::default() {
+                continue;
+            }
+            let visibility = match self.shadow_maps.get(i) {
+                Some(shadow_map) => shadow_map.visibility(position, &normalized, &to_light),
+                None => 1.0,
+            };
+            let lit = normalized.dot(&to_light).max(0.0) * visibility;
+            let diffuse = (lit * bands).ceil() / bands;
+            let highlight = if visibility > 0.5
+                && self.specular_lobe(&normalized, &to_light, specular, shininess) > 0.5
+            {
+                1.0
+            } else {
+                0.0
+            };
+            result += intensity
+                * (
+                    diffuse * kd.0 + highlight * ks.0,
+                    diffuse * kd.1 + highlight * ks.1,
+                    diffuse * kd.2 + highlight * ks.2,
+                );
+        }
         result
     }
 }
@@ -90,10 +516,159 @@ impl Lighter {
 impl Default for Lighter {
     fn default() -> Self {
         Self {
-            sources: vec![(Vector3D::new(1.0, 1.0, 1.0), color_constants::WHITE)],
-            spec_power: Lighter::SPEC_POWER,
+            sources: vec![LightSource::Directional {
+                direction: Vector3D::new(1.0, 1.0, 1.0),
+                color: color_constants::WHITE,
+            }],
             ambient_color: color_constants::WHITE,
             view_vector: Vector3D::new(0.0, 0.0, 1.0),
+            shadow_maps: Vec::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{LightSource, Lighter, LightingConfig, Material, PbrConfig, SpecularModel};
+    use crate::{color::color_constants, Vector3D};
+
+    fn pbr(metallic: f64, roughness: f64) -> Material {
+        Material::Pbr(PbrConfig::new(
+            color_constants::WHITE.into(),
+            metallic,
+            roughness,
+        ))
+    }
+
+    const DIFFUSE_ONLY: LightingConfig = LightingConfig {
+        ka: (0.0, 0.0, 0.0),
+        kd: (1.0, 1.0, 1.0),
+        ks: (0.0, 0.0, 0.0),
+        shininess: LightingConfig::DEFAULT_SHININESS,
+        specular: SpecularModel::Phong,
+        texture: None,
+        opacity: 1.0,
+    };
+
+    fn lit(light: LightSource, position: Vector3D) -> f32 {
+        let mut lighter = Lighter::from_sources(vec![]);
+        lighter.add_light(light);
+        lighter
+            .calculate(
+                &Vector3D::new(0.0, 0.0, 1.0),
+                &position,
+                &DIFFUSE_ONLY.into(),
+            )
+            .red
+    }
+
+    #[test]
+    fn point_light_falls_off_with_distance() {
+        let light = LightSource::point(
+            Vector3D::new(0.0, 0.0, 100.0),
+            color_constants::WHITE,
+            (1.0, 0.0, 1e-4),
+        );
+        let near = lit(light, Vector3D::new(0.0, 0.0, 0.0));
+        let far = lit(light, Vector3D::new(0.0, 0.0, -100.0));
+        assert!((near - 0.5).abs() < 1e-6);
+        assert!((far - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn spotlight_is_dark_outside_cone() {
+        let light = LightSource::spot(
+            Vector3D::new(0.0, 0.0, 100.0),
+            Vector3D::new(0.0, 0.0, -1.0),
+            color_constants::WHITE,
+            LightSource::NO_ATTENUATION,
+            10.0,
+            20.0,
+        );
+        let center = lit(light, Vector3D::new(0.0, 0.0, 0.0));
+        let edge = lit(
+            light,
+            Vector3D::new(100.0 * 15f64.to_radians().tan(), 0.0, 0.0),
+        );
+        let outside = lit(light, Vector3D::new(100.0, 0.0, 0.0));
+        assert!((center - 1.0).abs() < 1e-6);
+        assert!(edge > 0.0 && edge < center);
+        assert_eq!(outside, 0.0);
+    }
+
+    #[test]
+    fn no_highlight_behind_surface() {
+        let conf = LightingConfig {
+            ka: (0.0, 0.0, 0.0),
+            kd: (0.0, 0.0, 0.0),
+            ks: (1.0, 1.0, 1.0),
+            shininess: 4.0,
+            specular: SpecularModel::Phong,
+            texture: None,
+            opacity: 1.0,
+        };
+        // Light from behind reflects to -view, which an even exponent used to turn positive
+        let lighter = Lighter::from_sources(vec![(
+            Vector3D::new(0.0, 0.0, -1.0),
+            color_constants::WHITE,
+        )]);
+        let lit = lighter.calculate(
+            &Vector3D::new(0.0, 0.0, 1.0),
+            &Vector3D::new(0.0, 0.0, 0.0),
+            &conf.into(),
+        );
+        assert_eq!(lit.red, 0.0);
+    }
+
+    #[test]
+    fn shininess_narrows_highlight() {
+        let lighter =
+            Lighter::from_sources(vec![(Vector3D::new(0.0, 0.0, 1.0), color_constants::WHITE)]);
+        let tilted = Vector3D::new(0.2, 0.0, 1.0);
+        for specular in [SpecularModel::Phong, SpecularModel::BlinnPhong] {
+            let highlight = |shininess| {
+                let conf = LightingConfig {
+                    ka: (0.0, 0.0, 0.0),
+                    kd: (0.0, 0.0, 0.0),
+                    ks: (1.0, 1.0, 1.0),
+                    shininess,
+                    specular,
+                    texture: None,
+                    opacity: 1.0,
+                };
+                lighter
+                    .calculate(&tilted, &Vector3D::new(0.0, 0.0, 0.0), &conf.into())
+                    .red
+            };
+            assert!(highlight(100.0) < highlight(5.0));
+        }
+    }
+
+    #[test]
+    fn rough_dielectric_conserves_energy() {
+        let lighter = Lighter::from_sources_ambient(
+            vec![(Vector3D::new(0.0, 0.0, 1.0), color_constants::WHITE)],
+            color_constants::BLACK,
+        );
+        let lit = lighter.calculate(
+            &Vector3D::new(0.0, 0.0, 1.0),
+            &Vector3D::new(0.0, 0.0, 0.0),
+            &pbr(0.0, 1.0),
+        );
+        assert!(lit.red > 0.9 && lit.red <= 1.0);
+    }
+
+    #[test]
+    fn smooth_metal_has_no_diffuse() {
+        let lighter = Lighter::from_sources_ambient(
+            vec![(Vector3D::new(1.0, 0.0, 1.0), color_constants::WHITE)],
+            color_constants::BLACK,
+        );
+        let normal = Vector3D::new(0.0, 0.0, 1.0);
+        let origin = Vector3D::new(0.0, 0.0, 0.0);
+        let metal = lighter.calculate(&normal, &origin, &pbr(1.0, 0.1));
+        let plastic = lighter.calculate(&normal, &origin, &pbr(0.0, 0.1));
+        assert!(metal.red < 0.01);
+        assert!(plastic.red > 0.5);
+    }
+}
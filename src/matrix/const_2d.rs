@@ -205,7 +205,6 @@ where
         (&mut result).into_iter().enumerate().for_each(|(r, row)| {
             row.iter_mut().enumerate().for_each(|(c, ele)| {
                 *ele = (0..self.get_width())
-                    .into_iter()
                     .map(|index| *(self.at(r, index)) * *(rhs.at(index, c)))
                     .sum();
             })
@@ -0,0 +1,388 @@
+use std::mem;
+
+use super::{Corner, Mesh};
+use crate::Vector3D;
+
+/// Ways of combining two solids into one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Boolean {
+    Union,
+    Intersection,
+    /// The first solid with the second cut out of it.
+    Difference,
+}
+
+/// How close to a plane a point must be to count as lying on it.
+const EPSILON: f64 = 1e-5;
+
+#[derive(Clone, Copy, Debug)]
+struct Plane {
+    normal: Vector3D,
+    w: f64,
+}
+
+/// A flat convex polygon, remembering which part of which solid it was cut from.
+#[derive(Clone, Debug)]
+struct Polygon {
+    corners: Vec<Corner>,
+    plane: Plane,
+    part: usize,
+}
+
+/// Where the pieces of polygons land when split by a plane.
+#[derive(Default)]
+struct Sides {
+    coplanar_front: Vec<Polygon>,
+    coplanar_back: Vec<Polygon>,
+    front: Vec<Polygon>,
+    back: Vec<Polygon>,
+}
+
+/// One plane of a BSP tree, with the polygons lying in it and the subtrees either side.
+#[derive(Default)]
+struct Node {
+    plane: Option<Plane>,
+    front: Option<usize>,
+    back: Option<usize>,
+    polygons: Vec<Polygon>,
+}
+
+/// A binary space partition of a solid's surface. Nodes live in one list and point at each
+/// other by index, so neither building nor walking the tree recurses; convex shapes make
+/// trees as deep as they have faces.
+struct Bsp {
+    nodes: Vec<Node>,
+}
+
+fn position(corner: &Corner) -> Vector3D {
+    let (x, y, z, w) = corner.position;
+    Vector3D::new(x / w, y / w, z / w)
+}
+
+/// The corner a fraction `t` of the way from `a` to `b`.
+fn lerp(a: &Corner, b: &Corner, t: f64) -> Corner {
+    let mix = |a: Vector3D, b: Vector3D| a + (b - a).scale(t);
+    let point = mix(position(a), position(b));
+    Corner {
+        position: (point.x, point.y, point.z, 1.0),
+        normal: mix(a.normal, b.normal),
+        object: mix(a.object, b.object),
+    }
+}
+
+impl Plane {
+    fn through(p0: Vector3D, p1: Vector3D, p2: Vector3D) -> Option<Self> {
+        let normal = (p1 - p0).cross(&(p2 - p0));
+        let length = normal.magnitude();
+        (length > 0.0).then(|| {
+            let normal = normal.scale(1.0 / length);
+            Self {
+                normal,
+                w: normal.dot(&p0),
+            }
+        })
+    }
+
+    fn flip(&self) -> Self {
+        Self {
+            normal: self.normal.scale(-1.0),
+            w: -self.w,
+        }
+    }
+
+    /// Sorts a polygon into `sides`, cutting it in two if it crosses the plane.
+    fn split(&self, polygon: Polygon, sides: &mut Sides) {
+        const FRONT: u8 = 1;
+        const BACK: u8 = 2;
+        let kinds: Vec<u8> = polygon
+            .corners
+            .iter()
+            .map(|corner| {
+                let distance = self.normal.dot(&position(corner)) - self.w;
+                if distance < -EPSILON {
+                    BACK
+                } else if distance > EPSILON {
+                    FRONT
+                } else {
+                    0
+                }
+            })
+            .collect();
+
+        match kinds.iter().fold(0, |kind, k| kind | k) {
+            0 if self.normal.dot(&polygon.plane.normal) > 0.0 => sides.coplanar_front.push(polygon),
+            0 => sides.coplanar_back.push(polygon),
+            FRONT => sides.front.push(polygon),
+            BACK => sides.back.push(polygon),
+            _ => {
+                let (mut front, mut back) = (Vec::new(), Vec::new());
+                let count = polygon.corners.len();
+                for i in 0..count {
+                    let j = (i + 1) % count;
+                    let (a, b) = (&polygon.corners[i], &polygon.corners[j]);
+                    if kinds[i] != BACK {
+                        front.push(*a);
+                    }
+                    if kinds[i] != FRONT {
+                        back.push(*a);
+                    }
+                    if kinds[i] | kinds[j] == FRONT | BACK {
+                        let (pa, pb) = (position(a), position(b));
+                        let t = (self.w - self.normal.dot(&pa)) / self.normal.dot(&(pb - pa));
+                        let middle = lerp(a, b, t);
+                        front.push(middle);
+                        back.push(middle);
+                    }
+                }
+                for (corners, side) in [(front, &mut sides.front), (back, &mut sides.back)] {
+                    if corners.len() >= 3 {
+                        side.push(Polygon { corners, ..polygon });
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Polygon {
+    fn flip(&mut self) {
+        self.corners.reverse();
+        for corner in &mut self.corners {
+            corner.normal = corner.normal.scale(-1.0);
+        }
+        self.plane = self.plane.flip();
+    }
+}
+
+impl Bsp {
+    fn new(polygons: Vec<Polygon>) -> Self {
+        let mut bsp = Self {
+            nodes: vec![Node::default()],
+        };
+        bsp.build(polygons);
+        bsp
+    }
+
+    /// The child of a node on one side, made empty if it is not there yet.
+    fn child(&mut self, index: usize, front: bool) -> usize {
+        let existing = if front {
+            self.nodes[index].front
+        } else {
+            self.nodes[index].back
+        };
+        existing.unwrap_or_else(|| {
+            self.nodes.push(Node::default());
+            let child = self.nodes.len() - 1;
+            if front {
+                self.nodes[index].front = Some(child);
+            } else {
+                self.nodes[index].back = Some(child);
+            }
+            child
+        })
+    }
+
+    /// Files polygons into the tree, adding planes where they land in empty space.
+    fn build(&mut self, polygons: Vec<Polygon>) {
+        let mut pending = vec![(0, polygons)];
+        while let Some((index, polygons)) = pending.pop() {
+            if polygons.is_empty() {
+                continue;
+            }
+            let plane = *self.nodes[index].plane.get_or_insert(polygons[0].plane);
+            let mut sides = Sides::default();
+            for polygon in polygons {
+                plane.split(polygon, &mut sides);
+            }
+            let node = &mut self.nodes[index];
+            node.polygons.append(&mut sides.coplanar_front);
+            node.polygons.append(&mut sides.coplanar_back);
+            if !sides.front.is_empty() {
+                pending.push((self.child(index, true), sides.front));
+            }
+            if !sides.back.is_empty() {
+                pending.push((self.child(index, false), sides.back));
+            }
+        }
+    }
+
+    /// The parts of the polygons outside this solid.
+    fn clip_polygons(&self, polygons: Vec<Polygon>) -> Vec<Polygon> {
+        let mut kept = Vec::new();
+        let mut pending = vec![(0, polygons)];
+        while let Some((index, polygons)) = pending.pop() {
+            let node = &self.nodes[index];
+            let Some(plane) = node.plane else {
+                kept.extend(polygons);
+                continue;
+            };
+            let mut sides = Sides::default();
+            for polygon in polygons {
+                plane.split(polygon, &mut sides);
+            }
+            sides.front.append(&mut sides.coplanar_front);
+            sides.back.append(&mut sides.coplanar_back);
+            match node.front {
+                Some(front) => pending.push((front, sides.front)),
+                None => kept.extend(sides.front),
+            }
+            // Anything behind a leaf is inside the solid
+            if let Some(back) = node.back {
+                pending.push((back, sides.back));
+            }
+        }
+        kept
+    }
+
+    /// Removes everything in this tree that lies inside `other`.
+    fn clip_to(&mut self, other: &Bsp) {
+        for node in &mut self.nodes {
+            node.polygons = other.clip_polygons(mem::take(&mut node.polygons));
+        }
+    }
+
+    /// Turns the solid inside out.
+    fn invert(&mut self) {
+        for node in &mut self.nodes {
+            for polygon in &mut node.polygons {
+                polygon.flip();
+            }
+            node.plane = node.plane.map(|plane| plane.flip());
+            mem::swap(&mut node.front, &mut node.back);
+        }
+    }
+
+    fn into_polygons(self) -> Vec<Polygon> {
+        self.nodes
+            .into_iter()
+            .flat_map(|node| node.polygons)
+            .collect()
+    }
+}
+
+/// Every triangle of the meshes, tagged with its mesh's place in the list after `first_part`.
+fn polygons(parts: &[Mesh], first_part: usize) -> Vec<Polygon> {
+    parts
+        .iter()
+        .enumerate()
+        .flat_map(|(i, mesh)| {
+            mesh.triangles().filter_map(move |(corners, _)| {
+                let [p0, p1, p2] = corners.map(|corner| position(&corner));
+                Some(Polygon {
+                    corners: corners.to_vec(),
+                    plane: Plane::through(p0, p1, p2)?,
+                    part: first_part + i,
+                })
+            })
+        })
+        .collect()
+}
+
+impl Boolean {
+    /// Combines the solid made of the meshes in `a` with the one made of those in `b`,
+    /// returning what is left of each mesh in the same order. Each solid should be closed,
+    /// with its faces wound counterclockwise from outside. The surface a difference leaves
+    /// inside the first solid comes from the second, turned to face the hollow.
+    pub fn apply(self, a: &[Mesh], b: &[Mesh]) -> (Vec<Mesh>, Vec<Mesh>) {
+        let mut first = Bsp::new(polygons(a, 0));
+        let mut second = Bsp::new(polygons(b, a.len()));
+        // The classic sequence of clips from Evan Wallace's csg.js
+        match self {
+            Boolean::Union => {
+                first.clip_to(&second);
+                second.clip_to(&first);
+                second.invert();
+                second.clip_to(&first);
+                second.invert();
+                first.build(second.into_polygons());
+            }
+            Boolean::Intersection => {
+                first.invert();
+                second.clip_to(&first);
+                second.invert();
+                first.clip_to(&second);
+                second.clip_to(&first);
+                first.build(second.into_polygons());
+                first.invert();
+            }
+            Boolean::Difference => {
+                first.invert();
+                first.clip_to(&second);
+                second.clip_to(&first);
+                second.invert();
+                second.clip_to(&first);
+                second.invert();
+                first.build(second.into_polygons());
+                first.invert();
+            }
+        }
+
+        let mut meshes = vec![Mesh::default(); a.len() + b.len()];
+        for polygon in first.into_polygons() {
+            let mesh = &mut meshes[polygon.part];
+            let corners: Vec<usize> = polygon
+                .corners
+                .iter()
+                .map(|corner| mesh.add_corner(corner))
+                .collect();
+            for i in 1..corners.len() - 1 {
+                mesh.add_face(corners[0], corners[i], corners[i + 1]);
+            }
+        }
+        let second_parts = meshes.split_off(a.len());
+        (meshes, second_parts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Boolean;
+    use crate::{
+        matrix::{tests::volume, Mesh},
+        shapes3d::{Cube, Shape, Sphere, Tessellation},
+    };
+
+    fn solid(shape: &dyn Shape) -> Vec<Mesh> {
+        let mut p = Mesh::default();
+        shape.tessellate(&mut p, &Tessellation::new(1.0, 0.1));
+        vec![p]
+    }
+
+    #[test]
+    fn overlapping_boxes_combine_by_volume() {
+        // Two 10-unit boxes sharing a 5-unit corner
+        let a = solid(&Cube::new((0.0, 10.0, 0.0), 10.0, 10.0, 10.0));
+        let b = solid(&Cube::new((5.0, 15.0, -5.0), 10.0, 10.0, 10.0));
+        assert!((volume(&a) - 1000.0).abs() < 1e-9);
+        for (operation, expected) in [
+            (Boolean::Union, 1875.0),
+            (Boolean::Intersection, 125.0),
+            (Boolean::Difference, 875.0),
+        ] {
+            let (first, second) = operation.apply(&a, &b);
+            let total = volume(&first) + volume(&second);
+            assert!((total - expected).abs() < 1e-6, "{:?} {}", operation, total);
+        }
+    }
+
+    #[test]
+    fn holes_are_lined_by_the_shape_cut_out() {
+        let cube = solid(&Cube::new((-10.0, 10.0, 10.0), 20.0, 20.0, 20.0));
+        let sphere = solid(&Sphere::new(8.0, (0.0, 0.0, 10.0)));
+        let (outside, lining) = Boolean::Difference.apply(&cube, &sphere);
+        assert!(lining[0].get_poly_count() > 0);
+        // The lining faces in toward the hollow, and every vertex normal agrees with its face
+        for (corners, normal) in lining[0].triangles() {
+            let (x, y, z, _) = corners[0].position;
+            let out_from_center = crate::Vector3D::new(x, y, z - 10.0);
+            assert!(normal.dot(&out_from_center) < 0.0);
+            for corner in corners {
+                assert!(corner.normal.dot(&normal) > 0.0, "{:?}", corner);
+            }
+        }
+        // Half the ball sticks out of the front face, so half of it is carved away
+        let carved = volume(&outside) + volume(&lining);
+        let hollow = volume(&sphere) / 2.0;
+        assert!((carved - (8000.0 - hollow)).abs() < 1e-6, "{}", carved);
+    }
+}
@@ -192,7 +192,6 @@ where
         result.iter_mut().enumerate().for_each(|(r, row)| {
             row.iter_mut().enumerate().for_each(|(c, ele)| {
                 *ele = (0..self.get_width())
-                    .into_iter()
                     .map(|index| *(self.at(r, index)) * *(rhs.at(index, c)))
                     .sum();
             })
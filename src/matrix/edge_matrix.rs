@@ -75,12 +75,13 @@ impl Mul for EdgeMatrix {
 }
 
 impl<'data> IntoIterator for &'data EdgeMatrix {
-    type Item = ((f64, f64, f64), (f64, f64, f64));
+    type Item = ((f64, f64, f64, f64), (f64, f64, f64, f64));
     type IntoIter = Tuples<
         Zip<(
             Copied<slice::Iter<'data, f64>>,
             Copied<slice::Iter<'data, f64>>,
             Copied<slice::Iter<'data, f64>>,
+            Copied<slice::Iter<'data, f64>>,
         )>,
         Self::Item,
     >;
@@ -90,6 +91,7 @@ impl<'data> IntoIterator for &'data EdgeMatrix {
             self.matrix[0].iter().copied(),
             self.matrix[1].iter().copied(),
             self.matrix[2].iter().copied(),
+            self.matrix[3].iter().copied(),
         ))
         .tuples()
     }
@@ -0,0 +1,221 @@
+use std::{collections::HashMap, sync::Arc};
+
+use ordered_float::OrderedFloat;
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+
+use crate::Vector3D;
+
+use super::Const2D;
+
+type OrderedPoint = (OrderedFloat<f64>, OrderedFloat<f64>, OrderedFloat<f64>);
+
+/// One corner of a triangle, as handed to a renderer.
+#[derive(Clone, Copy, Debug)]
+pub struct Corner {
+    pub position: (f64, f64, f64, f64),
+    pub normal: Vector3D,
+    /// Where the vertex sat before any transforms, for textures that should stick to the shape.
+    pub object: Vector3D,
+}
+
+/// Triangles over a shared vertex buffer. A transform touches each vertex once, however many
+/// triangles meet there, and carries the vertex normals along instead of rebuilding them.
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    positions: Vec<(f64, f64, f64, f64)>,
+    normals: Vec<Vector3D>,
+    object_positions: Arc<Vec<Vector3D>>,
+    /// Three vertex indices per triangle, counterclockwise when seen from outside.
+    triangles: Arc<Vec<[usize; 3]>>,
+    /// Vertices made by `add_triangle`, by position, so later triangles there can share them.
+    welded: HashMap<OrderedPoint, usize>,
+}
+
+impl Mesh {
+    /// Adds a vertex with a known normal, returning its index for `add_face`.
+    pub fn add_vertex(&mut self, (x, y, z): (f64, f64, f64), normal: Vector3D) -> usize {
+        self.positions.push((x, y, z, 1.0));
+        self.normals.push(normal);
+        Arc::make_mut(&mut self.object_positions).push(Vector3D::new(x, y, z));
+        self.positions.len() - 1
+    }
+
+    /// Adds a vertex copied from a corner of another mesh, keeping where it sat before any
+    /// transforms so textures stay put.
+    pub fn add_corner(&mut self, corner: &Corner) -> usize {
+        let (x, y, z, w) = corner.position;
+        self.positions.push((x / w, y / w, z / w, 1.0));
+        self.normals.push(corner.normal);
+        Arc::make_mut(&mut self.object_positions).push(corner.object);
+        self.positions.len() - 1
+    }
+
+    /// Adds a triangle between three existing vertices.
+    pub fn add_face(&mut self, a: usize, b: usize, c: usize) {
+        Arc::make_mut(&mut self.triangles).push([a, b, c]);
+    }
+
+    /// Adds a triangle by its corners. Corners landing on the same point as a corner from an
+    /// earlier call share its vertex, whose normal becomes the average of the faces around it.
+    pub fn add_triangle(&mut self, p0: (f64, f64, f64), p1: (f64, f64, f64), p2: (f64, f64, f64)) {
+        let cross = Vector3D::from_points(p0, p1).cross(&Vector3D::from_points(p0, p2));
+        let face_normal = if cross.magnitude() > 0.0 {
+            cross.normalize()
+        } else {
+            Vector3D::new(0.0, 0.0, 0.0)
+        };
+        let corners = [p0, p1, p2].map(|point| {
+            let key = (
+                OrderedFloat(point.0),
+                OrderedFloat(point.1),
+                OrderedFloat(point.2),
+            );
+            let index = match self.welded.get(&key) {
+                Some(&index) => index,
+                None => {
+                    let index = self.add_vertex(point, Vector3D::new(0.0, 0.0, 0.0));
+                    self.welded.insert(key, index);
+                    index
+                }
+            };
+            self.normals[index] = self.normals[index] + face_normal;
+            index
+        });
+        self.add_face(corners[0], corners[1], corners[2]);
+    }
+
+    /// Adds a triangle with vertices of its own, so it stays flat under any shading.
+    pub fn add_facet(&mut self, p0: (f64, f64, f64), p1: (f64, f64, f64), p2: (f64, f64, f64)) {
+        let normal = Vector3D::from_points(p0, p1).cross(&Vector3D::from_points(p0, p2));
+        let corners = [p0, p1, p2].map(|point| self.add_vertex(point, normal));
+        self.add_face(corners[0], corners[1], corners[2]);
+    }
+
+    pub fn get_poly_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn get_vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Every vertex position, once each.
+    pub fn points(&self) -> impl Iterator<Item = Vector3D> + '_ {
+        self.positions
+            .iter()
+            .map(|&(x, y, z, w)| Vector3D::new(x / w, y / w, z / w))
+    }
+
+    /// The corners of a triangle, with the normal of its face as wound.
+    fn triangle(&self, indices: &[usize; 3]) -> ([Corner; 3], Vector3D) {
+        let corners = indices.map(|i| Corner {
+            position: self.positions[i],
+            normal: self.normals[i].normalize(),
+            object: self.object_positions[i],
+        });
+        let [p0, p1, p2] = corners.map(|corner| {
+            let (x, y, z, _) = corner.position;
+            (x, y, z)
+        });
+        let normal = Vector3D::from_points(p0, p1)
+            .cross(&Vector3D::from_points(p0, p2))
+            .normalize();
+        (corners, normal)
+    }
+
+    pub fn triangles(&self) -> impl Iterator<Item = ([Corner; 3], Vector3D)> + '_ {
+        self.triangles.iter().map(|indices| self.triangle(indices))
+    }
+
+    pub fn par_triangles(
+        &self,
+    ) -> impl IndexedParallelIterator<Item = ([Corner; 3], Vector3D)> + '_ {
+        self.triangles
+            .par_iter()
+            .map(|indices| self.triangle(indices))
+    }
+
+    /// Applies a transform to every vertex. Normals go through the inverse transpose of its
+    /// linear part, so they stay perpendicular to the surface under uneven scaling.
+    pub fn transform(&self, matrix: &Const2D<f64, 4, 4>) -> Mesh {
+        let positions = self
+            .positions
+            .par_iter()
+            .map(|&(x, y, z, w)| {
+                let row = |r: usize| {
+                    matrix[r][0] * x + matrix[r][1] * y + matrix[r][2] * z + matrix[r][3] * w
+                };
+                (row(0), row(1), row(2), row(3))
+            })
+            .collect();
+
+        // The cofactor matrix is the inverse transpose scaled by the determinant, and the
+        // scale is normalized away. Only its sign matters, for mirroring transforms.
+        let rows = [0, 1, 2].map(|r| Vector3D::new(matrix[r][0], matrix[r][1], matrix[r][2]));
+        let cofactors = [
+            rows[1].cross(&rows[2]),
+            rows[2].cross(&rows[0]),
+            rows[0].cross(&rows[1]),
+        ];
+        let sign = rows[0].dot(&cofactors[0]).signum();
+        let normals = self
+            .normals
+            .par_iter()
+            .map(|normal| {
+                Vector3D::new(
+                    cofactors[0].dot(normal),
+                    cofactors[1].dot(normal),
+                    cofactors[2].dot(normal),
+                )
+                .scale(sign)
+            })
+            .collect();
+
+        Mesh {
+            positions,
+            normals,
+            object_positions: Arc::clone(&self.object_positions),
+            triangles: Arc::clone(&self.triangles),
+            welded: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mesh;
+    use crate::{Axis, Transformer, Vector3D};
+
+    #[test]
+    fn add_triangle_shares_and_smooths_vertices() {
+        let mut mesh = Mesh::default();
+        mesh.add_triangle((0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (0.0, 1.0, 0.0));
+        mesh.add_triangle((0.0, 0.0, 0.0), (0.0, 1.0, 0.0), (0.0, 0.0, -1.0));
+        assert_eq!(mesh.get_poly_count(), 2);
+        assert_eq!(mesh.get_vertex_count(), 4);
+
+        let (corners, _) = mesh.triangles().next().unwrap();
+        let shared = corners[0].normal;
+        let expected = Vector3D::new(-1.0, 0.0, 1.0).normalize();
+        assert!((shared.dot(&expected) - 1.0).abs() < 1e-12, "{:?}", shared);
+    }
+
+    #[test]
+    fn normals_follow_uneven_scaling() {
+        // A slope facing up and to the right, squashed sideways
+        let mut mesh = Mesh::default();
+        let normal = Vector3D::new(1.0, 1.0, 0.0).normalize();
+        let a = mesh.add_vertex((1.0, 0.0, 0.0), normal);
+        let b = mesh.add_vertex((0.0, 1.0, 1.0), normal);
+        let c = mesh.add_vertex((0.0, 1.0, 0.0), normal);
+        mesh.add_face(a, c, b);
+
+        let mut t = Transformer::default();
+        t.scale(2.0, 1.0, 1.0);
+        t.rotate(Axis::Z, 0.3);
+        let mesh = t.apply_mesh(&mesh);
+        let (corners, face_normal) = mesh.triangles().next().unwrap();
+        assert!((corners[0].normal.dot(&face_normal) - 1.0).abs() < 1e-12);
+        assert_eq!(corners[0].object.x, 1.0);
+    }
+}
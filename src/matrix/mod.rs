@@ -10,5 +10,35 @@ pub use edge_matrix::EdgeMatrix;
 mod parallel_grid;
 pub use parallel_grid::ParallelGrid;
 
-mod polygon_matrix;
-pub use polygon_matrix::PolygonMatrix;
+mod mesh;
+pub use mesh::{Corner, Mesh};
+
+mod obj;
+
+mod csg;
+pub use csg::Boolean;
+
+mod subdivision;
+pub use subdivision::{Subdivision, SubdivisionScheme};
+
+#[cfg(test)]
+mod tests {
+    use super::Mesh;
+    use crate::Vector3D;
+
+    /// The volume closed meshes enclose, summed from the tetrahedra their triangles make with
+    /// the origin. Faces wound inside out count against it.
+    pub fn volume<'a>(meshes: impl IntoIterator<Item = &'a Mesh>) -> f64 {
+        meshes
+            .into_iter()
+            .flat_map(|mesh| mesh.triangles())
+            .map(|(corners, _)| {
+                let [p0, p1, p2] = corners.map(|corner| {
+                    let (x, y, z, w) = corner.position;
+                    Vector3D::new(x / w, y / w, z / w)
+                });
+                p0.dot(&p1.cross(&p2)) / 6.0
+            })
+            .sum()
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+};
+
+use super::Mesh;
+
+impl Mesh {
+    /// Reads the vertices and faces of a Wavefront OBJ file. Faces with more than three
+    /// corners are cut into fans of triangles, and corners shared between faces get the
+    /// average of their normals. Texture coordinates, normals, groups and materials in the
+    /// file are skipped.
+    pub fn load_obj(filename: &str) -> io::Result<Self> {
+        parse_obj(&fs::read_to_string(filename)?)
+    }
+}
+
+fn parse_obj(text: &str) -> io::Result<Mesh> {
+    let mut points: Vec<(f64, f64, f64)> = Vec::new();
+    let mut mesh = Mesh::default();
+    for (number, line) in text.lines().enumerate() {
+        let invalid = |message: &str| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Line {}: {}", number + 1, message),
+            )
+        };
+        let mut tokens = line.split('#').next().unwrap_or("").split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                let coordinates = tokens
+                    .take(3)
+                    .map(str::parse::<f64>)
+                    .collect::<Result<Vec<f64>, _>>()
+                    .map_err(|_| invalid("Malformed vertex"))?;
+                let [x, y, z] = coordinates[..] else {
+                    return Err(invalid("A vertex needs x, y and z"));
+                };
+                points.push((x, y, z));
+            }
+            Some("f") => {
+                let corners = tokens
+                    .map(|token| {
+                        // Only the position is used from `v/vt/vn`, counting from 1, or back
+                        // from the latest vertex when negative
+                        let index = token
+                            .split('/')
+                            .next()
+                            .and_then(|index| index.parse::<isize>().ok())
+                            .ok_or_else(|| invalid("Malformed face"))?;
+                        let position = if index < 0 {
+                            points.len().checked_sub(index.unsigned_abs())
+                        } else {
+                            (index as usize).checked_sub(1)
+                        };
+                        position
+                            .and_then(|position| points.get(position).copied())
+                            .ok_or_else(|| invalid(&format!("No vertex numbered {}", index)))
+                    })
+                    .collect::<io::Result<Vec<_>>>()?;
+                if corners.len() < 3 {
+                    return Err(invalid("A face needs at least three corners"));
+                }
+                for i in 1..corners.len() - 1 {
+                    mesh.add_triangle(corners[0], corners[i], corners[i + 1]);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_obj;
+
+    #[test]
+    fn obj_faces_become_triangles() {
+        let square = "# a unit square\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\n\
+                      f 1//1 2//1 3//1 4//1\n";
+        let mesh = parse_obj(square).unwrap();
+        assert_eq!(mesh.get_poly_count(), 2);
+        assert_eq!(mesh.get_vertex_count(), 4);
+        for (_, normal) in mesh.triangles() {
+            assert!((normal.z - 1.0).abs() < 1e-12);
+        }
+        // Negative indices count back from the latest vertex
+        let relative = parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n").unwrap();
+        assert_eq!(relative.get_poly_count(), 1);
+    }
+
+    #[test]
+    fn malformed_obj_is_rejected() {
+        assert!(parse_obj("v 0 0\n").is_err());
+        assert!(parse_obj("v 0 0 0\nv 1 0 0\nf 1 2\n").is_err());
+        assert!(parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n").is_err());
+        assert!(parse_obj("f 0 1 2\n").is_err());
+    }
+}
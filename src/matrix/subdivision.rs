@@ -0,0 +1,527 @@
+use std::{
+    collections::{HashMap, HashSet},
+    f64::consts::TAU,
+};
+
+use ordered_float::OrderedFloat;
+
+use super::{Corner, Mesh};
+use crate::Vector3D;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubdivisionScheme {
+    /// Splits every triangle into four. Larger faces are cut into triangles first.
+    Loop,
+    /// Splits every face into one quad per side. Pairs of triangles lying flat together are
+    /// joined back into the quads they were most likely cut from first, so a box rounds off
+    /// evenly instead of along its diagonals.
+    CatmullClark,
+}
+
+/// Smooths a mesh by repeatedly splitting its faces and easing each point toward its
+/// neighbours.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Subdivision {
+    pub scheme: SubdivisionScheme,
+    pub levels: usize,
+    /// Edges where faces meet at more than this many degrees stay sharp, and so do the normals
+    /// either side of them. Edges with only one face, around holes, always keep their line.
+    pub crease_angle: Option<f64>,
+}
+
+/// Weights of the old points that make up a new one.
+type Stencil = Vec<(usize, f64)>;
+
+/// An edge by its points, the lower one first, with the faces either side of it.
+type Edge = ((usize, usize), Vec<usize>);
+
+/// A mesh welded into one point per position, with faces of any number of sides.
+struct Polyhedron {
+    points: Vec<Vector3D>,
+    /// Where each point sat before any transforms, carried along for textures.
+    objects: Vec<Vector3D>,
+    /// Counterclockwise from outside, as in a `Mesh`.
+    faces: Vec<Vec<usize>>,
+    /// Edges that stay sharp, by their points with the lower one first.
+    creases: HashSet<(usize, usize)>,
+}
+
+fn edge(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+/// Adds `other`, scaled by `weight`, to a stencil.
+fn blend(stencil: &mut Stencil, other: &[(usize, f64)], weight: f64) {
+    stencil.extend(other.iter().map(|&(point, w)| (point, w * weight)));
+}
+
+impl Polyhedron {
+    fn from_mesh(mesh: &Mesh, crease_angle: Option<f64>) -> Self {
+        let mut welded = HashMap::new();
+        let (mut points, mut objects, mut faces) = (Vec::new(), Vec::new(), Vec::new());
+        for (corners, _) in mesh.triangles() {
+            let face = corners.map(|corner| {
+                let (x, y, z, w) = corner.position;
+                let point = Vector3D::new(x / w, y / w, z / w);
+                let key = (
+                    OrderedFloat(point.x),
+                    OrderedFloat(point.y),
+                    OrderedFloat(point.z),
+                );
+                *welded.entry(key).or_insert_with(|| {
+                    points.push(point);
+                    objects.push(corner.object);
+                    points.len() - 1
+                })
+            });
+            if face[0] != face[1] && face[1] != face[2] && face[2] != face[0] {
+                faces.push(face.to_vec());
+            }
+        }
+
+        let mut solid = Self {
+            points,
+            objects,
+            faces,
+            creases: HashSet::new(),
+        };
+        let normals = solid.normals();
+        let sharp = crease_angle.map(|angle| angle.to_radians().cos());
+        let (edges, _) = solid.edges();
+        solid.creases = edges
+            .into_iter()
+            .filter(|(_, faces)| match faces[..] {
+                [f, g] => sharp.is_some_and(|sharp| {
+                    normals[f].normalize().dot(&normals[g].normalize()) < sharp
+                }),
+                _ => true,
+            })
+            .map(|(key, _)| key)
+            .collect();
+        solid
+    }
+
+    /// Each face's normal, as long as twice its area.
+    fn normals(&self) -> Vec<Vector3D> {
+        self.faces
+            .iter()
+            .map(|face| {
+                // Newell's method, which holds up for faces that are not quite flat
+                (0..face.len())
+                    .map(|i| {
+                        let (a, b) = (
+                            self.points[face[i]],
+                            self.points[face[(i + 1) % face.len()]],
+                        );
+                        Vector3D::new(
+                            (a.y - b.y) * (a.z + b.z),
+                            (a.z - b.z) * (a.x + b.x),
+                            (a.x - b.x) * (a.y + b.y),
+                        )
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Every edge once, in the order the faces first use them, with the faces either side, and
+    /// where to find each edge in that list.
+    fn edges(&self) -> (Vec<Edge>, HashMap<(usize, usize), usize>) {
+        let (mut edges, mut lookup) = (Vec::new(), HashMap::new());
+        for (f, face) in self.faces.iter().enumerate() {
+            for i in 0..face.len() {
+                let key = edge(face[i], face[(i + 1) % face.len()]);
+                let index = *lookup.entry(key).or_insert_with(|| {
+                    edges.push((key, Vec::new()));
+                    edges.len() - 1
+                });
+                edges[index].1.push(f);
+            }
+        }
+        (edges, lookup)
+    }
+
+    /// The edges and faces meeting at each point.
+    fn around(&self, edges: &[Edge]) -> Vec<(Vec<usize>, Vec<usize>)> {
+        let mut around = vec![(Vec::new(), Vec::new()); self.points.len()];
+        for (i, &((a, b), _)) in edges.iter().enumerate() {
+            around[a].0.push(i);
+            around[b].0.push(i);
+        }
+        for (f, face) in self.faces.iter().enumerate() {
+            for &point in face {
+                around[point].1.push(f);
+            }
+        }
+        around
+    }
+
+    /// Builds the next level from a stencil per new point. Crease edges carry on through the
+    /// points made on them.
+    fn refine(&self, stencils: Vec<Stencil>, faces: Vec<Vec<usize>>, edges: &[Edge]) -> Self {
+        let apply = |values: &[Vector3D], stencil: &Stencil| {
+            stencil
+                .iter()
+                .map(|&(point, weight)| values[point].scale(weight))
+                .sum::<Vector3D>()
+        };
+        let count = self.points.len();
+        let creases = edges
+            .iter()
+            .enumerate()
+            .filter(|(_, (key, _))| self.creases.contains(key))
+            .flat_map(|(i, &((a, b), _))| [edge(a, count + i), edge(count + i, b)])
+            .collect();
+        Self {
+            points: stencils
+                .iter()
+                .map(|stencil| apply(&self.points, stencil))
+                .collect(),
+            objects: stencils
+                .iter()
+                .map(|stencil| apply(&self.objects, stencil))
+                .collect(),
+            faces,
+            creases,
+        }
+    }
+
+    /// The crease rule for a point on exactly two sharp edges, or `None` if the point is smooth
+    /// enough for the scheme's own rule. Points where more creases meet stay where they are.
+    fn crease_stencil(&self, point: usize, edges: &[Edge], around: &[usize]) -> Option<Stencil> {
+        let ends: Vec<usize> = around
+            .iter()
+            .map(|&i| edges[i].0)
+            .filter(|key| self.creases.contains(key))
+            .map(|(a, b)| if a == point { b } else { a })
+            .collect();
+        match ends[..] {
+            [] | [_] => None,
+            [a, b] => Some(vec![(point, 0.75), (a, 0.125), (b, 0.125)]),
+            _ => Some(vec![(point, 1.0)]),
+        }
+    }
+
+    /// Joins pairs of triangles that lie flat together into convex quads.
+    fn join_quads(&mut self) {
+        let (edges, lookup) = self.edges();
+        let normals = self.normals();
+        let mut joined = vec![false; self.faces.len()];
+        for f in 0..self.faces.len() {
+            if joined[f] || self.faces[f].len() != 3 {
+                continue;
+            }
+            for i in 0..3 {
+                let face = &self.faces[f];
+                let (a, b, c) = (face[i], face[(i + 1) % 3], face[(i + 2) % 3]);
+                let [f0, f1] = edges[lookup[&edge(a, b)]].1[..] else {
+                    continue;
+                };
+                let g = if f0 == f { f1 } else { f0 };
+                if joined[g]
+                    || self.faces[g].len() != 3
+                    || normals[f].normalize().dot(&normals[g].normalize()) < 1.0 - 1e-9
+                {
+                    continue;
+                }
+                let Some(&d) = self.faces[g]
+                    .iter()
+                    .find(|&&point| point != a && point != b)
+                else {
+                    continue;
+                };
+                let quad = [a, d, b, c];
+                let convex = (0..4).all(|k| {
+                    let [p0, p1, p2] = [k, k + 1, k + 2].map(|k| self.points[quad[k % 4]]);
+                    (p1 - p0).cross(&(p2 - p1)).dot(&normals[f]) > 0.0
+                });
+                if convex {
+                    self.faces[f] = quad.to_vec();
+                    joined[f] = true;
+                    joined[g] = true;
+                    break;
+                }
+            }
+        }
+        let mut f = 0;
+        self.faces.retain(|face| {
+            f += 1;
+            face.len() != 3 || !joined[f - 1]
+        });
+    }
+
+    fn triangulate(&mut self) {
+        self.faces = self
+            .faces
+            .iter()
+            .flat_map(|face| (1..face.len() - 1).map(|i| vec![face[0], face[i], face[i + 1]]))
+            .collect();
+    }
+
+    fn catmull_clark(&self) -> Self {
+        let (edges, lookup) = self.edges();
+        let around = self.around(&edges);
+        let face_points: Vec<Stencil> = self
+            .faces
+            .iter()
+            .map(|face| {
+                let weight = 1.0 / face.len() as f64;
+                face.iter().map(|&point| (point, weight)).collect()
+            })
+            .collect();
+
+        let vertex_points = around.iter().enumerate().map(|(point, (near, faces))| {
+            if let Some(stencil) = self.crease_stencil(point, &edges, near) {
+                return stencil;
+            }
+            let n = near.len() as f64;
+            if near.len() < 3 || faces.is_empty() {
+                return vec![(point, 1.0)];
+            }
+            // The average of the faces around, twice the average of the edges' middles, and
+            // the point itself the rest of the way
+            let mut stencil = vec![(point, (n - 3.0) / n)];
+            for &f in faces {
+                blend(
+                    &mut stencil,
+                    &face_points[f],
+                    1.0 / (faces.len() as f64 * n),
+                );
+            }
+            for &i in near {
+                let (a, b) = edges[i].0;
+                blend(&mut stencil, &[(a, 0.5), (b, 0.5)], 2.0 / (n * n));
+            }
+            stencil
+        });
+        let edge_points = edges.iter().map(|(key @ (a, b), faces)| {
+            let mut stencil = vec![(*a, 0.5), (*b, 0.5)];
+            if let ([f, g], false) = (&faces[..], self.creases.contains(key)) {
+                stencil = vec![(*a, 0.25), (*b, 0.25)];
+                blend(&mut stencil, &face_points[*f], 0.25);
+                blend(&mut stencil, &face_points[*g], 0.25);
+            }
+            stencil
+        });
+        let stencils = vertex_points
+            .chain(edge_points)
+            .chain(face_points.iter().cloned())
+            .collect();
+
+        let (points, edge_count) = (self.points.len(), edges.len());
+        let faces = self
+            .faces
+            .iter()
+            .enumerate()
+            .flat_map(|(f, face)| {
+                let k = face.len();
+                let middle = |a: usize, b: usize| points + lookup[&edge(a, b)];
+                (0..k).map(move |i| {
+                    let (previous, point, next) =
+                        (face[(i + k - 1) % k], face[i], face[(i + 1) % k]);
+                    vec![
+                        point,
+                        middle(point, next),
+                        points + edge_count + f,
+                        middle(previous, point),
+                    ]
+                })
+            })
+            .collect();
+        self.refine(stencils, faces, &edges)
+    }
+
+    /// Expects every face to be a triangle.
+    fn loop_subdivide(&self) -> Self {
+        let (edges, lookup) = self.edges();
+        let around = self.around(&edges);
+
+        let vertex_points = around.iter().enumerate().map(|(point, (near, _))| {
+            if let Some(stencil) = self.crease_stencil(point, &edges, near) {
+                return stencil;
+            }
+            let n = near.len() as f64;
+            if near.len() < 3 {
+                return vec![(point, 1.0)];
+            }
+            // Loop's own weights for the neighbours
+            let beta = (0.625 - (0.375 + 0.25 * (TAU / n).cos()).powi(2)) / n;
+            let mut stencil = vec![(point, 1.0 - n * beta)];
+            stencil.extend(near.iter().map(|&i| {
+                let (a, b) = edges[i].0;
+                (if a == point { b } else { a }, beta)
+            }));
+            stencil
+        });
+        let edge_points = edges.iter().map(|(key @ (a, b), faces)| match faces[..] {
+            [f, g] if !self.creases.contains(key) => {
+                let mut stencil = vec![(*a, 0.375), (*b, 0.375)];
+                for face in [f, g] {
+                    let opposite = self.faces[face]
+                        .iter()
+                        .find(|&&point| point != *a && point != *b);
+                    stencil.extend(opposite.map(|&point| (point, 0.125)));
+                }
+                stencil
+            }
+            _ => vec![(*a, 0.5), (*b, 0.5)],
+        });
+        let stencils = vertex_points.chain(edge_points).collect();
+
+        let points = self.points.len();
+        let faces = self
+            .faces
+            .iter()
+            .flat_map(|face| {
+                let [a, b, c] = [face[0], face[1], face[2]];
+                let middle = |p: usize, q: usize| points + lookup[&edge(p, q)];
+                let (ab, bc, ca) = (middle(a, b), middle(b, c), middle(c, a));
+                [
+                    vec![a, ab, ca],
+                    vec![ab, b, bc],
+                    vec![ca, bc, c],
+                    vec![ab, bc, ca],
+                ]
+            })
+            .collect();
+        self.refine(stencils, faces, &edges)
+    }
+
+    /// Triangles with a normal per corner, averaged over the faces around each point that
+    /// meet the corner's own face within the crease angle.
+    fn into_mesh(self, crease_angle: Option<f64>) -> Mesh {
+        let normals = self.normals();
+        let sharp = crease_angle.map(|angle| angle.to_radians().cos());
+        let mut faces_at = vec![Vec::new(); self.points.len()];
+        for (f, face) in self.faces.iter().enumerate() {
+            for &point in face {
+                faces_at[point].push(f);
+            }
+        }
+
+        let mut p = Mesh::default();
+        // Corners already added at each point, by normal
+        let mut added: Vec<Vec<(Vector3D, usize)>> = vec![Vec::new(); self.points.len()];
+        for (f, face) in self.faces.iter().enumerate() {
+            let corners: Vec<usize> = face
+                .iter()
+                .map(|&point| {
+                    let normal = faces_at[point]
+                        .iter()
+                        .filter(|&&g| {
+                            sharp.is_none_or(|sharp| {
+                                normals[f].normalize().dot(&normals[g].normalize()) >= sharp
+                            })
+                        })
+                        .map(|&g| normals[g])
+                        .sum::<Vector3D>();
+                    let same = |other: &Vector3D| {
+                        (other.x, other.y, other.z) == (normal.x, normal.y, normal.z)
+                    };
+                    if let Some(&(_, index)) = added[point].iter().find(|(n, _)| same(n)) {
+                        return index;
+                    }
+                    let position = self.points[point];
+                    let index = p.add_corner(&Corner {
+                        position: (position.x, position.y, position.z, 1.0),
+                        normal,
+                        object: self.objects[point],
+                    });
+                    added[point].push((normal, index));
+                    index
+                })
+                .collect();
+            for i in 1..corners.len() - 1 {
+                p.add_face(corners[0], corners[i], corners[i + 1]);
+            }
+        }
+        p
+    }
+}
+
+impl Subdivision {
+    pub fn apply(&self, mesh: &Mesh) -> Mesh {
+        let mut solid = Polyhedron::from_mesh(mesh, self.crease_angle);
+        match self.scheme {
+            SubdivisionScheme::Loop => solid.triangulate(),
+            SubdivisionScheme::CatmullClark => solid.join_quads(),
+        }
+        for _ in 0..self.levels {
+            solid = match self.scheme {
+                SubdivisionScheme::Loop => solid.loop_subdivide(),
+                SubdivisionScheme::CatmullClark => solid.catmull_clark(),
+            };
+        }
+        solid.into_mesh(self.crease_angle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Subdivision, SubdivisionScheme};
+    use crate::{
+        matrix::{tests::volume, Mesh},
+        shapes3d::{Cube, Icosphere, Shape, Tessellation},
+        Vector3D,
+    };
+
+    #[test]
+    fn catmull_clark_rounds_a_box_unless_creased() {
+        let mut cube = Mesh::default();
+        Cube::new((-10.0, 10.0, 10.0), 20.0, 20.0, 20.0)
+            .tessellate(&mut cube, &Tessellation::new(1.0, 0.1));
+        let smooth = |crease_angle| Subdivision {
+            scheme: SubdivisionScheme::CatmullClark,
+            levels: 2,
+            crease_angle,
+        };
+
+        // Each side is one quad again, split in four twice
+        let rounded = smooth(None).apply(&cube);
+        assert_eq!(rounded.get_poly_count(), 6 * 16 * 2);
+        let inside = volume([&rounded]);
+        assert!(inside > 0.0 && inside < 8000.0, "{}", inside);
+        for (corners, normal) in rounded.triangles() {
+            let (x, y, z, _) = corners[0].position;
+            assert!(normal.dot(&Vector3D::new(x, y, z)) > 0.0);
+            // Smooth all over, so every corner leans the way of its face
+            for corner in corners {
+                assert!(corner.normal.normalize().dot(&normal) > 0.5, "{:?}", corner);
+            }
+        }
+
+        // With every edge sharp, the box keeps its shape and its flat sides
+        let creased = smooth(Some(30.0)).apply(&cube);
+        assert_eq!(creased.get_poly_count(), 6 * 16 * 2);
+        assert!((volume([&creased]) - 8000.0).abs() < 1e-6);
+        for (corners, normal) in creased.triangles() {
+            for corner in corners {
+                assert!((corner.normal.normalize().dot(&normal) - 1.0).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn loop_keeps_a_sphere_closed() {
+        let mut sphere = Mesh::default();
+        Icosphere::new(10.0, (0.0, 0.0, 0.0)).tessellate(
+            &mut sphere,
+            &Tessellation::new(1.0, 0.1).with_subdivisions(0),
+        );
+        let smoothed = Subdivision {
+            scheme: SubdivisionScheme::Loop,
+            levels: 2,
+            crease_angle: None,
+        }
+        .apply(&sphere);
+        assert_eq!(smoothed.get_poly_count(), 20 * 16);
+        // Every point is shared, so no cracks open up between faces
+        assert_eq!(smoothed.get_vertex_count(), 10 * 16 + 2);
+        // Points are pulled in toward the middle, but not far
+        for point in smoothed.points() {
+            let radius = point.magnitude();
+            assert!(radius > 7.0 && radius < 10.0, "{}", radius);
+        }
+        assert!(volume([&smoothed]) > 0.0);
+    }
+}
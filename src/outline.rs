@@ -0,0 +1,129 @@
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    color::{color_constants, HdrColor},
+    matrix::{Dynamic2D, ParallelGrid},
+    parser, Vector3D,
+};
+
+/// Ink lines along silhouettes and creases, found from jumps in the supersampled depth and
+/// normal buffers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OutlineSettings {
+    pub color: HdrColor,
+    /// Line thickness in output pixels.
+    pub width: f64,
+    /// How far, in scene units, a neighbor has to fall away before the nearer surface gets a
+    /// silhouette line.
+    pub depth_threshold: f64,
+    /// Smallest angle between neighboring normals, in degrees, that counts as a crease.
+    pub crease_angle: f64,
+}
+
+impl Default for OutlineSettings {
+    fn default() -> Self {
+        Self {
+            color: color_constants::BLACK.into(),
+            width: 1.5,
+            depth_threshold: 5.0,
+            crease_angle: 40.0,
+        }
+    }
+}
+
+impl OutlineSettings {
+    /// Which samples lie on a line. Only the nearer side of a depth jump is marked, so
+    /// silhouettes hug the object instead of spilling onto whatever is behind it.
+    pub fn edges(&self, zbuffer: &Dynamic2D<f64>, normals: &[Vector3D]) -> Dynamic2D<bool> {
+        let (width, height) = (zbuffer.get_width(), zbuffer.get_height());
+        // Silhouettes are drawn on one side of the jump and creases on both, so creases look
+        // half as far to come out the same width
+        let reach = (self.width * parser::SAMPLE_SCALE).round().max(1.0) as isize;
+        let crease_reach = (reach / 2).max(1);
+        let depth_threshold = self.depth_threshold * parser::SAMPLE_SCALE;
+        let cos_crease = self.crease_angle.to_radians().cos();
+
+        let mut edges: Dynamic2D<bool> = Dynamic2D::new(width, height);
+        (&mut edges)
+            .into_par_iter()
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, edge) in row.iter_mut().enumerate() {
+                    let z = zbuffer[y][x];
+                    if z == f64::NEG_INFINITY {
+                        continue;
+                    }
+                    let normal = normals[y * width + x];
+                    // Surfaces seen edge-on fall away steeply by themselves; that drop alone
+                    // is not a silhouette
+                    let slope = (1.0 - normal.z * normal.z).max(0.0).sqrt() / normal.z.max(0.1);
+                    let allowed_drop = depth_threshold + slope * reach as f64;
+                    let neighbor = |dx: isize, dy: isize| {
+                        let (nx, ny) = (x as isize + dx, y as isize + dy);
+                        if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                            None
+                        } else {
+                            Some((nx as usize, ny as usize))
+                        }
+                    };
+                    let silhouette = [(reach, 0), (-reach, 0), (0, reach), (0, -reach)]
+                        .into_iter()
+                        .filter_map(|(dx, dy)| neighbor(dx, dy))
+                        .any(|(nx, ny)| z - zbuffer[ny][nx] > allowed_drop);
+                    let crease = || {
+                        [
+                            (crease_reach, 0),
+                            (-crease_reach, 0),
+                            (0, crease_reach),
+                            (0, -crease_reach),
+                        ]
+                        .into_iter()
+                        .filter_map(|(dx, dy)| neighbor(dx, dy))
+                        .any(|(nx, ny)| {
+                            zbuffer[ny][nx] != f64::NEG_INFINITY
+                                && normal.dot(&normals[ny * width + nx]) < cos_crease
+                        })
+                    };
+                    *edge = silhouette || crease();
+                }
+            });
+        edges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OutlineSettings;
+    use crate::{matrix::Dynamic2D, Vector3D};
+
+    const SIZE: usize = 32;
+
+    #[test]
+    fn silhouette_marks_near_side_only() {
+        let mut zbuffer = Dynamic2D::fill(f64::NEG_INFINITY, SIZE, SIZE);
+        for row in &mut zbuffer {
+            for z in row[..SIZE / 2].iter_mut() {
+                *z = 0.0;
+            }
+        }
+        let normals = vec![Vector3D::new(0.0, 0.0, 1.0); SIZE * SIZE];
+        let edges = OutlineSettings::default().edges(&zbuffer, &normals);
+        assert!(edges[SIZE / 2][SIZE / 2 - 1]);
+        assert!(!edges[SIZE / 2][SIZE / 2]);
+        assert!(!edges[SIZE / 2][0]);
+    }
+
+    #[test]
+    fn crease_is_marked() {
+        let zbuffer = Dynamic2D::fill(0.0, SIZE, SIZE);
+        let normals: Vec<Vector3D> = (0..SIZE * SIZE)
+            .map(|i| {
+                let side = if i % SIZE < SIZE / 2 { -1.0 } else { 1.0 };
+                Vector3D::new(side, 0.0, 1.0).normalize()
+            })
+            .collect();
+        let edges = OutlineSettings::default().edges(&zbuffer, &normals);
+        assert!(edges[SIZE / 2][SIZE / 2]);
+        assert!(!edges[SIZE / 2][SIZE - 1]);
+    }
+}
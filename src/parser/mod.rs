@@ -5,30 +5,37 @@ use pest::{
     Parser,
 };
 use pest_derive::Parser;
-use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator,
+};
 use std::{
     collections::{BTreeMap, HashMap},
     error::Error,
     fs,
     io::{Read, Write},
+    mem,
     num::{ParseFloatError, ParseIntError},
     process::{Command, Stdio},
+    sync::Arc,
     time::Instant,
 };
 
 use crate::{
+    background::ImageCache,
     color::color_constants,
-    curves::{Bezier, Circle, Hermite, Parametric},
+    curves::{BSpline, Bezier, BezierCurve, CatmullRom, Circle, Hermite, Parametric, Polyline},
     image::ShadingMethod,
-    lighter::LightingConfig,
-    matrix::{EdgeMatrix, PolygonMatrix},
+    lighter::{LightingConfig, Material, PbrConfig, SpecularModel},
+    matrix::{Boolean, EdgeMatrix, Mesh, Subdivision, SubdivisionScheme},
     shapes3d::*,
-    Axis, Color, Image, TStack, Transformer, Vector3D,
+    Axis, BackdropFit, Background, Color, FilterKind, Fog, FogMode, Image, LightSource,
+    OutlineSettings, Pattern, SamplePattern, ShadowMap, ShadowSettings, SsaoSettings, TStack,
+    Texture, ToneMapOperator, ToneMapper, Transformer, Vector3D,
 };
 
 #[derive(Clone, Debug)]
 pub enum OutputType {
-    Image(Frame),
+    Image(Box<Frame>),
     Animation(Vec<Frame>),
 }
 
@@ -44,35 +51,49 @@ pub const SCREEN_SIZE: usize = 500;
 pub const SAMPLE_SCALE: f64 = 4.0;
 pub const FINAL_SCREEN_SIZE: usize = SCREEN_SIZE * SAMPLE_SCALE as usize;
 
+/// A mesh with what it is drawn in.
+type Part = (Mesh, Material, ShadingMethod);
+
 #[derive(Clone, Debug)]
 pub struct Frame {
     image: Box<Image<FINAL_SCREEN_SIZE, FINAL_SCREEN_SIZE>>,
     t: TStack,
-    constants: HashMap<String, LightingConfig>,
+    constants: HashMap<String, Material>,
     knob_map: Option<HashMap<String, f64>>,
     shading_method: Option<ShadingMethod>,
+    /// Furthest, in pixels, a curved shape's facets may stray from its true surface.
+    tolerance: f64,
+    shadows: Option<ShadowSettings>,
+    /// Geometry held back until the frame is output, so shadow maps can see all of it.
+    scene: Vec<Part>,
+    /// Booleans waiting on their shapes, innermost last, with the operands drawn so far. Each
+    /// operand is a list of parts, since a finished boolean keeps each shape's own material.
+    booleans: Vec<(Boolean, Vec<Vec<Part>>)>,
+    /// Smoothing waiting for the next shape drawn.
+    subdivision: Option<Subdivision>,
+    /// Backdrop images read so far, shared with every frame cloned from this one.
+    backdrops: ImageCache,
 }
 
 const DEFAULT_LIGHTING_CONFIG: LightingConfig = LightingConfig {
     ka: (0.1, 0.1, 0.1),
     kd: (0.5, 0.5, 0.5),
     ks: (0.5, 0.5, 0.5),
+    shininess: LightingConfig::DEFAULT_SHININESS,
+    specular: SpecularModel::Phong,
+    texture: None,
+    opacity: 1.0,
 };
-const SIDE_LENGTH: f64 = 10.0;
+const DEFAULT_TOLERANCE: f64 = 0.1;
 
-#[derive(Clone, Copy, Debug, Hash)]
+#[derive(Clone, Copy, Debug, Default, Hash)]
 pub enum InterpolationMethod {
+    #[default]
     Linear,
     Exponential,
     Logarithmic,
 }
 
-impl Default for InterpolationMethod {
-    fn default() -> Self {
-        Self::Linear
-    }
-}
-
 impl MDLParser {
     fn next<'i>(args: &mut impl Iterator<Item = Pair<'i, Rule>>) -> &'i str {
         args.next().unwrap().as_str()
@@ -88,6 +109,114 @@ impl MDLParser {
         MDLParser::next(args).parse::<u8>()
     }
 
+    /// The values of a curve rule after its keyword, and any knots given after those.
+    fn curve_values(curve: Pair<Rule>) -> Result<(Rule, Vec<f64>, Vec<f64>), ParseFloatError> {
+        let mut parts = curve.into_inner();
+        let keyword = parts.next().unwrap().as_rule();
+        let (mut values, mut knots) = (Vec::new(), Vec::new());
+        let mut into = &mut values;
+        for part in parts {
+            match part.as_rule() {
+                Rule::KNOTS => into = &mut knots,
+                _ => into.push(part.as_str().parse::<f64>()?),
+            }
+        }
+        Ok((keyword, values, knots))
+    }
+
+    fn pairs(values: &[f64], curve: &str) -> Result<Vec<(f64, f64)>, String> {
+        if !values.len().is_multiple_of(2) {
+            return Err(format!("{} needs an x and a y for every point", curve));
+        }
+        if values.len() < 4 {
+            return Err(format!("{} needs at least two points", curve));
+        }
+        Ok(values.chunks(2).map(|point| (point[0], point[1])).collect())
+    }
+
+    fn triples(values: &[f64], curve: &str) -> Result<Vec<(f64, f64, f64)>, String> {
+        if !values.len().is_multiple_of(3) {
+            return Err(format!("{} needs an x, a y and a z for every point", curve));
+        }
+        if values.len() < 6 {
+            return Err(format!("{} needs at least two points", curve));
+        }
+        Ok(values
+            .chunks(3)
+            .map(|point| (point[0], point[1], point[2]))
+            .collect())
+    }
+
+    /// The curve a `PATH` rule describes, or what is wrong with it.
+    fn path(path: Pair<Rule>) -> Result<Arc<dyn Parametric + Send + Sync>, Box<dyn Error>> {
+        let (keyword, v, knots) = MDLParser::curve_values(path)?;
+        Ok(match keyword {
+            Rule::BEZIER if v.len() == 8 => Arc::new(Bezier::new(
+                (v[0], v[1]),
+                (v[2], v[3]),
+                (v[4], v[5]),
+                (v[6], v[7]),
+            )),
+            Rule::BEZIER => match MDLParser::triples(&v, "Bezier")?[..] {
+                [p0, p1, p2, p3] => Arc::new(Bezier::new_3d(p0, p1, p2, p3)),
+                ref points => Arc::new(BezierCurve::new(points.to_vec())),
+            },
+            Rule::HERMITE if v.len() == 12 => Arc::new(Hermite::new_3d(
+                (v[0], v[1], v[2]),
+                (v[3], v[4], v[5]),
+                (v[6], v[7], v[8]),
+                (v[9], v[10], v[11]),
+            )),
+            Rule::HERMITE => Arc::new(Hermite::new(
+                (v[0], v[1]),
+                (v[2], v[3]),
+                (v[4], v[5]),
+                (v[6], v[7]),
+            )),
+            Rule::CIRCLE => Arc::new(Circle::new(v[3], (v[0], v[1], v[2]))),
+            Rule::BSPLINE => {
+                if v[0].fract() != 0.0 {
+                    return Err(format!(
+                        "A B-spline's degree must be a whole number, not {}",
+                        v[0]
+                    )
+                    .into());
+                }
+                let degree = v[0] as usize;
+                let points = MDLParser::triples(&v[1..], "B-spline")?;
+                let knots = if knots.is_empty() {
+                    BSpline::uniform_knots(degree, points.len())
+                } else {
+                    knots
+                };
+                BSpline::check(degree, points.len(), &knots)?;
+                Arc::new(BSpline::new(degree, points, knots))
+            }
+            Rule::CATMULLROM => Arc::new(CatmullRom::new(MDLParser::triples(&v, "Catmull-Rom")?)),
+            _ => Arc::new(Polyline::new(MDLParser::pairs(&v, "Polyline")?)),
+        })
+    }
+
+    /// The closed outline a `PROFILE` rule describes, around its own origin. Polygons are
+    /// closed up for you.
+    fn profile(profile: Pair<Rule>) -> Result<Arc<dyn Parametric + Send + Sync>, Box<dyn Error>> {
+        let (keyword, v, _) = MDLParser::curve_values(profile)?;
+        Ok(match keyword {
+            Rule::CIRCLE => Arc::new(Circle::new(v[0], (0.0, 0.0, 0.0))),
+            Rule::BEZIER => Arc::new(Bezier::new(
+                (v[0], v[1]),
+                (v[2], v[3]),
+                (v[4], v[5]),
+                (v[6], v[7]),
+            )),
+            _ => {
+                let mut points = MDLParser::pairs(&v, "Polygon")?;
+                points.push(points[0]);
+                Arc::new(Polyline::new(points))
+            }
+        })
+    }
+
     fn next_usize<'i>(
         args: &mut impl Iterator<Item = Pair<'i, Rule>>,
     ) -> Result<usize, ParseIntError> {
@@ -344,6 +473,7 @@ impl MDLParser {
                         let time = Instant::now();
                         frame
                             .parse_command(local_parse_result)
+                            .and_then(|_| frame.flush_scene())
                             .expect("Command parse failed");
                         println!("Drew frame {} in {:?}.", i, time.elapsed());
                         frame
@@ -398,39 +528,105 @@ impl MDLParser {
 
 impl Frame {
     fn parse_command(&mut self, mut parse_result: Pairs<Rule>) -> Result<(), Box<dyn Error>> {
+        // Occlusion needs the normal of every sample, so ssao is set up before anything is
+        // drawn, wherever it appears
+        parse_result
+            .clone()
+            .filter(|command| command.as_rule() == Rule::SSAO_ARGS)
+            .try_for_each(|command| self.set_ssao(&mut command.into_inner().skip(1)))?;
+
         parse_result.try_for_each(|command| -> Result<(), Box<dyn Error>> {
             let mut args = command.clone().into_inner().skip(1);
 
             match command.as_rule() {
                 Rule::CONSTANTS_SHORT_ARGS => self.process_constants(&mut args),
+                Rule::CONSTANTS_PBR_ARGS => self.process_pbr_constants(&mut args),
+                Rule::MATERIAL_ARGS => self.process_material(&mut args),
+                Rule::OPACITY_ARGS => self.set_opacity(&mut args),
                 Rule::LINE_DDDDDD => self.line(&mut args),
-                Rule::CIRCLE_DDDD => self.circle(&mut args),
-                Rule::HERMITE_DDDDDDDD => self.hermite(&mut args),
-                Rule::BEZIER_DDDDDDDD => self.bezier(&mut args),
+                Rule::PATH => self.path(command.clone()),
                 Rule::BOX_DDDDDD => self.cube(&mut args, false),
                 Rule::BOX_SDDDDDD => self.cube(&mut args, true),
                 Rule::SPHERE_DDDD => self.sphere(&mut args, false),
                 Rule::SPHERE_SDDDD => self.sphere(&mut args, true),
                 Rule::TORUS_DDDDD => self.torus(&mut args, false),
                 Rule::TORUS_SDDDDD => self.torus(&mut args, true),
+                Rule::CYLINDER_DDDDD => self.cylinder(&mut args, false, true),
+                Rule::CYLINDER_SDDDDD => self.cylinder(&mut args, true, true),
+                Rule::TUBE_DDDDD => self.cylinder(&mut args, false, false),
+                Rule::TUBE_SDDDDD => self.cylinder(&mut args, true, false),
+                Rule::CONE_DDDDD => self.cone(&mut args, false),
+                Rule::CONE_SDDDDD => self.cone(&mut args, true),
+                Rule::PYRAMID_DDDDD => self.pyramid(&mut args, false),
+                Rule::PYRAMID_SDDDDD => self.pyramid(&mut args, true),
+                Rule::PLANE_DDDDDD => self.plane(&mut args, false),
+                Rule::PLANE_SDDDDDD => self.plane(&mut args, true),
+                Rule::ICOSPHERE_DDDDD => self.icosphere(&mut args, false),
+                Rule::ICOSPHERE_SDDDDD => self.icosphere(&mut args, true),
+                Rule::LATHE_ARGS => self.lathe(&mut args),
+                Rule::SWEEP_ARGS => self.sweep(&mut args),
+                Rule::EXTRUDE_ARGS => self.extrude(&mut args),
+                Rule::PATCHES_ARGS => self.patches(&mut args),
+                Rule::PATCH_ARGS => self.patch(&mut args),
+                Rule::MESH_CS => self.mesh(&mut args, false),
+                Rule::MESH_SCS => self.mesh(&mut args, true),
+                Rule::SUBDIVIDE_ARGS => self.set_subdivision(&mut args),
+                Rule::BOOLEAN => {
+                    self.booleans.push((
+                        match command.as_str() {
+                            "union" => Boolean::Union,
+                            "intersection" => Boolean::Intersection,
+                            _ => Boolean::Difference,
+                        },
+                        Vec::new(),
+                    ));
+                    Ok(())
+                }
                 Rule::SCALE_DDD => self.scale(&mut args),
                 Rule::SCALE_DDDS => self.scale(&mut args),
                 Rule::MOVE_DDD => self.translate(&mut args),
                 Rule::MOVE_DDDS => self.translate(&mut args),
                 Rule::ROTATE_SD => self.rotate(&mut args),
                 Rule::ROTATE_SDS => self.rotate(&mut args),
-                Rule::TPUSH => Ok(self.t.push_copy()),
-                Rule::TPOP => Ok(self.t.pop()),
+                Rule::TPUSH => {
+                    self.t.push_copy();
+                    Ok(())
+                }
+                Rule::TPOP => {
+                    self.t.pop();
+                    Ok(())
+                }
                 Rule::SET_ARG => Ok(()),
                 Rule::LIGHT_ARGS => self.light(&mut args),
                 Rule::MOVING_LIGHT => self.moving_light(&mut args),
+                Rule::POINT_LIGHT_ARGS => self.point_light(&mut args),
+                Rule::SPOT_LIGHT_ARGS => self.spot_light(&mut args),
                 Rule::SHADING_ARG => self.set_shading(&mut args),
-                Rule::CLEAR => Ok(self.image = Box::new(Image::new("result".to_string()))), // self.t = Default::default();
-                Rule::DISPLAY => Ok({
-                    self.image.display().ok();
-                }),
+                Rule::TONEMAP_ARGS => self.set_tone_map(&mut args),
+                Rule::SAMPLES_ARGS => self.set_samples(&mut args),
+                Rule::FILTER_ARGS => self.set_filter(&mut args),
+                Rule::SHADOWS_ARGS => self.set_shadows(&mut args),
+                Rule::OUTLINE_ARGS => self.set_outline(&mut args),
+                Rule::DETAIL_ARGS => self.set_detail(&mut args),
+                Rule::FOG_ARGS => self.set_fog(&mut args),
+                Rule::BACKGROUND_ARGS => self.set_background(&mut args),
+                Rule::CLEAR => {
+                    // self.t = Default::default();
+                    // Only what was drawn goes; lights, backdrop and passes stay set up
+                    self.image.clear_shapes_only();
+                    self.scene.clear();
+                    self.booleans.clear();
+                    self.subdivision = None;
+                    Ok(())
+                }
+                Rule::DISPLAY => {
+                    self.flush_scene()?;
+                    self.image.downsample().display().ok();
+                    Ok(())
+                }
                 Rule::SAVE_S => self.save(&mut args),
                 Rule::FRAMES_ARG => Ok(()),
+                Rule::SSAO_ARGS => Ok(()),
                 Rule::BASENAME_ARG => Ok(()),
                 Rule::VARY_ARGS => Ok(()),
                 Rule::TWEEN_ARGS => Ok(()),
@@ -438,7 +634,8 @@ impl Frame {
                 Rule::EOI => Ok(()),
                 _ => panic!("{} is unimplemented!", command.as_str()),
             }
-        })
+        })?;
+        self.check_booleans()
     }
 
     pub fn process_constants<'i>(
@@ -461,14 +658,115 @@ impl Frame {
             MDLParser::next_f64(args)?,
             MDLParser::next_f64(args)?,
         );
+        let mut specular = SpecularModel::Phong;
+        let mut shininess = LightingConfig::DEFAULT_SHININESS;
+        for arg in args {
+            match arg.as_rule() {
+                Rule::SPECULAR_MODEL => {
+                    specular = match arg.as_str() {
+                        "phong" => SpecularModel::Phong,
+                        "blinn" => SpecularModel::BlinnPhong,
+                        _ => panic!("Unimplemented specular model"),
+                    }
+                }
+                _ => shininess = arg.as_str().parse::<f64>()?,
+            }
+        }
         self.constants.insert(
             name,
-            LightingConfig {
+            Material::Phong(LightingConfig {
                 ka: (reds.0, greens.0, blues.0),
                 kd: (reds.1, greens.1, blues.1),
                 ks: (reds.2, greens.2, blues.2),
-            },
+                shininess,
+                specular,
+                texture: None,
+                opacity: 1.0,
+            }),
+        );
+        Ok(())
+    }
+
+    pub fn process_pbr_constants<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let name = MDLParser::next(args).to_string();
+        MDLParser::next(args);
+        let base_color = Color::new(
+            MDLParser::next_u8(args)?,
+            MDLParser::next_u8(args)?,
+            MDLParser::next_u8(args)?,
         );
+        let metallic = MDLParser::next_f64(args)?;
+        let roughness = MDLParser::next_f64(args)?;
+        self.constants.insert(
+            name,
+            Material::Pbr(PbrConfig::new(base_color.into(), metallic, roughness)),
+        );
+        Ok(())
+    }
+
+    pub fn process_material<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let name = MDLParser::next(args).to_string();
+        let mut material = Material::Phong(DEFAULT_LIGHTING_CONFIG);
+        let mut pattern = args.next().unwrap();
+        if pattern.as_rule() == Rule::STRING {
+            material = self.constants[pattern.as_str()];
+            pattern = args.next().unwrap();
+        }
+        let pattern = match pattern.as_str() {
+            "checker" => Pattern::Checker,
+            "stripes" => Pattern::Stripes,
+            "gradient" => Pattern::Gradient,
+            "noise" => Pattern::Noise,
+            "marble" => Pattern::Marble,
+            "wood" => Pattern::Wood,
+            _ => panic!("Unimplemented texture pattern"),
+        };
+        let scale = MDLParser::next_f64(args)?;
+        let first = Color::new(
+            MDLParser::next_u8(args)?,
+            MDLParser::next_u8(args)?,
+            MDLParser::next_u8(args)?,
+        );
+        let second = Color::new(
+            MDLParser::next_u8(args)?,
+            MDLParser::next_u8(args)?,
+            MDLParser::next_u8(args)?,
+        );
+        material.set_texture(Texture::new(pattern, scale, first, second));
+        self.constants.insert(name, material);
+        Ok(())
+    }
+
+    pub fn set_opacity<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let name = MDLParser::next(args);
+        let opacity = MDLParser::next_f64(args)?;
+        self.constants
+            .get_mut(name)
+            .unwrap_or_else(|| panic!("No constants named {}", name))
+            .set_opacity(opacity);
+        Ok(())
+    }
+
+    /// Draws a curve as lines, in as many pieces as it needs to look smooth at its size on
+    /// screen.
+    pub fn path(&mut self, path: Pair<Rule>) -> Result<(), Box<dyn Error>> {
+        let curve = MDLParser::path(path)?;
+        let mut e: EdgeMatrix = Default::default();
+        curve
+            .flatten(self.tolerance / self.t.top().screen_scale())
+            .windows(2)
+            .for_each(|window| e.add_edge(curve.f(window[0]), curve.f(window[1])));
+        e = self.t.top().apply_edges(&e);
+        self.image.draw_matrix(&e, color_constants::WHITE);
         Ok(())
     }
 
@@ -494,12 +792,36 @@ impl Frame {
         Ok(())
     }
 
-    pub fn circle<'i>(
+    pub fn cube<'i>(
         &mut self,
         args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+        use_constant: bool,
     ) -> Result<(), Box<dyn Error>> {
-        let mut e: EdgeMatrix = Default::default();
+        let material = self.shape_material(args, use_constant);
+        let ltf = (
+            MDLParser::next_f64(args)?,
+            MDLParser::next_f64(args)?,
+            MDLParser::next_f64(args)?,
+        );
+        let width = MDLParser::next_f64(args)?;
+        let height = MDLParser::next_f64(args)?;
+        let depth = MDLParser::next_f64(args)?;
+
+        self.draw_shape(
+            &Cube::new(ltf, width, height, depth),
+            None,
+            material,
+            ShadingMethod::Flat,
+        );
+        Ok(())
+    }
 
+    pub fn sphere<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+        use_constant: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let material = self.shape_material(args, use_constant);
         let center = (
             MDLParser::next_f64(args)?,
             MDLParser::next_f64(args)?,
@@ -507,160 +829,425 @@ impl Frame {
         );
         let radius = MDLParser::next_f64(args)?;
 
-        const SIDE_LENGTH: f64 = 5.0;
-        let point_count = std::f64::consts::TAU * radius / SIDE_LENGTH;
-        let circle = Circle::new(radius, center);
-        circle
-            .points(point_count as usize)
-            .windows(2)
-            .for_each(|window| e.add_edge(window[0], window[1]));
-
-        e = self.t.top().apply_edges(&e);
-        self.image.draw_matrix(&e, color_constants::WHITE);
+        self.draw_shape(
+            &Sphere::new(radius, center),
+            None,
+            material,
+            ShadingMethod::Phong,
+        );
         Ok(())
     }
 
-    pub fn hermite<'i>(
+    pub fn torus<'i>(
         &mut self,
         args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+        use_constant: bool,
     ) -> Result<(), Box<dyn Error>> {
-        let mut e: EdgeMatrix = Default::default();
+        let material = self.shape_material(args, use_constant);
+        let center = (
+            MDLParser::next_f64(args)?,
+            MDLParser::next_f64(args)?,
+            MDLParser::next_f64(args)?,
+        );
+        let thickness = MDLParser::next_f64(args)?;
+        let radius = MDLParser::next_f64(args)?;
 
-        let p0 = (MDLParser::next_f64(args)?, MDLParser::next_f64(args)?);
-        let p1 = (MDLParser::next_f64(args)?, MDLParser::next_f64(args)?);
-        let r0 = (MDLParser::next_f64(args)?, MDLParser::next_f64(args)?);
-        let r1 = (MDLParser::next_f64(args)?, MDLParser::next_f64(args)?);
-        let hermite = Hermite::new(p0, p1, r0, r1);
-        hermite
-            .points(50)
-            .windows(2)
-            .for_each(|window| e.add_edge(window[0], window[1]));
-        e = self.t.top().apply_edges(&e);
-        self.image.draw_matrix(&e, color_constants::WHITE);
+        self.draw_shape(
+            &Torus::new(thickness, radius, center),
+            None,
+            material,
+            ShadingMethod::Phong,
+        );
         Ok(())
     }
 
-    pub fn bezier<'i>(
+    /// `cylinder` closes both ends, `tube` leaves them open.
+    pub fn cylinder<'i>(
         &mut self,
         args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+        use_constant: bool,
+        capped: bool,
     ) -> Result<(), Box<dyn Error>> {
-        let mut e: EdgeMatrix = Default::default();
+        let material = self.shape_material(args, use_constant);
+        let center = (
+            MDLParser::next_f64(args)?,
+            MDLParser::next_f64(args)?,
+            MDLParser::next_f64(args)?,
+        );
+        let radius = MDLParser::next_f64(args)?;
+        let height = MDLParser::next_f64(args)?;
 
-        let p0 = (MDLParser::next_f64(args)?, MDLParser::next_f64(args)?);
-        let p1 = (MDLParser::next_f64(args)?, MDLParser::next_f64(args)?);
-        let p2 = (MDLParser::next_f64(args)?, MDLParser::next_f64(args)?);
-        let p3 = (MDLParser::next_f64(args)?, MDLParser::next_f64(args)?);
-        let bezier = Bezier::new(p0, p1, p2, p3);
-        bezier
-            .points(50)
-            .windows(2)
-            .for_each(|window| e.add_edge(window[0], window[1]));
-        e = self.t.top().apply_edges(&e);
-        self.image.draw_matrix(&e, color_constants::WHITE);
+        self.draw_shape(
+            &Cylinder::new(radius, height, center, capped),
+            None,
+            material,
+            ShadingMethod::Phong,
+        );
         Ok(())
     }
 
-    pub fn cube<'i>(
+    pub fn cone<'i>(
         &mut self,
         args: &mut impl Iterator<Item = Pair<'i, Rule>>,
         use_constant: bool,
     ) -> Result<(), Box<dyn Error>> {
-        let mut p: PolygonMatrix = Default::default();
-
-        let mut light_conf = None;
-        if use_constant {
-            let constant = MDLParser::next(args);
-            light_conf = Some(&self.constants[constant]);
-        }
-        let ltf = (
+        let material = self.shape_material(args, use_constant);
+        let center = (
             MDLParser::next_f64(args)?,
             MDLParser::next_f64(args)?,
             MDLParser::next_f64(args)?,
         );
-        let width = MDLParser::next_f64(args)?;
+        let radius = MDLParser::next_f64(args)?;
         let height = MDLParser::next_f64(args)?;
-        let depth = MDLParser::next_f64(args)?;
-
-        let cube = Cube::new(ltf, width, height, depth);
-        cube.add_to_matrix(&mut p);
 
-        p = self.t.top().apply_poly(&p);
-        self.image.draw_polygons(
-            &p,
-            light_conf.unwrap_or(&DEFAULT_LIGHTING_CONFIG),
-            self.shading_method.unwrap_or(ShadingMethod::Flat),
+        self.draw_shape(
+            &Cone::new(radius, height, center),
+            None,
+            material,
+            ShadingMethod::Phong,
         );
         Ok(())
     }
 
-    pub fn sphere<'i>(
+    pub fn pyramid<'i>(
         &mut self,
         args: &mut impl Iterator<Item = Pair<'i, Rule>>,
         use_constant: bool,
     ) -> Result<(), Box<dyn Error>> {
-        let mut p: PolygonMatrix = Default::default();
-
-        let mut light_conf = None;
-        if use_constant {
-            let constant = MDLParser::next(args);
-            light_conf = Some(&self.constants[constant]);
-        }
+        let material = self.shape_material(args, use_constant);
         let center = (
             MDLParser::next_f64(args)?,
             MDLParser::next_f64(args)?,
             MDLParser::next_f64(args)?,
         );
-        let radius = MDLParser::next_f64(args)?;
+        let side = MDLParser::next_f64(args)?;
+        let height = MDLParser::next_f64(args)?;
 
-        let point_count = std::f64::consts::TAU * radius / SIDE_LENGTH;
+        self.draw_shape(
+            &Pyramid::new(side, height, center),
+            None,
+            material,
+            ShadingMethod::Flat,
+        );
+        Ok(())
+    }
 
-        let sphere = Sphere::new(radius, center);
-        sphere.add_to_matrix(&mut p, point_count as usize);
+    pub fn plane<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+        use_constant: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let material = self.shape_material(args, use_constant);
+        let corner = (
+            MDLParser::next_f64(args)?,
+            MDLParser::next_f64(args)?,
+            MDLParser::next_f64(args)?,
+        );
+        let width = MDLParser::next_f64(args)?;
+        let depth = MDLParser::next_f64(args)?;
+        let divisions = MDLParser::next_f64(args)?;
 
-        p = self.t.top().apply_poly(&p);
-        self.image.draw_polygons(
-            &p,
-            light_conf.unwrap_or(&DEFAULT_LIGHTING_CONFIG),
-            self.shading_method.unwrap_or(ShadingMethod::Phong),
+        self.draw_shape(
+            &Plane::new(corner, width, depth),
+            Some(divisions as usize),
+            material,
+            ShadingMethod::Flat,
         );
         Ok(())
     }
 
-    pub fn torus<'i>(
+    pub fn icosphere<'i>(
         &mut self,
         args: &mut impl Iterator<Item = Pair<'i, Rule>>,
         use_constant: bool,
     ) -> Result<(), Box<dyn Error>> {
-        let mut p: PolygonMatrix = Default::default();
-
-        let mut light_conf = None;
-        if use_constant {
-            let constant = MDLParser::next(args);
-            light_conf = Some(&self.constants[constant]);
-        }
+        let material = self.shape_material(args, use_constant);
         let center = (
             MDLParser::next_f64(args)?,
             MDLParser::next_f64(args)?,
             MDLParser::next_f64(args)?,
         );
-        let thickness = MDLParser::next_f64(args)?;
         let radius = MDLParser::next_f64(args)?;
+        let subdivisions = MDLParser::next_f64(args)?;
+
+        self.draw_shape(
+            &Icosphere::new(radius, center),
+            Some(subdivisions as usize),
+            material,
+            ShadingMethod::Phong,
+        );
+        Ok(())
+    }
+
+    /// Spins a profile curve around the upright line through `axis_x`, optionally in a fixed
+    /// number of pieces, as in `lathe [constants] axis_x [segments] [open] path`. Ends of the
+    /// profile are capped unless `open` is given.
+    pub fn lathe<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let args: Vec<Pair<Rule>> = args.collect();
+        let (material, args) = self.leading_material(&args);
+        let (profile, settings) = args.split_last().expect("Lathe is missing its profile");
+        let (open, settings) = match settings.split_last() {
+            Some((last, rest)) if last.as_rule() == Rule::LATHE_OPEN => (true, rest),
+            _ => (false, settings),
+        };
+        let axis_x = settings[0].as_str().parse::<f64>()?;
+        let segments = match settings.get(1) {
+            Some(segments) => Some(segments.as_str().parse::<f64>()? as usize),
+            None => None,
+        };
 
-        let ring_count = std::f64::consts::TAU * radius / SIDE_LENGTH;
-        let cir_count = std::f64::consts::TAU * thickness / SIDE_LENGTH;
+        self.draw_shape(
+            &Lathe::new(MDLParser::path(profile.clone())?, axis_x, !open),
+            segments,
+            material,
+            ShadingMethod::Phong,
+        );
+        Ok(())
+    }
 
-        let torus = Torus::new(thickness, radius, center);
-        torus.add_to_matrix(&mut p, ring_count as usize, cir_count as usize);
+    /// Carries an outline along a path, as in `sweep [constants] profile path`.
+    pub fn sweep<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let args: Vec<Pair<Rule>> = args.collect();
+        let (material, args) = self.leading_material(&args);
+        let profile = MDLParser::profile(args[0].clone())?;
+        let path = MDLParser::path(args[1].clone())?;
+
+        self.draw_shape(
+            &Sweep::new(profile, path, true),
+            None,
+            material,
+            ShadingMethod::Phong,
+        );
+        Ok(())
+    }
 
-        p = self.t.top().apply_poly(&p);
-        self.image.draw_polygons(
-            &p,
-            light_conf.unwrap_or(&DEFAULT_LIGHTING_CONFIG),
-            self.shading_method.unwrap_or(ShadingMethod::Phong),
+    /// Pushes an outline straight back from the xy plane, as in
+    /// `extrude [constants] depth profile`.
+    pub fn extrude<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let args: Vec<Pair<Rule>> = args.collect();
+        let (material, args) = self.leading_material(&args);
+        let depth = args[0].as_str().parse::<f64>()?;
+        let profile = MDLParser::profile(args[1].clone())?;
+
+        self.draw_shape(
+            &Sweep::extrusion(profile, depth),
+            None,
+            material,
+            ShadingMethod::Phong,
+        );
+        Ok(())
+    }
+
+    /// Draws a bicubic Bezier patch from 16 control points given row by row, as in
+    /// `patch [constants] x0 y0 z0 ... x15 y15 z15 [segments]`.
+    pub fn patch<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let args: Vec<Pair<Rule>> = args.collect();
+        let (material, args) = self.leading_material(&args);
+        let values = args
+            .iter()
+            .map(|arg| arg.as_str().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()?;
+        let points = MDLParser::triples(&values[..48], "Patch")?;
+        let mut grid = [[(0.0, 0.0, 0.0); 4]; 4];
+        for (k, point) in points.into_iter().enumerate() {
+            grid[k / 4][k % 4] = point;
+        }
+        let segments = values.get(48).map(|segments| *segments as usize);
+
+        self.draw_shape(
+            &BezierPatch::new(grid),
+            segments,
+            material,
+            ShadingMethod::Phong,
         );
         Ok(())
     }
 
+    /// Draws every patch in a file, as in `patches [constants] [segments] :filename`.
+    pub fn patches<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let args: Vec<Pair<Rule>> = args.collect();
+        let (material, args) = self.leading_material(&args);
+        let (filename, settings) = args.split_last().expect("Patches is missing its file");
+        let segments = match settings.first().filter(|arg| arg.as_rule() == Rule::DOUBLE) {
+            Some(segments) => Some(segments.as_str().parse::<f64>()? as usize),
+            None => None,
+        };
+
+        // Every patch is cut as finely as the most curved one needs, so neighbours split their
+        // shared edges at the same points and leave no cracks between them. They go into one
+        // mesh, so a boolean or a subdivide takes the whole file as a single shape
+        let patches = BezierPatch::load(filename.as_str())?;
+        let mut tessellation = self.tessellation(segments);
+        tessellation.subdivisions = patches
+            .iter()
+            .map(|patch| patch.segments(&tessellation))
+            .max();
+        let mut p: Mesh = Default::default();
+        for patch in &patches {
+            patch.tessellate(&mut p, &tessellation);
+        }
+        self.draw_mesh(p, material, ShadingMethod::Phong);
+        Ok(())
+    }
+
+    /// Draws the faces of a Wavefront OBJ file, as in `mesh [constants] :filename`.
+    pub fn mesh<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+        use_constant: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let material = self.shape_material(args, use_constant);
+        // Past the colon
+        args.next();
+        let filename = MDLParser::next(args);
+
+        self.draw_mesh(Mesh::load_obj(filename)?, material, ShadingMethod::Flat);
+        Ok(())
+    }
+
+    /// Splits off the material named by an optional first argument.
+    fn leading_material<'a, 'i>(
+        &self,
+        args: &'a [Pair<'i, Rule>],
+    ) -> (Option<Material>, &'a [Pair<'i, Rule>]) {
+        match args[0].as_rule() {
+            Rule::STRING => (Some(self.constants[args[0].as_str()]), &args[1..]),
+            _ => (None, args),
+        }
+    }
+
+    /// The material named at the start of a shape command, if it has one.
+    fn shape_material<'i>(
+        &self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+        use_constant: bool,
+    ) -> Option<Material> {
+        use_constant.then(|| self.constants[MDLParser::next(args)])
+    }
+
+    /// Tessellates any shape for its size on screen, places it with the top of the transform
+    /// stack and draws it, falling back on the default material and the shape's usual shading.
+    pub fn draw_shape(
+        &mut self,
+        shape: &dyn Shape,
+        subdivisions: Option<usize>,
+        material: Option<Material>,
+        shading: ShadingMethod,
+    ) {
+        let mut p: Mesh = Default::default();
+        shape.tessellate(&mut p, &self.tessellation(subdivisions));
+        self.draw_mesh(p, material, shading);
+    }
+
+    /// How finely to cut a shape drawn with the top of the transform stack.
+    fn tessellation(&mut self, subdivisions: Option<usize>) -> Tessellation {
+        let mut tessellation = Tessellation::new(self.t.top().screen_scale(), self.tolerance);
+        tessellation.subdivisions = subdivisions;
+        tessellation
+    }
+
+    /// Places a mesh with the top of the transform stack and draws it, smoothing it first if
+    /// a `subdivide` is waiting. Smoothed meshes are shaded smoothly, however blocky they were.
+    fn draw_mesh(&mut self, mut p: Mesh, material: Option<Material>, shading: ShadingMethod) {
+        let mut shading = shading;
+        if let Some(subdivision) = self.subdivision.take() {
+            p = subdivision.apply(&p);
+            shading = ShadingMethod::Phong;
+        }
+        p = self.t.top().apply_mesh(&p);
+        let material = material.unwrap_or(Material::Phong(DEFAULT_LIGHTING_CONFIG));
+        self.draw_polygons(p, material, self.shading_method.unwrap_or(shading));
+    }
+
+    /// Draws right away, or queues the polygons for `flush_scene` while shadows are on.
+    fn draw_polygons(&mut self, p: Mesh, material: Material, shading: ShadingMethod) {
+        self.draw_parts(vec![(p, material, shading)]);
+    }
+
+    /// Draws a solid made of parts that may differ in material, unless a boolean is waiting on
+    /// it. The second operand completes the boolean, and the result is drawn in its place.
+    fn draw_parts(&mut self, parts: Vec<Part>) {
+        let Some((_, operands)) = self.booleans.last_mut() else {
+            for (p, material, shading) in parts {
+                if self.shadows.is_some() {
+                    self.scene.push((p, material, shading));
+                } else {
+                    self.image.draw_polygons(&p, &material, shading);
+                }
+            }
+            return;
+        };
+        operands.push(parts);
+        if operands.len() < 2 {
+            return;
+        }
+
+        let (operation, mut operands) = self.booleans.pop().unwrap();
+        let (second, first) = (operands.pop().unwrap(), operands.pop().unwrap());
+        let meshes =
+            |parts: &[Part]| -> Vec<Mesh> { parts.iter().map(|(p, _, _)| p.clone()).collect() };
+        let (a, b) = operation.apply(&meshes(&first), &meshes(&second));
+        let combined = first
+            .into_iter()
+            .chain(second)
+            .zip(a.into_iter().chain(b))
+            .map(|((_, material, shading), p)| (p, material, shading))
+            .collect();
+        self.draw_parts(combined);
+    }
+
+    /// Renders a shadow map per light from everything queued so far, then draws the queue.
+    /// Lights added after a shape still light it, since nothing is shaded until now.
+    fn flush_scene(&mut self) -> Result<(), Box<dyn Error>> {
+        self.check_booleans()?;
+        let Some(settings) = self.shadows else {
+            return Ok(());
+        };
+        if self.scene.is_empty() {
+            return Ok(());
+        }
+        let scene = mem::take(&mut self.scene);
+        let meshes: Vec<Mesh> = scene.iter().map(|(p, _, _)| p.clone()).collect();
+        let shadow_maps = self
+            .image
+            .get_lighter()
+            .get_sources()
+            .par_iter()
+            .map(|light| Arc::new(ShadowMap::build(light, &meshes, settings)))
+            .collect();
+        self.image.get_lighter().set_shadow_maps(shadow_maps);
+        for (p, material, shading) in &scene {
+            self.image.draw_polygons(p, material, *shading);
+        }
+        self.image.get_lighter().set_shadow_maps(Vec::new());
+        Ok(())
+    }
+
+    /// Fails if a boolean never got both of its shapes.
+    fn check_booleans(&self) -> Result<(), Box<dyn Error>> {
+        match self.booleans.last() {
+            Some((operation, _)) => {
+                Err(format!("{:?} is still waiting for its shapes", operation).into())
+            }
+            None => Ok(()),
+        }
+    }
+
     pub fn scale<'i>(
         &mut self,
         args: &mut impl Iterator<Item = Pair<'i, Rule>>,
@@ -767,7 +1354,158 @@ impl Frame {
         let knob_name = MDLParser::next(args);
         let knob_value = *self.knob_map.as_ref().unwrap().get(knob_name).unwrap();
 
-        self.image.get_lighter().add_source(Vector3D::interpolate([(vector_first, 1.0 - knob_value), (vector_last, knob_value)].into_iter()), color);
+        self.image.get_lighter().add_source(
+            Vector3D::interpolate(
+                [(vector_first, 1.0 - knob_value), (vector_last, knob_value)].into_iter(),
+            ),
+            color,
+        );
+        Ok(())
+    }
+
+    /// Reads the optional constant, linear and quadratic attenuation applied to point and spot
+    /// lights.
+    fn attenuation<'i>(
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(f64, f64, f64), Box<dyn Error>> {
+        match args.next() {
+            Some(kc) => Ok((
+                kc.as_str().parse::<f64>()?,
+                MDLParser::next_f64(args)?,
+                MDLParser::next_f64(args)?,
+            )),
+            None => Ok(LightSource::NO_ATTENUATION),
+        }
+    }
+
+    pub fn point_light<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let color = Color::new(
+            MDLParser::next_u8(args)?,
+            MDLParser::next_u8(args)?,
+            MDLParser::next_u8(args)?,
+        );
+        let position = Vector3D::new(
+            MDLParser::next_f64(args)?,
+            MDLParser::next_f64(args)?,
+            MDLParser::next_f64(args)?,
+        );
+        let attenuation = Self::attenuation(args)?;
+        self.image
+            .get_lighter()
+            .add_light(LightSource::point(position, color, attenuation));
+        Ok(())
+    }
+
+    pub fn spot_light<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let color = Color::new(
+            MDLParser::next_u8(args)?,
+            MDLParser::next_u8(args)?,
+            MDLParser::next_u8(args)?,
+        );
+        let position = Vector3D::new(
+            MDLParser::next_f64(args)?,
+            MDLParser::next_f64(args)?,
+            MDLParser::next_f64(args)?,
+        );
+        let direction = Vector3D::new(
+            MDLParser::next_f64(args)?,
+            MDLParser::next_f64(args)?,
+            MDLParser::next_f64(args)?,
+        );
+        let inner = MDLParser::next_f64(args)?;
+        let outer = MDLParser::next_f64(args)?;
+        let attenuation = Self::attenuation(args)?;
+        self.image.get_lighter().add_light(LightSource::spot(
+            position,
+            direction,
+            color,
+            attenuation,
+            inner,
+            outer,
+        ));
+        Ok(())
+    }
+
+    pub fn set_shadows<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut settings: ShadowSettings = Default::default();
+        if let Some(bias) = args.next() {
+            settings.bias = bias.as_str().parse::<f64>()?;
+        }
+        if let Some(pcf_radius) = args.next() {
+            settings.pcf_radius = pcf_radius.as_str().parse::<f64>()? as usize;
+        }
+        if let Some(resolution) = args.next() {
+            settings.resolution = resolution.as_str().parse::<f64>()? as usize;
+        }
+        self.shadows = Some(settings);
+        Ok(())
+    }
+
+    /// Turns on ambient occlusion for the whole frame, as in `ssao [radius] [samples] [seed]`.
+    /// `parse_command` runs this before any drawing, so shapes drawn earlier in the script are
+    /// occluded too.
+    pub fn set_ssao<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut settings: SsaoSettings = Default::default();
+        if let Some(radius) = args.next() {
+            settings.radius = radius.as_str().parse::<f64>()?;
+        }
+        if let Some(samples) = args.next() {
+            settings.samples = samples.as_str().parse::<f64>()? as usize;
+        }
+        if let Some(seed) = args.next() {
+            settings.seed = seed.as_str().parse::<f64>()? as u64;
+        }
+        self.image.set_ssao(Some(settings));
+        Ok(())
+    }
+
+    /// Smooths the next shape drawn, as in `subdivide loop|catmullclark levels [crease_angle]`.
+    /// Each level quadruples the faces, so there are at most a few.
+    pub fn set_subdivision<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        const MAX_LEVELS: usize = 4;
+        let scheme = match MDLParser::next(args) {
+            "loop" => SubdivisionScheme::Loop,
+            _ => SubdivisionScheme::CatmullClark,
+        };
+        let levels = (MDLParser::next_f64(args)? as usize).min(MAX_LEVELS);
+        let crease_angle = match args.next() {
+            Some(angle) => Some(angle.as_str().parse::<f64>()?),
+            None => None,
+        };
+        self.subdivision = Some(Subdivision {
+            scheme,
+            levels,
+            crease_angle,
+        });
+        Ok(())
+    }
+
+    /// How far, in pixels, curved shapes drawn from here on may stray from their true
+    /// surface. Smaller is smoother and slower.
+    pub fn set_detail<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let tolerance = MDLParser::next_f64(args)?;
+        if tolerance <= 0.0 {
+            panic!("Detail tolerance must be positive, got {tolerance}");
+        }
+        self.tolerance = tolerance;
         Ok(())
     }
 
@@ -779,6 +1517,13 @@ impl Frame {
         self.shading_method = match shading_type_string {
             "flat" => Some(ShadingMethod::Flat),
             "phong" => Some(ShadingMethod::Phong),
+            "toon" => {
+                let bands = match args.next() {
+                    Some(bands) => bands.as_str().parse::<f64>()? as usize,
+                    None => 3,
+                };
+                Some(ShadingMethod::Toon(bands))
+            }
             "default" => None,
             other => {
                 println!("{other} shading has not been implemented yet. Using defaults");
@@ -788,13 +1533,161 @@ impl Frame {
         Ok(())
     }
 
+    /// Either `outline off`, or a line color followed by optional width, depth threshold and
+    /// crease angle.
+    pub fn set_outline<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let first = args.next().unwrap();
+        if first.as_rule() == Rule::OUTLINE_OFF {
+            self.image.set_outline(None);
+            return Ok(());
+        }
+        let mut settings = OutlineSettings {
+            color: Color::new(
+                first.as_str().parse::<u8>()?,
+                MDLParser::next_u8(args)?,
+                MDLParser::next_u8(args)?,
+            )
+            .into(),
+            ..Default::default()
+        };
+        if let Some(width) = args.next() {
+            settings.width = width.as_str().parse::<f64>()?;
+        }
+        if let Some(depth_threshold) = args.next() {
+            settings.depth_threshold = depth_threshold.as_str().parse::<f64>()?;
+        }
+        if let Some(crease_angle) = args.next() {
+            settings.crease_angle = crease_angle.as_str().parse::<f64>()?;
+        }
+        self.image.set_outline(Some(settings));
+        Ok(())
+    }
+
+    pub fn set_tone_map<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let operator = match MDLParser::next(args) {
+            "clamp" => ToneMapOperator::Clamp,
+            "reinhard" => ToneMapOperator::Reinhard,
+            "aces" => ToneMapOperator::Aces,
+            _ => panic!("Unimplemented tone mapping operator"),
+        };
+        let exposure = match args.next() {
+            Some(exposure) => exposure.as_str().parse::<f64>()?,
+            None => 0.0,
+        };
+        self.image
+            .set_tone_mapper(ToneMapper::new(operator, exposure));
+        Ok(())
+    }
+
+    pub fn set_samples<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let pattern = match MDLParser::next(args) {
+            "ordered" => SamplePattern::Ordered,
+            "rotated" => SamplePattern::RotatedGrid,
+            "jitter" => SamplePattern::Jittered,
+            "poisson" => SamplePattern::Poisson,
+            _ => panic!("Unimplemented sample pattern"),
+        };
+        let seed = match args.next() {
+            Some(seed) => seed.as_str().parse::<f64>()? as u64,
+            None => 0,
+        };
+        self.image.get_sampler().set_pattern(pattern, seed);
+        Ok(())
+    }
+
+    pub fn set_filter<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let filter = match MDLParser::next(args) {
+            "box" => FilterKind::Box,
+            "tent" => FilterKind::Tent,
+            "gaussian" => FilterKind::Gaussian,
+            "mitchell" => FilterKind::MitchellNetravali,
+            _ => panic!("Unimplemented reconstruction filter"),
+        };
+        let radius = match args.next() {
+            Some(radius) => Some(radius.as_str().parse::<f64>()?),
+            None => None,
+        };
+        self.image.get_sampler().set_filter(filter, radius);
+        Ok(())
+    }
+
+    pub fn set_fog<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mode = match MDLParser::next(args) {
+            "linear" => FogMode::Linear,
+            "exp" => FogMode::Exponential,
+            "exp2" => FogMode::ExponentialSquared,
+            _ => panic!("Unimplemented fog mode"),
+        };
+        let color = Color::new(
+            MDLParser::next_u8(args)?,
+            MDLParser::next_u8(args)?,
+            MDLParser::next_u8(args)?,
+        );
+        let (start, end) = (MDLParser::next_f64(args)?, MDLParser::next_f64(args)?);
+        let mut knob_mul = 1.0;
+        if let Some(knob_map_r) = &self.knob_map {
+            if let Some(knob) = args.next() {
+                knob_mul = knob_map_r[knob.as_str()];
+            }
+        }
+        self.image.set_fog(Some(Fog::new(
+            mode,
+            color,
+            start * knob_mul,
+            end * knob_mul,
+        )));
+        Ok(())
+    }
+
+    pub fn set_background<'i>(
+        &mut self,
+        args: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let args: Vec<&str> = args.map(|arg| arg.as_str()).collect();
+        let background = match args.as_slice() {
+            [fit, filename] => {
+                let fit = match *fit {
+                    "stretch" => BackdropFit::Stretch,
+                    "tile" => BackdropFit::Tile,
+                    _ => panic!("Unimplemented backdrop fit"),
+                };
+                Background::load(filename, fit, &self.backdrops)?
+            }
+            [r, g, b] => Background::Solid(Color::new(r.parse()?, g.parse()?, b.parse()?).into()),
+            [r0, g0, b0, r1, g1, b1] => Background::gradient(
+                Color::new(r0.parse()?, g0.parse()?, b0.parse()?),
+                Color::new(r1.parse()?, g1.parse()?, b1.parse()?),
+            ),
+            _ => panic!("background takes a color, two colors, or a fit and a filename"),
+        };
+        self.image.set_background(background);
+        Ok(())
+    }
+
     pub fn save<'i>(
         &mut self,
         args: &mut impl Iterator<Item = Pair<'i, Rule>>,
     ) -> Result<(), Box<dyn Error>> {
         let filename = MDLParser::next(args);
+        self.flush_scene()?;
         if filename.contains('.') {
             self.image
+                .downsample()
                 .save_name(filename)
                 .unwrap_or_else(|_| panic!("Could not save {}", filename));
         } else {
@@ -822,6 +1715,54 @@ impl Default for Frame {
             constants: HashMap::new(),
             knob_map: Some(HashMap::new()),
             shading_method: None,
+            tolerance: DEFAULT_TOLERANCE,
+            shadows: None,
+            scene: Vec::new(),
+            booleans: Vec::new(),
+            subdivision: None,
+            backdrops: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MDLParser, OutputType};
+
+    /// The center pixel of a single-image program, as resolved for output.
+    fn center(program: &str) -> crate::color::HdrColor {
+        let mut p: MDLParser = Default::default();
+        p.parse_str(program).expect("Program parse failed");
+        match p.frames.as_ref().unwrap() {
+            OutputType::Image(frame) => frame.image.downsample()[250][250],
+            OutputType::Animation(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn clear_keeps_the_background() {
+        let backdrop = center("background 255 0 0");
+        assert_ne!(backdrop, Default::default());
+        assert_eq!(
+            center("background 255 0 0\nbox 0 500 0 500 500 500\nclear"),
+            backdrop
+        );
+    }
+
+    #[test]
+    fn bad_curves_are_errors() {
+        let points = "0 0 0 10 10 0 20 0 0 30 10 0";
+        for path in [
+            format!("bspline 0 {}", points),
+            format!("bspline 1.5 {}", points),
+            format!("bspline 2 {} knots 0 1 2", points),
+            format!("bspline 1 {} knots 0 1 2 1 2 3", points),
+            "catmullrom 0 0 0 10 10 0 20".to_string(),
+            "polyline 0 0 10 10 20".to_string(),
+        ] {
+            let mut p: MDLParser = Default::default();
+            let program = format!("lathe 0 {}", path);
+            assert!(p.parse_str(&program).is_err(), "{}", program);
         }
     }
 }
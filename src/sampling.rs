@@ -0,0 +1,271 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::parser;
+
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum SamplePattern {
+    /// One sample in the center of each cell of the supersampling grid.
+    #[default]
+    Ordered,
+    /// A skewed lattice where no two samples share a row or column, which breaks up
+    /// staircasing on near-horizontal and near-vertical edges.
+    RotatedGrid,
+    /// One uniformly random sample per grid cell, reseeded for every pixel.
+    Jittered,
+    /// A blue-noise pattern with a minimum spacing between samples, tiled across pixels.
+    Poisson,
+}
+
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum FilterKind {
+    #[default]
+    Box,
+    Tent,
+    Gaussian,
+    MitchellNetravali,
+}
+
+impl FilterKind {
+    /// Support of the filter in output pixels when no radius is given.
+    pub fn default_radius(&self) -> f64 {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::MitchellNetravali => 2.0,
+        }
+    }
+
+    fn evaluate(&self, x: f64, radius: f64) -> f64 {
+        let x = x.abs();
+        if x > radius {
+            return 0.0;
+        }
+        match self {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => 1.0 - x / radius,
+            FilterKind::Gaussian => {
+                const ALPHA: f64 = 2.0;
+                (-ALPHA * x * x).exp() - (-ALPHA * radius * radius).exp()
+            }
+            FilterKind::MitchellNetravali => {
+                const B: f64 = 1.0 / 3.0;
+                const C: f64 = 1.0 / 3.0;
+                let x = 2.0 * x / radius;
+                if x < 1.0 {
+                    ((12.0 - 9.0 * B - 6.0 * C) * x * x * x
+                        + (-18.0 + 12.0 * B + 6.0 * C) * x * x
+                        + (6.0 - 2.0 * B))
+                        / 6.0
+                } else {
+                    ((-B - 6.0 * C) * x * x * x
+                        + (6.0 * B + 30.0 * C) * x * x
+                        + (-12.0 * B - 48.0 * C) * x
+                        + (8.0 * B + 24.0 * C))
+                        / 6.0
+                }
+            }
+        }
+    }
+}
+
+/// Decides where each supersample is taken inside its pixel, and how samples are
+/// weighted when the supersampled image is resolved back down.
+///
+/// Every pixel owns a `SAMPLE_SCALE` by `SAMPLE_SCALE` block of slots in the supersampled
+/// buffers. A slot keeps its place in the buffers, but the point the rasterizer tests for
+/// it is moved around according to the pattern.
+#[derive(Clone, Debug)]
+pub struct Sampler {
+    pattern: SamplePattern,
+    seed: u64,
+    offsets: Vec<(f64, f64)>,
+    filter: FilterKind,
+    radius: f64,
+}
+
+impl Sampler {
+    pub fn new(pattern: SamplePattern, seed: u64) -> Self {
+        let mut sampler = Self {
+            pattern,
+            seed,
+            offsets: Vec::new(),
+            filter: Default::default(),
+            radius: FilterKind::default().default_radius(),
+        };
+        sampler.offsets = sampler.generate_offsets();
+        sampler
+    }
+
+    pub fn set_pattern(&mut self, pattern: SamplePattern, seed: u64) {
+        self.pattern = pattern;
+        self.seed = seed;
+        self.offsets = self.generate_offsets();
+    }
+
+    pub fn set_filter(&mut self, filter: FilterKind, radius: Option<f64>) {
+        self.filter = filter;
+        self.radius = radius.unwrap_or_else(|| filter.default_radius());
+    }
+
+    pub fn get_radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn generate_offsets(&self) -> Vec<(f64, f64)> {
+        let n = parser::SAMPLE_SCALE as usize;
+        match self.pattern {
+            SamplePattern::Ordered | SamplePattern::Jittered => (0..n * n)
+                .map(|k| ((k % n) as f64 + 0.5, (k / n) as f64 + 0.5))
+                .collect(),
+            SamplePattern::RotatedGrid => (0..n * n)
+                .map(|k| {
+                    let (i, j) = (k % n, k / n);
+                    (
+                        i as f64 + (j as f64 + 0.5) / n as f64,
+                        j as f64 + ((n - 1 - i) as f64 + 0.5) / n as f64,
+                    )
+                })
+                .collect(),
+            SamplePattern::Poisson => poisson_disk(n * n, n as f64, self.seed),
+        }
+    }
+
+    /// Where the sample for slot `(x, y)` of the supersampled buffers lies, in the same
+    /// coordinates the rasterizer works in.
+    pub fn position(&self, x: usize, y: usize) -> (f64, f64) {
+        let n = parser::SAMPLE_SCALE as usize;
+        let (block_x, block_y) = ((x - x % n) as f64, (y - y % n) as f64);
+        let (cell_x, cell_y) = (x % n, y % n);
+        match self.pattern {
+            SamplePattern::Jittered => (
+                block_x + cell_x as f64 + hash_unit(self.seed, x, y, 0),
+                block_y + cell_y as f64 + hash_unit(self.seed, x, y, 1),
+            ),
+            _ => {
+                let (ox, oy) = self.offsets[cell_y * n + cell_x];
+                (block_x + ox, block_y + oy)
+            }
+        }
+    }
+
+    /// How many slots beyond a primitive's bounds the rasterizer has to look, since a slot's
+    /// sample may have wandered out of its own cell.
+    pub fn margin(&self) -> usize {
+        match self.pattern {
+            SamplePattern::Poisson => parser::SAMPLE_SCALE as usize - 1,
+            _ => 0,
+        }
+    }
+
+    /// Reconstruction weight of a sample offset by `(dx, dy)` output pixels from a pixel center.
+    pub fn weight(&self, dx: f64, dy: f64) -> f64 {
+        self.filter.evaluate(dx, self.radius) * self.filter.evaluate(dy, self.radius)
+    }
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Sampler::new(Default::default(), 0)
+    }
+}
+
+/// A deterministic value in `[0, 1)` for a slot, so jittered renders are repeatable.
+pub(crate) fn hash_unit(seed: u64, x: usize, y: usize, salt: u64) -> f64 {
+    let mut h = seed
+        ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ salt.wrapping_mul(0x1656_67B1_9E37_79F9);
+    // splitmix64 finalizer
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^= h >> 31;
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Dart throwing on a torus of side `size`, relaxing the spacing whenever it gets stuck.
+fn poisson_disk(count: usize, size: f64, seed: u64) -> Vec<(f64, f64)> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut min_dist = 0.8 * size / (count as f64).sqrt();
+    let mut points: Vec<(f64, f64)> = Vec::with_capacity(count);
+    let mut misses = 0;
+    while points.len() < count {
+        let candidate = (rng.gen::<f64>() * size, rng.gen::<f64>() * size);
+        let fits = points.iter().all(|p| {
+            let dx = (p.0 - candidate.0).abs();
+            let dy = (p.1 - candidate.1).abs();
+            let (dx, dy) = (dx.min(size - dx), dy.min(size - dy));
+            dx * dx + dy * dy >= min_dist * min_dist
+        });
+        if fits {
+            points.push(candidate);
+            misses = 0;
+        } else {
+            misses += 1;
+            if misses > 1000 {
+                min_dist *= 0.95;
+                misses = 0;
+            }
+        }
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FilterKind, SamplePattern, Sampler};
+    use crate::parser;
+
+    #[test]
+    fn samples_stay_in_their_pixel() {
+        let n = parser::SAMPLE_SCALE as usize;
+        for pattern in [
+            SamplePattern::Ordered,
+            SamplePattern::RotatedGrid,
+            SamplePattern::Jittered,
+            SamplePattern::Poisson,
+        ] {
+            let sampler = Sampler::new(pattern, 7);
+            for y in 0..3 * n {
+                for x in 0..3 * n {
+                    let (sx, sy) = sampler.position(x, y);
+                    assert_eq!(sx as usize / n, x / n, "{:?}", pattern);
+                    assert_eq!(sy as usize / n, y / n, "{:?}", pattern);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rotated_grid_has_no_shared_columns() {
+        let n = parser::SAMPLE_SCALE as usize;
+        let sampler = Sampler::new(SamplePattern::RotatedGrid, 0);
+        let mut xs: Vec<f64> = (0..n * n)
+            .map(|k| sampler.position(k % n, k / n).0)
+            .collect();
+        xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert!(xs.windows(2).all(|w| w[1] - w[0] > 1e-9));
+    }
+
+    #[test]
+    fn jitter_is_repeatable() {
+        let a = Sampler::new(SamplePattern::Jittered, 42);
+        let b = Sampler::new(SamplePattern::Jittered, 42);
+        assert_eq!(a.position(13, 27), b.position(13, 27));
+    }
+
+    #[test]
+    fn filters_peak_at_center() {
+        for kind in [
+            FilterKind::Box,
+            FilterKind::Tent,
+            FilterKind::Gaussian,
+            FilterKind::MitchellNetravali,
+        ] {
+            let mut sampler: Sampler = Default::default();
+            sampler.set_filter(kind, None);
+            assert!(sampler.weight(0.0, 0.0) >= sampler.weight(0.4, 0.0));
+            assert_eq!(sampler.weight(sampler.get_radius() + 0.01, 0.0), 0.0);
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+
+use crate::{
+    clip::ClipVertex,
+    image::rasterize_depth,
+    lighter::LightSource,
+    matrix::{Dynamic2D, Mesh, ParallelGrid},
+    Vector3D,
+};
+
+/// Widest spotlight cone a single shadow map will try to cover, in degrees.
+const MAX_SPOT_ANGLE: f64 = 80.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowSettings {
+    /// How far, in scene units, a surface may sit behind the nearest occluder and still count
+    /// as lit. Grows on surfaces that face away from the light, where acne is worst.
+    pub bias: f64,
+    /// Half-width of the percentage-closer filtering kernel, in shadow map texels.
+    pub pcf_radius: usize,
+    /// Side length of each depth map in texels.
+    pub resolution: usize,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            bias: 2.0,
+            pcf_radius: 1,
+            resolution: 2048,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Projection {
+    /// Parallel rays travelling against `l`; depth grows towards the light.
+    Orthographic {
+        u: Vector3D,
+        v: Vector3D,
+        l: Vector3D,
+        origin: (f64, f64),
+        scale: f64,
+    },
+    /// A pinhole at `eye` looking along `forward`; depth is stored as the reciprocal distance.
+    Perspective {
+        eye: Vector3D,
+        u: Vector3D,
+        v: Vector3D,
+        forward: Vector3D,
+        focal: f64,
+    },
+}
+
+impl Projection {
+    fn project(&self, p: &Vector3D, size: f64) -> (f64, f64, f64, f64) {
+        match self {
+            Projection::Orthographic {
+                u,
+                v,
+                l,
+                origin,
+                scale,
+            } => (
+                (p.dot(u) - origin.0) * scale,
+                (p.dot(v) - origin.1) * scale,
+                p.dot(l),
+                1.0,
+            ),
+            Projection::Perspective {
+                eye,
+                u,
+                v,
+                forward,
+                focal,
+            } => {
+                let d = *p - *eye;
+                let depth = d.dot(forward);
+                (
+                    focal * d.dot(u) + size / 2.0 * depth,
+                    focal * d.dot(v) + size / 2.0 * depth,
+                    1.0,
+                    depth,
+                )
+            }
+        }
+    }
+
+    /// Distance from the light of whatever left `depth` in the map.
+    fn distance(&self, depth: f64) -> f64 {
+        match self {
+            Projection::Orthographic { .. } => -depth,
+            Projection::Perspective { .. } => 1.0 / depth,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct ShadowFace {
+    projection: Projection,
+    depth: Dynamic2D<f64>,
+}
+
+impl ShadowFace {
+    fn new(projection: Projection, resolution: usize) -> Self {
+        Self {
+            projection,
+            depth: Dynamic2D::fill(f64::NEG_INFINITY, resolution, resolution),
+        }
+    }
+
+    fn render(&mut self, scene: &[Mesh]) {
+        let size = self.depth.get_width() as f64;
+        for mesh in scene {
+            for (corners, normal) in mesh.triangles() {
+                let corners = corners.map(|corner| {
+                    let (x, y, z, w) = corner.position;
+                    let world = Vector3D::new(x / w, y / w, z / w);
+                    ClipVertex::new(self.projection.project(&world, size), normal)
+                });
+                rasterize_depth(&mut self.depth, corners);
+            }
+        }
+    }
+}
+
+/// Depth as seen from one light, covering everything drawn in the frame. Point lights need a
+/// face for each axis direction; other lights get by with one.
+#[derive(Clone, Debug)]
+pub struct ShadowMap {
+    faces: Vec<ShadowFace>,
+    settings: ShadowSettings,
+}
+
+impl ShadowMap {
+    pub fn build(light: &LightSource, scene: &[Mesh], settings: ShadowSettings) -> Self {
+        let resolution = settings.resolution;
+        let half = resolution as f64 / 2.0;
+        let projections = match light {
+            LightSource::Directional { direction, .. } => {
+                let l = direction.normalize();
+                let (u, v) = basis(&l);
+                let (mut min, mut max) = (
+                    (f64::INFINITY, f64::INFINITY),
+                    (f64::NEG_INFINITY, f64::NEG_INFINITY),
+                );
+                for p in scene.iter().flat_map(Mesh::points) {
+                    let (pu, pv) = (p.dot(&u), p.dot(&v));
+                    min = (min.0.min(pu), min.1.min(pv));
+                    max = (max.0.max(pu), max.1.max(pv));
+                }
+                let extent = (max.0 - min.0).max(max.1 - min.1).max(f64::EPSILON) * 1.02;
+                vec![Projection::Orthographic {
+                    u,
+                    v,
+                    l,
+                    origin: (min.0 - extent * 0.01, min.1 - extent * 0.01),
+                    scale: resolution as f64 / extent,
+                }]
+            }
+            LightSource::Point { position, .. } => [
+                Vector3D::new(1.0, 0.0, 0.0),
+                Vector3D::new(-1.0, 0.0, 0.0),
+                Vector3D::new(0.0, 1.0, 0.0),
+                Vector3D::new(0.0, -1.0, 0.0),
+                Vector3D::new(0.0, 0.0, 1.0),
+                Vector3D::new(0.0, 0.0, -1.0),
+            ]
+            .into_iter()
+            .map(|forward| {
+                let (u, v) = basis(&forward);
+                Projection::Perspective {
+                    eye: *position,
+                    u,
+                    v,
+                    forward,
+                    focal: half,
+                }
+            })
+            .collect(),
+            LightSource::Spot {
+                position,
+                direction,
+                cos_outer,
+                ..
+            } => {
+                let angle = cos_outer.acos().min(MAX_SPOT_ANGLE.to_radians());
+                let (u, v) = basis(direction);
+                vec![Projection::Perspective {
+                    eye: *position,
+                    u,
+                    v,
+                    forward: *direction,
+                    focal: half / angle.tan(),
+                }]
+            }
+        };
+
+        let mut faces: Vec<ShadowFace> = projections
+            .into_iter()
+            .map(|projection| ShadowFace::new(projection, resolution))
+            .collect();
+        faces.par_iter_mut().for_each(|face| face.render(scene));
+        Self { faces, settings }
+    }
+
+    /// Fraction of the PCF kernel around `position` that the light reaches, from 0 to 1.
+    /// `to_light` is the unit vector towards the light, used to scale the bias with slope.
+    pub fn visibility(&self, position: &Vector3D, normal: &Vector3D, to_light: &Vector3D) -> f64 {
+        let face = self
+            .faces
+            .iter()
+            .max_by(|a, b| {
+                facing(&a.projection, position).total_cmp(&facing(&b.projection, position))
+            })
+            .expect("Shadow map has no faces");
+
+        let size = face.depth.get_width();
+        let (x, y, _z, w) = face.projection.project(position, size as f64);
+        if w <= 0.0 {
+            return 1.0;
+        }
+        let distance = match face.projection {
+            Projection::Orthographic { l, .. } => -position.dot(&l),
+            Projection::Perspective { .. } => w,
+        };
+
+        let cos = normal.normalize().dot(to_light).abs().max(0.1);
+        let slope = (1.0 - cos * cos).sqrt() / cos;
+        let bias = self.settings.bias * (1.0 + slope);
+
+        let radius = self.settings.pcf_radius as isize;
+        let (cx, cy) = ((x / w).floor() as isize, (y / w).floor() as isize);
+        let mut lit = 0;
+        for ty in cy - radius..=cy + radius {
+            for tx in cx - radius..=cx + radius {
+                if tx < 0 || ty < 0 || tx >= size as isize || ty >= size as isize {
+                    lit += 1;
+                    continue;
+                }
+                let stored = face.depth[ty as usize][tx as usize];
+                if stored == f64::NEG_INFINITY
+                    || distance <= face.projection.distance(stored) + bias
+                {
+                    lit += 1;
+                }
+            }
+        }
+        lit as f64 / ((2 * radius + 1) * (2 * radius + 1)) as f64
+    }
+}
+
+/// How squarely a face looks at `position`; picks the cube face for point lights.
+fn facing(projection: &Projection, position: &Vector3D) -> f64 {
+    match projection {
+        Projection::Orthographic { .. } => 0.0,
+        Projection::Perspective { eye, forward, .. } => (*position - *eye).normalize().dot(forward),
+    }
+}
+
+/// Two unit vectors perpendicular to `forward` and to each other.
+fn basis(forward: &Vector3D) -> (Vector3D, Vector3D) {
+    let forward = forward.normalize();
+    let up = if forward.y.abs() < 0.9 {
+        Vector3D::new(0.0, 1.0, 0.0)
+    } else {
+        Vector3D::new(1.0, 0.0, 0.0)
+    };
+    let u = up.cross(&forward).normalize();
+    (u, forward.cross(&u))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ShadowMap, ShadowSettings};
+    use crate::{color::color_constants, lighter::LightSource, matrix::Mesh, Vector3D};
+
+    /// A small square hovering over a large floor, both facing +z.
+    fn scene() -> Vec<Mesh> {
+        let mut p: Mesh = Default::default();
+        p.add_triangle((0.0, 0.0, 0.0), (500.0, 0.0, 0.0), (500.0, 500.0, 0.0));
+        p.add_triangle((0.0, 0.0, 0.0), (500.0, 500.0, 0.0), (0.0, 500.0, 0.0));
+        p.add_triangle(
+            (200.0, 200.0, 100.0),
+            (300.0, 200.0, 100.0),
+            (300.0, 300.0, 100.0),
+        );
+        p.add_triangle(
+            (200.0, 200.0, 100.0),
+            (300.0, 300.0, 100.0),
+            (200.0, 300.0, 100.0),
+        );
+        vec![p]
+    }
+
+    fn assert_shadowed(light: LightSource, to_light: Vector3D) {
+        let settings = ShadowSettings {
+            resolution: 256,
+            ..Default::default()
+        };
+        let map = ShadowMap::build(&light, &scene(), settings);
+        let up = Vector3D::new(0.0, 0.0, 1.0);
+        assert_eq!(
+            map.visibility(&Vector3D::new(250.0, 250.0, 0.0), &up, &to_light),
+            0.0
+        );
+        assert_eq!(
+            map.visibility(&Vector3D::new(50.0, 50.0, 0.0), &up, &to_light),
+            1.0
+        );
+        assert_eq!(
+            map.visibility(&Vector3D::new(250.0, 250.0, 100.0), &up, &to_light),
+            1.0
+        );
+    }
+
+    #[test]
+    fn directional_light_casts_shadow() {
+        let direction = Vector3D::new(0.0, 0.0, 1.0);
+        assert_shadowed(
+            LightSource::Directional {
+                direction,
+                color: color_constants::WHITE,
+            },
+            direction,
+        );
+    }
+
+    #[test]
+    fn point_light_casts_shadow() {
+        assert_shadowed(
+            LightSource::point(
+                Vector3D::new(250.0, 250.0, 400.0),
+                color_constants::WHITE,
+                LightSource::NO_ATTENUATION,
+            ),
+            Vector3D::new(0.0, 0.0, 1.0),
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use std::f64::consts::TAU;
+
+use super::{
+    shape::{nearest_hit, quadratic_roots, turn},
+    Aabb, Ray, Shape, Tessellation,
+};
+use crate::{matrix::Mesh, Vector3D};
+
+/// An upright cone with its base on the circle around `center` and its tip `height` above.
+#[derive(Clone, Copy, Debug)]
+pub struct Cone {
+    radius: f64,
+    height: f64,
+    center: (f64, f64, f64),
+}
+
+impl Cone {
+    pub fn new(radius: f64, height: f64, center: (f64, f64, f64)) -> Self {
+        Self {
+            radius,
+            height,
+            center,
+        }
+    }
+
+    pub fn add_to_matrix(&self, p: &mut Mesh, steps: usize) {
+        let steps = steps.max(3);
+        let base = Vector3D::from_point(self.center);
+        let apex = base + Vector3D::new(0.0, self.height, 0.0);
+        let down = Vector3D::new(0.0, -1.0, 0.0);
+        // Leaning back by the slope of the side
+        let side_normal = |angle: f64| {
+            Vector3D::new(angle.cos(), 0.0, angle.sin()).scale(self.height)
+                + Vector3D::new(0.0, self.radius, 0.0)
+        };
+        let rim = |s: usize| {
+            let angle = TAU * s as f64 / steps as f64;
+            let point = base + Vector3D::new(angle.cos(), 0.0, angle.sin()).scale(self.radius);
+            ((point.x, point.y, point.z), angle)
+        };
+
+        let side: Vec<usize> = (0..steps)
+            .map(|s| {
+                let (point, angle) = rim(s);
+                p.add_vertex(point, side_normal(angle))
+            })
+            .collect();
+        let bottom: Vec<usize> = (0..steps).map(|s| p.add_vertex(rim(s).0, down)).collect();
+        let bottom_center = p.add_vertex(self.center, down);
+
+        (0..steps).for_each(|s| {
+            let next = (s + 1) % steps;
+            // The tip gets a vertex per face, since its normal depends on the way you come
+            let middle = TAU * (s as f64 + 0.5) / steps as f64;
+            let tip = p.add_vertex((apex.x, apex.y, apex.z), side_normal(middle));
+            p.add_face(side[s], tip, side[next]);
+            p.add_face(bottom_center, bottom[s], bottom[next]);
+        });
+    }
+}
+
+impl Shape for Cone {
+    fn tessellate(&self, p: &mut Mesh, tessellation: &Tessellation) {
+        self.add_to_matrix(p, tessellation.circle_segments(self.radius));
+    }
+
+    fn bounds(&self) -> Aabb {
+        let (x, y, z) = self.center;
+        Aabb::new(
+            Vector3D::new(x - self.radius, y, z - self.radius),
+            Vector3D::new(x + self.radius, y + self.height, z + self.radius),
+        )
+    }
+
+    fn hit_distance(&self, ray: &Ray) -> Option<f64> {
+        // Measured from the tip, where the radius squared grows by `k` per unit drop squared
+        let tip = Vector3D::from_point(self.center) + Vector3D::new(0.0, self.height, 0.0);
+        let (o, d) = (ray.origin - tip, ray.direction);
+        let k = (self.radius / self.height).powi(2);
+        let side = quadratic_roots(
+            d.x * d.x + d.z * d.z - k * d.y * d.y,
+            2.0 * (o.x * d.x + o.z * d.z - k * o.y * d.y),
+            o.x * o.x + o.z * o.z - k * o.y * o.y,
+        )
+        .map(|(t0, t1)| [t0, t1])
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|t| (-self.height..=0.0).contains(&(o.y + d.y * t)));
+
+        let base = (d.y != 0.0)
+            .then(|| (-self.height - o.y) / d.y)
+            .filter(|t| {
+                let (x, z) = (o.x + d.x * t, o.z + d.z * t);
+                x * x + z * z <= self.radius * self.radius
+            });
+        nearest_hit(side.chain(base))
+    }
+
+    fn normal_at(&self, point: Vector3D) -> Option<Vector3D> {
+        let local = point - Vector3D::from_point(self.center);
+        let across = local.x.hypot(local.z);
+        let side_radius = self.radius * (1.0 - local.y / self.height);
+        if local.y.abs() < (across - side_radius).abs() {
+            return Some(Vector3D::new(0.0, -1.0, 0.0));
+        }
+        if across == 0.0 {
+            return Some(Vector3D::new(0.0, 1.0, 0.0));
+        }
+        let outward = Vector3D::new(local.x / across, 0.0, local.z / across);
+        Some((outward.scale(self.height) + Vector3D::new(0.0, self.radius, 0.0)).normalize())
+    }
+
+    /// The side wraps once around, base to tip. The base is mapped flat across its diameter.
+    fn uv_at(&self, point: Vector3D) -> Option<(f64, f64)> {
+        let local = point - Vector3D::from_point(self.center);
+        Some(if self.normal_at(point)?.y == -1.0 {
+            (
+                (local.x / self.radius + 1.0) / 2.0,
+                (local.z / self.radius + 1.0) / 2.0,
+            )
+        } else {
+            (
+                turn(local.x, local.z),
+                (local.y / self.height).clamp(0.0, 1.0),
+            )
+        })
+    }
+}
@@ -1,4 +1,5 @@
-use crate::matrix::PolygonMatrix;
+use super::{shape::nearest_hit, Aabb, Ray, Shape, Tessellation};
+use crate::{matrix::Mesh, Vector3D};
 
 #[derive(Clone, Copy, Debug)]
 pub struct Cube {
@@ -22,7 +23,7 @@ impl Cube {
         }
     }
 
-    pub fn add_to_matrix(&self, p: &mut PolygonMatrix) {
+    pub fn add_to_matrix(&self, p: &mut Mesh) {
         let ltf = (self.left, self.top, self.front);
         let lbf = (self.left, self.top - self.height, self.front);
         let lbb = (self.left, self.top - self.height, self.front - self.depth);
@@ -38,27 +39,79 @@ impl Cube {
         let rtb = (self.left + self.width, self.top, self.front - self.depth);
 
         // Left face
-        p.add_triangle(ltf, ltb, lbb);
-        p.add_triangle(ltf, lbb, lbf);
+        p.add_facet(ltf, ltb, lbb);
+        p.add_facet(ltf, lbb, lbf);
 
         // Front face
-        p.add_triangle(rtf, ltf, lbf);
-        p.add_triangle(rtf, lbf, rbf);
+        p.add_facet(rtf, ltf, lbf);
+        p.add_facet(rtf, lbf, rbf);
 
         // Right face
-        p.add_triangle(rtb, rtf, rbf);
-        p.add_triangle(rtb, rbf, rbb);
+        p.add_facet(rtb, rtf, rbf);
+        p.add_facet(rtb, rbf, rbb);
 
         // Back face
-        p.add_triangle(ltb, rtb, rbb);
-        p.add_triangle(ltb, rbb, lbb);
+        p.add_facet(ltb, rtb, rbb);
+        p.add_facet(ltb, rbb, lbb);
 
         // Top face
-        p.add_triangle(rtb, ltb, ltf);
-        p.add_triangle(rtb, ltf, rtf);
+        p.add_facet(rtb, ltb, ltf);
+        p.add_facet(rtb, ltf, rtf);
 
         // Bottom face
-        p.add_triangle(rbf, lbf, lbb);
-        p.add_triangle(rbf, lbb, rbb);
+        p.add_facet(rbf, lbf, lbb);
+        p.add_facet(rbf, lbb, rbb);
+    }
+}
+
+impl Shape for Cube {
+    fn tessellate(&self, p: &mut Mesh, _tessellation: &Tessellation) {
+        self.add_to_matrix(p);
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::new(
+            Vector3D::new(self.left, self.top - self.height, self.front - self.depth),
+            Vector3D::new(self.left + self.width, self.top, self.front),
+        )
+    }
+
+    fn hit_distance(&self, ray: &Ray) -> Option<f64> {
+        let (enter, exit) = self.bounds().ray_span(ray)?;
+        nearest_hit([enter, exit])
+    }
+
+    fn normal_at(&self, point: Vector3D) -> Option<Vector3D> {
+        let Aabb { min, max } = self.bounds();
+        // Whichever face the point is closest to
+        [
+            (point.x - min.x, Vector3D::new(-1.0, 0.0, 0.0)),
+            (max.x - point.x, Vector3D::new(1.0, 0.0, 0.0)),
+            (point.y - min.y, Vector3D::new(0.0, -1.0, 0.0)),
+            (max.y - point.y, Vector3D::new(0.0, 1.0, 0.0)),
+            (point.z - min.z, Vector3D::new(0.0, 0.0, -1.0)),
+            (max.z - point.z, Vector3D::new(0.0, 0.0, 1.0)),
+        ]
+        .into_iter()
+        .min_by(|(a, _), (b, _)| a.abs().total_cmp(&b.abs()))
+        .map(|(_, normal)| normal)
+    }
+
+    /// Each face is mapped whole, along the two axes it spans.
+    fn uv_at(&self, point: Vector3D) -> Option<(f64, f64)> {
+        let Aabb { min, .. } = self.bounds();
+        let (u, v, w) = (
+            (point.x - min.x) / self.width,
+            (point.y - min.y) / self.height,
+            (point.z - min.z) / self.depth,
+        );
+        let normal = self.normal_at(point)?;
+        Some(if normal.x != 0.0 {
+            (w, v)
+        } else if normal.y != 0.0 {
+            (u, w)
+        } else {
+            (u, v)
+        })
     }
 }
@@ -0,0 +1,150 @@
+use std::f64::consts::TAU;
+
+use super::{
+    shape::{nearest_hit, quadratic_roots, turn},
+    Aabb, Ray, Shape, Tessellation,
+};
+use crate::{matrix::Mesh, Vector3D};
+
+/// An upright cylinder standing on the circle around `center`.
+#[derive(Clone, Copy, Debug)]
+pub struct Cylinder {
+    radius: f64,
+    height: f64,
+    center: (f64, f64, f64),
+    capped: bool,
+}
+
+impl Cylinder {
+    pub fn new(radius: f64, height: f64, center: (f64, f64, f64), capped: bool) -> Self {
+        Self {
+            radius,
+            height,
+            center,
+            capped,
+        }
+    }
+
+    /// Direction from the axis to each of `steps` points around it.
+    fn spokes(steps: usize) -> Vec<Vector3D> {
+        (0..steps)
+            .map(|s| {
+                let angle = TAU * s as f64 / steps as f64;
+                Vector3D::new(angle.cos(), 0.0, angle.sin())
+            })
+            .collect()
+    }
+
+    pub fn add_to_matrix(&self, p: &mut Mesh, steps: usize) {
+        let steps = steps.max(3);
+        let spokes = Self::spokes(steps);
+        let base = Vector3D::from_point(self.center);
+        let up = Vector3D::new(0.0, self.height, 0.0);
+        let mut ring = |height: Vector3D, normal: Option<Vector3D>| -> Vec<usize> {
+            spokes
+                .iter()
+                .map(|&spoke| {
+                    let point = base + height + spoke.scale(self.radius);
+                    p.add_vertex((point.x, point.y, point.z), normal.unwrap_or(spoke))
+                })
+                .collect()
+        };
+        let zero = Vector3D::new(0.0, 0.0, 0.0);
+        let (bottom, top) = (ring(zero, None), ring(up, None));
+        let caps = self.capped.then(|| {
+            (
+                ring(zero, Some(Vector3D::new(0.0, -1.0, 0.0))),
+                ring(up, Some(Vector3D::new(0.0, 1.0, 0.0))),
+            )
+        });
+
+        (0..steps).for_each(|s| {
+            let next = (s + 1) % steps;
+            p.add_face(bottom[s], top[next], bottom[next]);
+            p.add_face(bottom[s], top[s], top[next]);
+        });
+        if let Some((bottom, top)) = caps {
+            let top_center = base + up;
+            let bottom_center = p.add_vertex(self.center, Vector3D::new(0.0, -1.0, 0.0));
+            let top_center = p.add_vertex(
+                (top_center.x, top_center.y, top_center.z),
+                Vector3D::new(0.0, 1.0, 0.0),
+            );
+            (0..steps).for_each(|s| {
+                let next = (s + 1) % steps;
+                p.add_face(top_center, top[next], top[s]);
+                p.add_face(bottom_center, bottom[s], bottom[next]);
+            });
+        }
+    }
+}
+
+impl Shape for Cylinder {
+    fn tessellate(&self, p: &mut Mesh, tessellation: &Tessellation) {
+        self.add_to_matrix(p, tessellation.circle_segments(self.radius));
+    }
+
+    fn bounds(&self) -> Aabb {
+        let (x, y, z) = self.center;
+        Aabb::new(
+            Vector3D::new(x - self.radius, y, z - self.radius),
+            Vector3D::new(x + self.radius, y + self.height, z + self.radius),
+        )
+    }
+
+    fn hit_distance(&self, ray: &Ray) -> Option<f64> {
+        let (o, d) = (
+            ray.origin - Vector3D::from_point(self.center),
+            ray.direction,
+        );
+        let within_height = |t: &f64| (0.0..=self.height).contains(&(o.y + d.y * *t));
+        let side = quadratic_roots(
+            d.x * d.x + d.z * d.z,
+            2.0 * (o.x * d.x + o.z * d.z),
+            o.x * o.x + o.z * o.z - self.radius * self.radius,
+        )
+        .map(|(t0, t1)| [t0, t1])
+        .unwrap_or_default()
+        .into_iter()
+        .filter(within_height);
+
+        let caps = [0.0, self.height]
+            .into_iter()
+            .filter(|_| self.capped && d.y != 0.0)
+            .map(|y| (y - o.y) / d.y)
+            .filter(|t| {
+                let (x, z) = (o.x + d.x * t, o.z + d.z * t);
+                x * x + z * z <= self.radius * self.radius
+            });
+        nearest_hit(side.chain(caps))
+    }
+
+    fn normal_at(&self, point: Vector3D) -> Option<Vector3D> {
+        let local = point - Vector3D::from_point(self.center);
+        let to_side = (local.x.hypot(local.z) - self.radius).abs();
+        Some(if self.capped && local.y.abs() < to_side {
+            Vector3D::new(0.0, -1.0, 0.0)
+        } else if self.capped && (self.height - local.y).abs() < to_side {
+            Vector3D::new(0.0, 1.0, 0.0)
+        } else {
+            Vector3D::new(local.x, 0.0, local.z).normalize()
+        })
+    }
+
+    /// The side wraps once around, bottom to top. The caps are mapped flat across their
+    /// diameter.
+    fn uv_at(&self, point: Vector3D) -> Option<(f64, f64)> {
+        let local = point - Vector3D::from_point(self.center);
+        Some(if self.normal_at(point)?.y != 0.0 {
+            (
+                (local.x / self.radius + 1.0) / 2.0,
+                (local.z / self.radius + 1.0) / 2.0,
+            )
+        } else {
+            (
+                turn(local.x, local.z),
+                (local.y / self.height).clamp(0.0, 1.0),
+            )
+        })
+    }
+}
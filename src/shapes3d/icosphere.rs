@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use super::{Aabb, Ray, Shape, Sphere, Tessellation};
+use crate::{matrix::Mesh, Vector3D};
+
+/// A sphere made by repeatedly splitting the faces of an icosahedron, which spreads its
+/// triangles far more evenly than latitude and longitude rings.
+#[derive(Clone, Copy, Debug)]
+pub struct Icosphere {
+    radius: f64,
+    center: (f64, f64, f64),
+}
+
+impl Icosphere {
+    /// Most splits an icosphere gets, which already leaves it with 327,680 faces.
+    pub const MAX_SUBDIVISIONS: usize = 7;
+
+    pub fn new(radius: f64, center: (f64, f64, f64)) -> Self {
+        Self { radius, center }
+    }
+
+    /// The sphere the icosphere approximates, which rays are traced against.
+    fn sphere(&self) -> Sphere {
+        Sphere::new(self.radius, self.center)
+    }
+
+    fn icosahedron() -> (Vec<Vector3D>, Vec<[usize; 3]>) {
+        let t = (1.0 + 5f64.sqrt()) / 2.0;
+        let vertices = [
+            (-1.0, t, 0.0),
+            (1.0, t, 0.0),
+            (-1.0, -t, 0.0),
+            (1.0, -t, 0.0),
+            (0.0, -1.0, t),
+            (0.0, 1.0, t),
+            (0.0, -1.0, -t),
+            (0.0, 1.0, -t),
+            (t, 0.0, -1.0),
+            (t, 0.0, 1.0),
+            (-t, 0.0, -1.0),
+            (-t, 0.0, 1.0),
+        ]
+        .map(|(x, y, z)| Vector3D::new(x, y, z).normalize());
+        const FACES: [[usize; 3]; 20] = [
+            [0, 11, 5],
+            [0, 5, 1],
+            [0, 1, 7],
+            [0, 7, 10],
+            [0, 10, 11],
+            [1, 5, 9],
+            [5, 11, 4],
+            [11, 10, 2],
+            [10, 7, 6],
+            [7, 1, 8],
+            [3, 9, 4],
+            [3, 4, 2],
+            [3, 2, 6],
+            [3, 6, 8],
+            [3, 8, 9],
+            [4, 9, 5],
+            [2, 4, 11],
+            [6, 2, 10],
+            [8, 6, 7],
+            [9, 8, 1],
+        ];
+        (vertices.to_vec(), FACES.to_vec())
+    }
+
+    pub fn add_to_matrix(&self, p: &mut Mesh, subdivisions: usize) {
+        let (mut directions, mut faces) = Self::icosahedron();
+        for _ in 0..subdivisions {
+            // Neighboring faces split their shared edge at the same new vertex
+            let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+            let mut midpoint = |a: usize, b: usize| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    directions.push((directions[a] + directions[b]).normalize());
+                    directions.len() - 1
+                })
+            };
+            faces = faces
+                .into_iter()
+                .flat_map(|[a, b, c]| {
+                    let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        let first = p.get_vertex_count();
+        directions.into_iter().for_each(|direction| {
+            let point = Vector3D::from_point(self.center) + direction.scale(self.radius);
+            p.add_vertex((point.x, point.y, point.z), direction);
+        });
+        faces.into_iter().for_each(|[a, b, c]| {
+            p.add_face(first + a, first + b, first + c);
+        });
+    }
+}
+
+impl Shape for Icosphere {
+    /// Splits the faces until they follow the sphere as closely as the tessellation asks,
+    /// unless it sets the number of splits itself.
+    fn tessellate(&self, p: &mut Mesh, tessellation: &Tessellation) {
+        let subdivisions = tessellation.subdivisions.unwrap_or_else(|| {
+            // Each split halves the angle an edge spans, starting from the icosahedron's
+            let edge_angle = 2f64.atan();
+            let needed = std::f64::consts::TAU / tessellation.circle_segments(self.radius) as f64;
+            (edge_angle / needed).log2().ceil().max(0.0) as usize
+        });
+        self.add_to_matrix(p, subdivisions.min(Self::MAX_SUBDIVISIONS));
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.sphere().bounds()
+    }
+
+    fn hit_distance(&self, ray: &Ray) -> Option<f64> {
+        self.sphere().hit_distance(ray)
+    }
+
+    fn normal_at(&self, point: Vector3D) -> Option<Vector3D> {
+        self.sphere().normal_at(point)
+    }
+
+    fn uv_at(&self, point: Vector3D) -> Option<(f64, f64)> {
+        self.sphere().uv_at(point)
+    }
+}
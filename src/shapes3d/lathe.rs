@@ -0,0 +1,322 @@
+use std::{f64::consts::TAU, sync::Arc};
+
+use super::{
+    shape::{nearest_hit, quadratic_roots, turn},
+    Aabb, Ray, Shape, Tessellation,
+};
+use crate::{curves::Parametric, matrix::Mesh, Vector3D};
+
+/// A profile curve in the xy plane, spun around the upright line `x = axis_x, z = 0`.
+///
+/// The profile may run in either direction and on either side of the axis. When capped, ends
+/// that stop short of the axis are closed off with flat disks; otherwise they are left open,
+/// like the mouth of a vase. Profiles whose ends meet are closed already and get no caps.
+#[derive(Clone)]
+pub struct Lathe {
+    profile: Arc<dyn Parametric + Send + Sync>,
+    axis_x: f64,
+    capped: bool,
+    /// The profile cut into straight pieces much finer than any screen needs, as
+    /// `(parameter, r, y)` with `r` measured from the axis, and run in to the axis at capped
+    /// ends. Rays are traced against these.
+    trace: Vec<(f64, f64, f64)>,
+    /// 1 when the profile, closed along the axis, runs counterclockwise, otherwise -1.
+    orientation: f64,
+}
+
+impl Lathe {
+    /// Finest the traced profile gets, as a fraction of its size.
+    const TRACE_TOLERANCE: f64 = 1e-3;
+
+    pub fn new(profile: Arc<dyn Parametric + Send + Sync>, axis_x: f64, capped: bool) -> Self {
+        let sample = |t: f64| {
+            let (x, y, _) = profile.f(t);
+            (x - axis_x, y)
+        };
+        let rough = profile.points(64);
+        let (min, max) = rough.iter().fold(
+            (
+                (f64::INFINITY, f64::INFINITY),
+                (f64::NEG_INFINITY, f64::NEG_INFINITY),
+            ),
+            |(min, max), &(x, y, _)| ((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y))),
+        );
+        let size = (max.0 - min.0).hypot(max.1 - min.1).max(f64::EPSILON);
+        let mut trace: Vec<(f64, f64, f64)> = profile
+            .flatten(size * Self::TRACE_TOLERANCE)
+            .into_iter()
+            .map(|t| {
+                let (r, y) = sample(t);
+                (t, r, y)
+            })
+            .collect();
+
+        // Shoelace area of the profile, closed by running back along the axis
+        let (first, last) = (trace[0], trace[trace.len() - 1]);
+        let closed = trace
+            .iter()
+            .map(|&(_, r, y)| (r, y))
+            .chain([(0.0, last.2), (0.0, first.2), (first.1, first.2)])
+            .collect::<Vec<_>>();
+        let area: f64 = closed
+            .windows(2)
+            .map(|w| w[0].0 * w[1].1 - w[1].0 * w[0].1)
+            .sum();
+
+        let capped = capped && (first.1 - last.1).hypot(first.2 - last.2) > 1e-9 * size.max(1.0);
+        if capped {
+            if first.1 != 0.0 {
+                trace.insert(0, (first.0, 0.0, first.2));
+            }
+            if last.1 != 0.0 {
+                trace.push((last.0, 0.0, last.2));
+            }
+        }
+
+        Self {
+            profile,
+            axis_x,
+            capped,
+            trace,
+            orientation: if area >= 0.0 { 1.0 } else { -1.0 },
+        }
+    }
+
+    /// Where the profile is at `t`, as distance from the axis and height.
+    fn sample(&self, t: f64) -> (f64, f64) {
+        let (x, y, _) = self.profile.f(t);
+        (x - self.axis_x, y)
+    }
+
+    /// Outward normal in the plane of the profile, for a curve heading along `(dr, dy)`.
+    fn profile_normal(&self, (dr, dy): (f64, f64)) -> (f64, f64) {
+        let length = dr.hypot(dy);
+        if length == 0.0 {
+            return (0.0, 0.0);
+        }
+        (
+            self.orientation * dy / length,
+            -self.orientation * dr / length,
+        )
+    }
+
+    /// Revolves the profile at the given parameters into `steps` pieces around the axis.
+    /// Corners of the profile get two rings, so the faces either side stay sharp.
+    pub fn add_to_matrix(&self, p: &mut Mesh, parameters: &[f64], steps: usize) {
+        let steps = steps.max(3);
+        let corners = self.profile.corners();
+        let points: Vec<(f64, f64)> = parameters.iter().map(|&t| self.sample(t)).collect();
+        let heading = |from: (f64, f64), to: (f64, f64)| {
+            let (dr, dy) = (to.0 - from.0, to.1 - from.1);
+            let length = dr.hypot(dy);
+            if length == 0.0 {
+                (0.0, 0.0)
+            } else {
+                (dr / length, dy / length)
+            }
+        };
+
+        // Each row is a point on the profile and the direction the surface leaves it in
+        let mut rows: Vec<((f64, f64), (f64, f64))> = Vec::new();
+        for (i, &point) in points.iter().enumerate() {
+            let before = (i > 0).then(|| heading(points[i - 1], point));
+            let after = (i + 1 < points.len()).then(|| heading(point, points[i + 1]));
+            match (before, after) {
+                (Some(before), Some(after)) if corners.contains(&parameters[i]) => {
+                    rows.push((point, before));
+                    rows.push((point, after));
+                }
+                (Some(before), Some(after)) => {
+                    rows.push((point, (before.0 + after.0, before.1 + after.1)))
+                }
+                (Some(one_side), None) | (None, Some(one_side)) => rows.push((point, one_side)),
+                (None, None) => {}
+            }
+        }
+        // Caps run flat between the axis and the ends, with edges as sharp as a corner's
+        if let (true, Some(&first), Some(&last)) = (self.capped, points.first(), points.last()) {
+            if first.0 != 0.0 {
+                let inward = heading((0.0, first.1), first);
+                rows.splice(0..0, [((0.0, first.1), inward), (first, inward)]);
+            }
+            if last.0 != 0.0 {
+                let outward = heading(last, (0.0, last.1));
+                rows.extend([(last, outward), ((0.0, last.1), outward)]);
+            }
+        }
+
+        let spokes: Vec<(f64, f64)> = (0..steps)
+            .map(|s| {
+                let angle = TAU * s as f64 / steps as f64;
+                (angle.cos(), angle.sin())
+            })
+            .collect();
+        let rings: Vec<Vec<usize>> = rows
+            .iter()
+            .map(|&((r, y), tangent)| {
+                let (nr, ny) = self.profile_normal(tangent);
+                spokes
+                    .iter()
+                    .map(|&(cos, sin)| {
+                        p.add_vertex(
+                            (self.axis_x + r * cos, y, r * sin),
+                            Vector3D::new(nr * cos, ny, nr * sin),
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+
+        for i in 0..rows.len().saturating_sub(1) {
+            let ((r0, y0), _) = rows[i];
+            let ((r1, y1), _) = rows[i + 1];
+            if (r0, y0) == (r1, y1) {
+                continue;
+            }
+            // Spinning a profile on the far side of the axis mirrors the winding
+            let mirrored = (r0 + r1 < 0.0) != (self.orientation < 0.0);
+            for s in 0..steps {
+                let next = (s + 1) % steps;
+                let (a, b, c, d) = (
+                    rings[i][s],
+                    rings[i + 1][s],
+                    rings[i + 1][next],
+                    rings[i][next],
+                );
+                // Rings on the axis collapse to a point, leaving only one triangle per quad
+                if r1 != 0.0 {
+                    if mirrored {
+                        p.add_face(a, c, b);
+                    } else {
+                        p.add_face(a, b, c);
+                    }
+                }
+                if r0 != 0.0 {
+                    if mirrored {
+                        p.add_face(a, d, c);
+                    } else {
+                        p.add_face(a, c, d);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Straight pieces of the traced profile, as distance from the axis and height at each end.
+    fn pieces(&self) -> impl Iterator<Item = ((f64, f64), (f64, f64))> + '_ {
+        self.trace
+            .windows(2)
+            .map(|w| ((w[0].1.abs(), w[0].2), (w[1].1.abs(), w[1].2)))
+    }
+
+    /// The traced piece nearest a point given by its distance from the axis and height, with
+    /// how far along the piece the nearest point is.
+    fn nearest_piece(&self, (rho, y): (f64, f64)) -> (usize, f64) {
+        self.pieces()
+            .enumerate()
+            .map(|(i, ((r0, y0), (r1, y1)))| {
+                let (dr, dy) = (r1 - r0, y1 - y0);
+                let length = dr * dr + dy * dy;
+                let along = if length > 0.0 {
+                    (((rho - r0) * dr + (y - y0) * dy) / length).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let distance = (r0 + dr * along - rho).hypot(y0 + dy * along - y);
+                (i, along, distance)
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .map(|(i, along, _)| (i, along))
+            .expect("A lathe profile has at least one piece")
+    }
+}
+
+impl Shape for Lathe {
+    fn tessellate(&self, p: &mut Mesh, tessellation: &Tessellation) {
+        let parameters = self
+            .profile
+            .flatten(tessellation.tolerance / tessellation.scale);
+        let steps = tessellation.subdivisions.unwrap_or_else(|| {
+            let widest = self
+                .trace
+                .iter()
+                .map(|(_, r, _)| r.abs())
+                .fold(0.0, f64::max);
+            tessellation.circle_segments(widest)
+        });
+        self.add_to_matrix(p, &parameters, steps.min(Tessellation::MAX_SEGMENTS));
+    }
+
+    fn bounds(&self) -> Aabb {
+        let (widest, low, high) = self.trace.iter().fold(
+            (0.0, f64::INFINITY, f64::NEG_INFINITY),
+            |(widest, low, high): (f64, f64, f64), &(_, r, y)| {
+                (widest.max(r.abs()), low.min(y), high.max(y))
+            },
+        );
+        Aabb::new(
+            Vector3D::new(self.axis_x - widest, low, -widest),
+            Vector3D::new(self.axis_x + widest, high, widest),
+        )
+    }
+
+    /// Each traced piece spins into a band of a cone, or a flat ring when it is level.
+    fn hit_distance(&self, ray: &Ray) -> Option<f64> {
+        self.bounds().ray_span(ray)?;
+        let (o, d) = (
+            ray.origin - Vector3D::new(self.axis_x, 0.0, 0.0),
+            ray.direction,
+        );
+        let candidates = self.pieces().flat_map(|((r0, y0), (r1, y1))| {
+            let (low, high) = (y0.min(y1), y0.max(y1));
+            if y0 == y1 {
+                let t = (y0 - o.y) / d.y;
+                let rho = (o.x + d.x * t).hypot(o.z + d.z * t);
+                let on_ring = d.y != 0.0 && (r0.min(r1)..=r0.max(r1)).contains(&rho);
+                return [on_ring.then_some(t), None];
+            }
+            // Distance from the axis grows linearly with height along the band
+            let slope = (r1 - r0) / (y1 - y0);
+            let offset = r0 - slope * y0;
+            let start = offset + slope * o.y;
+            let Some((t0, t1)) = quadratic_roots(
+                d.x * d.x + d.z * d.z - slope * slope * d.y * d.y,
+                2.0 * (o.x * d.x + o.z * d.z - slope * start * d.y),
+                o.x * o.x + o.z * o.z - start * start,
+            ) else {
+                return [None, None];
+            };
+            [t0, t1].map(|t| {
+                let y = o.y + d.y * t;
+                ((low..=high).contains(&y) && offset + slope * y >= 0.0).then_some(t)
+            })
+        });
+        nearest_hit(candidates.flatten())
+    }
+
+    fn normal_at(&self, point: Vector3D) -> Option<Vector3D> {
+        let local = point - Vector3D::new(self.axis_x, 0.0, 0.0);
+        let rho = local.x.hypot(local.z);
+        let (i, _) = self.nearest_piece((rho, local.y));
+        let ((_, r0, y0), (_, r1, y1)) = (self.trace[i], self.trace[i + 1]);
+        let (nr, ny) = self.profile_normal((r1 - r0, y1 - y0));
+        // Pieces on the far side of the axis face the other way once spun around
+        let nr = nr * (r0 + r1).signum();
+        Some(if rho > 0.0 {
+            Vector3D::new(nr * local.x / rho, ny, nr * local.z / rho).normalize()
+        } else {
+            Vector3D::new(0.0, ny.signum(), 0.0)
+        })
+    }
+
+    /// Once around the axis, then along the profile from its start to its end.
+    fn uv_at(&self, point: Vector3D) -> Option<(f64, f64)> {
+        let local = point - Vector3D::new(self.axis_x, 0.0, 0.0);
+        let (i, along) = self.nearest_piece((local.x.hypot(local.z), local.y));
+        let (t0, t1) = (self.trace[i].0, self.trace[i + 1].0);
+        Some((
+            turn(local.x, local.z),
+            (t0 + (t1 - t0) * along).clamp(0.0, 1.0),
+        ))
+    }
+}
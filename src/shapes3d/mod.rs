@@ -1,3 +1,6 @@
+mod shape;
+pub use shape::{Aabb, Hit, Ray, Shape, Tessellation, HIT_EPSILON};
+
 mod cube;
 pub use cube::Cube;
 
@@ -6,3 +9,400 @@ pub use sphere::Sphere;
 
 mod torus;
 pub use torus::Torus;
+
+mod cylinder;
+pub use cylinder::Cylinder;
+
+mod cone;
+pub use cone::Cone;
+
+mod plane;
+pub use plane::Plane;
+
+mod pyramid;
+pub use pyramid::Pyramid;
+
+mod icosphere;
+pub use icosphere::Icosphere;
+
+mod lathe;
+pub use lathe::Lathe;
+
+mod sweep;
+pub use sweep::Sweep;
+
+mod patch;
+pub use patch::BezierPatch;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{
+        BezierPatch, Cone, Cube, Cylinder, Icosphere, Lathe, Plane, Pyramid, Ray, Shape, Sphere,
+        Sweep, Tessellation, Torus,
+    };
+    use crate::{
+        curves::{Bezier, Circle, Polyline},
+        matrix::Mesh,
+        Vector3D,
+    };
+
+    /// Each shape with a point inside it, and whether it is convex.
+    fn shapes() -> Vec<(Box<dyn Shape>, Vector3D, bool)> {
+        vec![
+            (
+                Box::new(Cube::new((0.0, 10.0, 0.0), 10.0, 10.0, 10.0)),
+                Vector3D::new(5.0, 5.0, -5.0),
+                true,
+            ),
+            (
+                Box::new(Sphere::new(10.0, (5.0, 5.0, 5.0))),
+                Vector3D::new(5.0, 5.0, 5.0),
+                true,
+            ),
+            (
+                Box::new(Torus::new(3.0, 10.0, (0.0, 0.0, 0.0))),
+                Vector3D::new(10.0, 0.0, 0.0),
+                false,
+            ),
+            (
+                Box::new(Cylinder::new(10.0, 20.0, (0.0, 0.0, 0.0), true)),
+                Vector3D::new(0.0, 10.0, 0.0),
+                true,
+            ),
+            (
+                Box::new(Cone::new(10.0, 20.0, (0.0, 0.0, 0.0))),
+                Vector3D::new(0.0, 5.0, 0.0),
+                true,
+            ),
+            (
+                Box::new(Pyramid::new(10.0, 20.0, (0.0, 0.0, 0.0))),
+                Vector3D::new(0.0, 5.0, 0.0),
+                true,
+            ),
+            (
+                Box::new(Icosphere::new(10.0, (5.0, 5.0, 5.0))),
+                Vector3D::new(5.0, 5.0, 5.0),
+                true,
+            ),
+            // A capped cylinder, drawn up the right of the axis and down the left of it
+            (
+                Box::new(Lathe::new(
+                    Arc::new(Polyline::new(vec![
+                        (5.0, 0.0),
+                        (15.0, 0.0),
+                        (15.0, 20.0),
+                        (5.0, 20.0),
+                    ])),
+                    5.0,
+                    true,
+                )),
+                Vector3D::new(5.0, 10.0, 0.0),
+                true,
+            ),
+            (
+                Box::new(Lathe::new(
+                    Arc::new(Polyline::new(vec![
+                        (0.0, 20.0),
+                        (-10.0, 20.0),
+                        (-10.0, 0.0),
+                        (0.0, 0.0),
+                    ])),
+                    0.0,
+                    true,
+                )),
+                Vector3D::new(0.0, 10.0, 0.0),
+                true,
+            ),
+            // A ring whose ends stop short of the axis, closed by its caps
+            (
+                Box::new(Lathe::new(
+                    Arc::new(Polyline::new(vec![
+                        (10.0, 0.0),
+                        (40.0, 0.0),
+                        (40.0, 50.0),
+                        (10.0, 50.0),
+                    ])),
+                    0.0,
+                    true,
+                )),
+                Vector3D::new(5.0, 25.0, 0.0),
+                true,
+            ),
+            (
+                Box::new(Sweep::extrusion(
+                    Arc::new(Circle::new(10.0, (0.0, 0.0, 0.0))),
+                    20.0,
+                )),
+                Vector3D::new(0.0, 0.0, -10.0),
+                true,
+            ),
+            // A square bar bent round a corner, drawn clockwise
+            (
+                Box::new(Sweep::new(
+                    Arc::new(Polyline::new(vec![
+                        (-3.0, -3.0),
+                        (-3.0, 3.0),
+                        (3.0, 3.0),
+                        (3.0, -3.0),
+                        (-3.0, -3.0),
+                    ])),
+                    Arc::new(Polyline::new(vec![(0.0, 0.0), (20.0, 0.0), (20.0, 20.0)])),
+                    true,
+                )),
+                Vector3D::new(10.0, 0.0, 0.0),
+                false,
+            ),
+        ]
+    }
+
+    /// Every face should point away from `inside` its centroid, and the normals generated at
+    /// its corners should agree with its winding.
+    fn assert_outward(p: &Mesh, inside: impl Fn(Vector3D) -> Vector3D) {
+        assert!(p.get_poly_count() > 0);
+        for (corners, normal) in p.triangles() {
+            let centroid = corners
+                .iter()
+                .map(|corner| {
+                    let (x, y, z, w) = corner.position;
+                    Vector3D::new(x / w, y / w, z / w)
+                })
+                .sum::<Vector3D>()
+                .scale(1.0 / 3.0);
+            assert!(
+                normal.dot(&(centroid - inside(centroid))) > 0.0,
+                "{:?}",
+                centroid
+            );
+            for corner in corners {
+                assert!(corner.normal.dot(&normal) > 0.0, "{:?}", corner);
+            }
+        }
+    }
+
+    #[test]
+    fn primitives_face_outward() {
+        let tessellation = Tessellation::new(1.0, 0.1);
+        // Every face of a convex shape points away from any point inside it
+        for (shape, inside, _) in shapes().into_iter().filter(|(_, _, convex)| *convex) {
+            let mut p = Mesh::default();
+            shape.tessellate(&mut p, &tessellation);
+            assert_outward(&p, |_| inside);
+        }
+
+        let mut p = Mesh::default();
+        Plane::new((0.0, 0.0, 0.0), 10.0, 10.0)
+            .tessellate(&mut p, &tessellation.with_subdivisions(3));
+        assert_eq!(p.get_poly_count(), 18);
+        let mut p = Mesh::default();
+        Icosphere::new(10.0, (0.0, 0.0, 0.0))
+            .tessellate(&mut p, &tessellation.with_subdivisions(2));
+        assert_eq!(p.get_poly_count(), 20 * 16);
+    }
+
+    #[test]
+    fn lathe_spins_curves_smoothly() {
+        // A bulb: out from the axis and back in again
+        let bulb = Lathe::new(
+            Arc::new(Bezier::new(
+                (0.0, 0.0),
+                (20.0, 0.0),
+                (20.0, 20.0),
+                (0.0, 20.0),
+            )),
+            0.0,
+            true,
+        );
+        let mut p = Mesh::default();
+        bulb.tessellate(&mut p, &Tessellation::new(1.0, 0.1).with_subdivisions(12));
+        assert_eq!(p.get_poly_count() % 12, 0);
+        assert_outward(&p, |_| Vector3D::new(0.0, 10.0, 0.0));
+        // Neighbouring faces share smooth normals that lean well towards each face
+        for (corners, normal) in p.triangles() {
+            for corner in corners {
+                assert!(corner.normal.dot(&normal) > 0.5, "{:?}", corner);
+            }
+        }
+
+        // Finer tolerances cut the profile into more rings
+        let (mut coarse, mut fine) = (Mesh::default(), Mesh::default());
+        bulb.tessellate(
+            &mut coarse,
+            &Tessellation::new(1.0, 1.0).with_subdivisions(12),
+        );
+        bulb.tessellate(
+            &mut fine,
+            &Tessellation::new(1.0, 0.01).with_subdivisions(12),
+        );
+        assert!(fine.get_poly_count() > coarse.get_poly_count());
+
+        // However many pieces the script asks for, the lathe stops at the usual circle's worth
+        let (mut capped, mut huge) = (Mesh::default(), Mesh::default());
+        let coarse = Tessellation::new(1.0, 1.0);
+        bulb.tessellate(
+            &mut capped,
+            &coarse.with_subdivisions(Tessellation::MAX_SEGMENTS),
+        );
+        bulb.tessellate(&mut huge, &coarse.with_subdivisions(1_000_000));
+        assert_eq!(huge.get_poly_count(), capped.get_poly_count());
+    }
+
+    #[test]
+    fn sweeps_round_a_loop_join_up() {
+        let ring = Sweep::new(
+            Arc::new(Circle::new(2.0, (0.0, 0.0, 0.0))),
+            Arc::new(Circle::new(10.0, (0.0, 0.0, 0.0))),
+            true,
+        );
+        let mut p = Mesh::default();
+        ring.tessellate(&mut p, &Tessellation::new(1.0, 0.1));
+        let points: Vec<Vector3D> = p.points().collect();
+        // No caps, and the last copy of the outline lands on the first. Each copy repeats its
+        // first point at the end, to carry the seam's texture coordinates.
+        let ring_size = points
+            .iter()
+            .skip(1)
+            .position(|point| (*point - points[0]).magnitude() < 1e-6)
+            .expect("outline should come back round to its start")
+            + 2;
+        let last = &points[points.len() - ring_size..];
+        for (end, start) in last.iter().zip(&points) {
+            assert!((*end - *start).magnitude() < 1e-6, "{:?} {:?}", end, start);
+        }
+
+        // Every face points away from the circle the path follows
+        assert_outward(&p, |centroid| {
+            Vector3D::new(centroid.x, centroid.y, 0.0)
+                .normalize()
+                .scale(10.0)
+        });
+
+        // The outline is cut into no more pieces than the usual circle's worth
+        let (mut capped, mut huge) = (Mesh::default(), Mesh::default());
+        let coarse = Tessellation::new(1.0, 1.0);
+        ring.tessellate(
+            &mut capped,
+            &coarse.with_subdivisions(Tessellation::MAX_SEGMENTS),
+        );
+        ring.tessellate(&mut huge, &coarse.with_subdivisions(1_000_000));
+        assert_eq!(huge.get_poly_count(), capped.get_poly_count());
+    }
+
+    #[test]
+    fn patches_bulge_between_their_corners() {
+        // A dome over a 30x30 square, rising in the middle and facing up
+        let dome = BezierPatch::new(std::array::from_fn(|i| {
+            std::array::from_fn(|j| {
+                let raised = (1..3).contains(&i) && (1..3).contains(&j);
+                (
+                    10.0 * j as f64,
+                    if raised { 10.0 } else { 0.0 },
+                    10.0 * i as f64,
+                )
+            })
+        }));
+        let corner = dome.point(1.0, 1.0);
+        assert_eq!((corner.x, corner.y, corner.z), (30.0, 0.0, 30.0));
+        assert!((dome.point(0.5, 0.5).y - 5.625).abs() < 1e-9);
+
+        let mut p = Mesh::default();
+        dome.tessellate(&mut p, &Tessellation::new(1.0, 0.1));
+        assert!(p.get_poly_count() > 2);
+        assert_outward(&p, |centroid| centroid - Vector3D::new(0.0, 1.0, 0.0));
+        let hit = dome
+            .intersect(&Ray::new(
+                Vector3D::new(15.0, 50.0, 15.0),
+                Vector3D::new(0.0, -1.0, 0.0),
+            ))
+            .expect("ray should hit the top of the dome");
+        assert!((hit.point.y - 5.625).abs() < 0.01, "{:?}", hit);
+        assert!(hit.normal.unwrap().y > 0.99);
+
+        // A flat patch needs no more than one cell, however close it is
+        let flat = BezierPatch::new(std::array::from_fn(|i| {
+            std::array::from_fn(|j| (j as f64, i as f64, 0.0))
+        }));
+        let mut p = Mesh::default();
+        flat.tessellate(&mut p, &Tessellation::new(100.0, 0.1));
+        assert_eq!(p.get_poly_count(), 2);
+    }
+
+    #[test]
+    fn segments_follow_size_on_screen() {
+        let near = Tessellation::new(10.0, 0.1);
+        let far = Tessellation::new(0.01, 0.1);
+        let radius = 50.0;
+        assert!(near.circle_segments(radius) > Tessellation::new(1.0, 0.1).circle_segments(radius));
+        assert_eq!(far.circle_segments(radius), Tessellation::MIN_SEGMENTS);
+
+        // The chords' bulge stays within the tolerance, in screen pixels
+        let segments = near.circle_segments(radius);
+        let screen_radius = radius * near.scale;
+        let bulge = screen_radius * (1.0 - (std::f64::consts::PI / segments as f64).cos());
+        assert!(bulge <= near.tolerance, "{}", bulge);
+        assert!(Tessellation::new(10.0, 1.0).circle_segments(radius) < segments);
+    }
+
+    #[test]
+    fn requested_detail_is_capped() {
+        let huge = Tessellation::new(1.0, 0.1).with_subdivisions(1_000_000);
+        let mut p = Mesh::default();
+        Plane::new((0.0, 0.0, 0.0), 10.0, 10.0).tessellate(&mut p, &huge);
+        assert_eq!(p.get_poly_count(), 2 * Plane::MAX_DIVISIONS.pow(2));
+
+        let mut p = Mesh::default();
+        Icosphere::new(10.0, (0.0, 0.0, 0.0)).tessellate(&mut p, &huge);
+        assert_eq!(
+            p.get_poly_count(),
+            20 * 4usize.pow(Icosphere::MAX_SUBDIVISIONS as u32)
+        );
+    }
+
+    #[test]
+    fn rays_hit_the_near_side() {
+        let directions = [
+            Vector3D::new(1.0, 0.0, 0.0),
+            Vector3D::new(0.0, -1.0, 0.0),
+            Vector3D::new(0.0, 0.0, -1.0),
+            Vector3D::new(-0.3, 0.5, 0.8).normalize(),
+        ];
+        for (shape, inside, _) in shapes() {
+            let bounds = shape.bounds();
+            for direction in directions {
+                // Aim at the inside point from well outside the shape
+                let ray = Ray::new(inside - direction.scale(100.0), direction);
+                let hit = shape.intersect(&ray).expect("ray should hit");
+                assert!(hit.t < 100.0, "{:?}", hit);
+                assert!(
+                    (hit.point.x - bounds.min.x) > -1e-6 && (bounds.max.x - hit.point.x) > -1e-6
+                );
+                assert!(
+                    (hit.point.y - bounds.min.y) > -1e-6 && (bounds.max.y - hit.point.y) > -1e-6
+                );
+                let normal = hit.normal.unwrap();
+                assert!(normal.dot(&direction) < 0.0, "{:?} {:?}", hit, direction);
+                let (u, v) = hit.uv.unwrap();
+                assert!(
+                    (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v),
+                    "{:?}",
+                    hit
+                );
+            }
+        }
+
+        let sphere = Sphere::new(10.0, (0.0, 0.0, 0.0));
+        let hit = sphere
+            .intersect(&Ray::new(
+                Vector3D::new(0.0, 0.0, 50.0),
+                Vector3D::new(0.0, 0.0, -1.0),
+            ))
+            .unwrap();
+        assert!((hit.t - 40.0).abs() < 1e-9);
+        let miss = Ray::new(
+            Vector3D::new(0.0, 20.0, 50.0),
+            Vector3D::new(0.0, 0.0, -1.0),
+        );
+        assert!(sphere.intersect(&miss).is_none());
+    }
+}
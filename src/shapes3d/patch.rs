@@ -0,0 +1,289 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    sync::OnceLock,
+};
+
+use super::{
+    shape::{Surface, Vertex},
+    Aabb, Ray, Shape, Tessellation,
+};
+use crate::{
+    matrix::{Const2D, Mesh},
+    Vector3D,
+};
+
+/// A bicubic Bezier surface, pulled toward a 4x4 grid of control points and passing through
+/// the four corner ones.
+///
+/// The first index of the grid runs along `u` and the second along `v`. The surface faces the
+/// way `∂P/∂u × ∂P/∂v` points, which for the usual teapot data is outward.
+#[derive(Clone, Debug)]
+pub struct BezierPatch {
+    points: [[Vector3D; 4]; 4],
+    coeff_x: Const2D<f64, 4, 4>,
+    coeff_y: Const2D<f64, 4, 4>,
+    coeff_z: Const2D<f64, 4, 4>,
+    /// What rays hit in place of the patch, cut to `TRACE_TOLERANCE` on first use.
+    trace: OnceLock<Surface>,
+}
+
+impl BezierPatch {
+    /// Finest the traced surface gets, as a fraction of its size.
+    const TRACE_TOLERANCE: f64 = 1e-3;
+    pub const MAX_SEGMENTS: usize = 64;
+
+    pub fn new(points: [[(f64, f64, f64); 4]; 4]) -> Self {
+        let bezier_mul = Const2D::from([
+            [-1.0, 3.0, -3.0, 1.0],
+            [3.0, -6.0, 3.0, 0.0],
+            [-3.0, 3.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+        ]);
+        // The Bezier matrix is symmetric, so it serves on both sides of the geometry
+        let coefficients = |axis: fn(&(f64, f64, f64)) -> f64| {
+            let geometry = Const2D::from(points.map(|row| row.map(|point| axis(&point))));
+            &(&bezier_mul * &geometry) * &bezier_mul
+        };
+
+        Self {
+            points: points.map(|row| row.map(Vector3D::from_point)),
+            coeff_x: coefficients(|point| point.0),
+            coeff_y: coefficients(|point| point.1),
+            coeff_z: coefficients(|point| point.2),
+            trace: OnceLock::new(),
+        }
+    }
+
+    /// Reads a file of patches in Newell's teapot layout: the number of patches, then 16
+    /// one-based vertex numbers for each patch, then the number of vertices, then `x y z` for
+    /// each. Numbers may be split by commas or whitespace.
+    pub fn load(filename: &str) -> io::Result<Vec<Self>> {
+        parse_patches(&fs::read_to_string(filename)?)
+    }
+
+    /// `U C Vᵀ` for one axis's coefficients, with `U` and `V` the powers of `u` and `v` or
+    /// their derivatives.
+    fn combine(coefficients: &Const2D<f64, 4, 4>, u: [f64; 4], v: [f64; 4]) -> f64 {
+        (0..4)
+            .map(|i| {
+                (0..4)
+                    .map(|j| u[i] * coefficients[i][j] * v[j])
+                    .sum::<f64>()
+            })
+            .sum()
+    }
+
+    fn evaluate(&self, u: [f64; 4], v: [f64; 4]) -> Vector3D {
+        Vector3D::new(
+            Self::combine(&self.coeff_x, u, v),
+            Self::combine(&self.coeff_y, u, v),
+            Self::combine(&self.coeff_z, u, v),
+        )
+    }
+
+    fn powers(t: f64) -> [f64; 4] {
+        [t * t * t, t * t, t, 1.0]
+    }
+
+    fn slopes(t: f64) -> [f64; 4] {
+        [3.0 * t * t, 2.0 * t, 1.0, 0.0]
+    }
+
+    pub fn point(&self, u: f64, v: f64) -> Vector3D {
+        self.evaluate(Self::powers(u), Self::powers(v))
+    }
+
+    /// The unit normal from the partial derivatives. Where those vanish or run parallel, as
+    /// at the tip of the teapot's lid, the normal is taken from just inside the patch.
+    pub fn normal(&self, u: f64, v: f64) -> Vector3D {
+        let (mut u, mut v) = (u, v);
+        for _ in 0..8 {
+            let along_u = self.evaluate(Self::slopes(u), Self::powers(v));
+            let along_v = self.evaluate(Self::powers(u), Self::slopes(v));
+            let normal = along_u.cross(&along_v);
+            if normal.magnitude() > 1e-12 {
+                return normal.normalize();
+            }
+            u += (0.5 - u) * 1e-3;
+            v += (0.5 - v) * 1e-3;
+        }
+        Vector3D::new(0.0, 0.0, 0.0)
+    }
+
+    /// How many pieces each side is cut into for the flat cells to stay within the tolerance.
+    /// A cubic's second derivative is at most six times the largest second difference of its
+    /// control points, and a chord strays at most an eighth of that over its length squared.
+    pub fn segments(&self, tessellation: &Tessellation) -> usize {
+        if let Some(subdivisions) = tessellation.subdivisions {
+            return subdivisions.clamp(1, Self::MAX_SEGMENTS);
+        }
+        let p = &self.points;
+        let along_u = (0..2)
+            .flat_map(|i| (0..4).map(move |j| p[i][j] - p[i + 1][j].scale(2.0) + p[i + 2][j]));
+        let along_v = (0..4)
+            .flat_map(|i| (0..2).map(move |j| p[i][j] - p[i][j + 1].scale(2.0) + p[i][j + 2]));
+        let bend = along_u
+            .chain(along_v)
+            .map(|difference| difference.magnitude())
+            .fold(0.0, f64::max);
+        ((0.75 * bend * tessellation.scale / tessellation.tolerance)
+            .sqrt()
+            .ceil() as usize)
+            .clamp(1, Self::MAX_SEGMENTS)
+    }
+
+    fn surface(&self, tessellation: &Tessellation) -> Surface {
+        let segments = self.segments(tessellation);
+        let step = 1.0 / segments as f64;
+        let mut surface = Surface::default();
+        for i in 0..=segments {
+            for j in 0..=segments {
+                let (u, v) = (i as f64 * step, j as f64 * step);
+                surface.vertices.push(Vertex {
+                    position: self.point(u, v),
+                    normal: self.normal(u, v),
+                    uv: (u, v),
+                });
+            }
+        }
+
+        let index = |i: usize, j: usize| i * (segments + 1) + j;
+        for i in 0..segments {
+            for j in 0..segments {
+                let (a, b, c, d) = (
+                    index(i, j),
+                    index(i + 1, j),
+                    index(i + 1, j + 1),
+                    index(i, j + 1),
+                );
+                // Edges of the grid pinched to a point leave only one triangle in their cells
+                for face in [[a, b, c], [a, c, d]] {
+                    let [p0, p1, p2] = face.map(|k| surface.vertices[k].position);
+                    if (p1 - p0).cross(&(p2 - p0)).magnitude() > 0.0 {
+                        surface.faces.push(face);
+                    }
+                }
+            }
+        }
+        surface
+    }
+
+    fn trace(&self) -> &Surface {
+        self.trace.get_or_init(|| {
+            let bounds = self.bounds();
+            let size = (bounds.max - bounds.min).magnitude().max(f64::EPSILON);
+            self.surface(&Tessellation::new(1.0, size * Self::TRACE_TOLERANCE))
+        })
+    }
+}
+
+impl Shape for BezierPatch {
+    fn tessellate(&self, p: &mut Mesh, tessellation: &Tessellation) {
+        self.surface(tessellation).add_to(p);
+    }
+
+    /// The surface stays inside the box around its control points.
+    fn bounds(&self) -> Aabb {
+        let first = self.points[0][0];
+        self.points
+            .iter()
+            .flatten()
+            .fold(Aabb::new(first, first), |bounds, &point| {
+                bounds.union(&Aabb::new(point, point))
+            })
+    }
+
+    fn hit_distance(&self, ray: &Ray) -> Option<f64> {
+        self.bounds().ray_span(ray)?;
+        self.trace().hit_distance(ray)
+    }
+
+    fn normal_at(&self, point: Vector3D) -> Option<Vector3D> {
+        let (u, v) = self.trace().at(point)?.1;
+        Some(self.normal(u, v))
+    }
+
+    fn uv_at(&self, point: Vector3D) -> Option<(f64, f64)> {
+        Some(self.trace().at(point)?.1)
+    }
+}
+
+fn parse_patches(text: &str) -> io::Result<Vec<BezierPatch>> {
+    let invalid = |message: &str| io::Error::new(ErrorKind::InvalidData, message.to_string());
+    let mut tokens = text
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|token| !token.is_empty());
+    let mut count = |what: &str| {
+        tokens
+            .next()
+            .and_then(|token| token.parse::<usize>().ok())
+            .ok_or_else(|| invalid(&format!("Expected the number of {}", what)))
+    };
+
+    let patch_count = count("patches")?;
+    let mut indices = Vec::with_capacity(patch_count * 16);
+    for _ in 0..patch_count * 16 {
+        indices.push(count("a vertex")?);
+    }
+    let vertex_count = count("vertices")?;
+    let mut vertices = Vec::with_capacity(vertex_count);
+    for _ in 0..vertex_count {
+        let mut coordinate = || {
+            tokens
+                .next()
+                .and_then(|token| token.parse::<f64>().ok())
+                .ok_or_else(|| invalid("Expected a vertex coordinate"))
+        };
+        vertices.push((coordinate()?, coordinate()?, coordinate()?));
+    }
+
+    indices
+        .chunks(16)
+        .map(|patch| {
+            let mut points = [[(0.0, 0.0, 0.0); 4]; 4];
+            for (k, &index) in patch.iter().enumerate() {
+                points[k / 4][k % 4] = *index
+                    .checked_sub(1)
+                    .and_then(|index| vertices.get(index))
+                    .ok_or_else(|| invalid(&format!("No vertex numbered {}", index)))?;
+            }
+            Ok(BezierPatch::new(points))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_patches;
+
+    #[test]
+    fn patches_read_one_based_vertices() {
+        // One flat patch over a 4x4 grid, numbered row by row
+        let mut text = String::from("1\n");
+        text += &(1..=16)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        text += "\n16\n";
+        for i in 0..4 {
+            for j in 0..4 {
+                text += &format!("{}.0,{}.0,0.0\n", i, j);
+            }
+        }
+        let patches = parse_patches(&text).unwrap();
+        assert_eq!(patches.len(), 1);
+        let far = patches[0].point(1.0, 1.0);
+        assert_eq!((far.x, far.y, far.z), (3.0, 3.0, 0.0));
+        let middle = patches[0].point(0.5, 0.5);
+        assert!((middle.x - 1.5).abs() < 1e-12 && (middle.y - 1.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn malformed_patches_are_rejected() {
+        assert!(parse_patches("").is_err());
+        assert!(parse_patches("1\n1 2 3").is_err());
+        let out_of_range = format!("1\n{}\n1\n0 0 0\n", vec!["2"; 16].join(" "));
+        assert!(parse_patches(&out_of_range).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use super::{shape::nearest_hit, Aabb, Ray, Shape, Tessellation};
+use crate::{matrix::Mesh, Vector3D};
+
+/// A flat, upward-facing rectangle split into a grid of quads. Like a box, it extends right
+/// and back from its corner.
+#[derive(Clone, Copy, Debug)]
+pub struct Plane {
+    left: f64,
+    y: f64,
+    front: f64,
+    width: f64,
+    depth: f64,
+}
+
+impl Plane {
+    /// Most cells a plane is cut into along each side.
+    pub const MAX_DIVISIONS: usize = 256;
+
+    pub fn new(corner: (f64, f64, f64), width: f64, depth: f64) -> Self {
+        Self {
+            left: corner.0,
+            y: corner.1,
+            front: corner.2,
+            width,
+            depth,
+        }
+    }
+
+    pub fn add_to_matrix(&self, p: &mut Mesh, divisions: usize) {
+        let divisions = divisions.max(1);
+        let up = Vector3D::new(0.0, 1.0, 0.0);
+        let grid: Vec<Vec<usize>> = (0..=divisions)
+            .map(|j| {
+                (0..=divisions)
+                    .map(|i| {
+                        let point = (
+                            self.left + self.width * i as f64 / divisions as f64,
+                            self.y,
+                            self.front - self.depth * j as f64 / divisions as f64,
+                        );
+                        p.add_vertex(point, up)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        (0..divisions).for_each(|j| {
+            (0..divisions).for_each(|i| {
+                p.add_face(grid[j][i], grid[j][i + 1], grid[j + 1][i + 1]);
+                p.add_face(grid[j][i], grid[j + 1][i + 1], grid[j + 1][i]);
+            });
+        });
+    }
+}
+
+impl Shape for Plane {
+    /// Being flat, a single cell is exact unless the tessellation asks for a finer grid.
+    fn tessellate(&self, p: &mut Mesh, tessellation: &Tessellation) {
+        let divisions = tessellation.subdivisions.unwrap_or(1);
+        self.add_to_matrix(p, divisions.clamp(1, Self::MAX_DIVISIONS));
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::new(
+            Vector3D::new(self.left, self.y, self.front - self.depth),
+            Vector3D::new(self.left + self.width, self.y, self.front),
+        )
+    }
+
+    fn hit_distance(&self, ray: &Ray) -> Option<f64> {
+        if ray.direction.y == 0.0 {
+            return None;
+        }
+        let t = (self.y - ray.origin.y) / ray.direction.y;
+        let point = ray.at(t);
+        let (u, v) = self.uv_at(point)?;
+        ((0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v))
+            .then(|| nearest_hit([t]))
+            .flatten()
+    }
+
+    fn normal_at(&self, _point: Vector3D) -> Option<Vector3D> {
+        Some(Vector3D::new(0.0, 1.0, 0.0))
+    }
+
+    /// `u` runs left to right and `v` front to back.
+    fn uv_at(&self, point: Vector3D) -> Option<(f64, f64)> {
+        Some((
+            (point.x - self.left) / self.width,
+            (self.front - point.z) / self.depth,
+        ))
+    }
+}
@@ -0,0 +1,118 @@
+use super::{
+    shape::{nearest_hit, turn},
+    Aabb, Ray, Shape, Tessellation,
+};
+use crate::{matrix::Mesh, Vector3D};
+
+/// A square pyramid with its base centered on `center` and its tip `height` above.
+#[derive(Clone, Copy, Debug)]
+pub struct Pyramid {
+    side: f64,
+    height: f64,
+    center: (f64, f64, f64),
+}
+
+impl Pyramid {
+    pub fn new(side: f64, height: f64, center: (f64, f64, f64)) -> Self {
+        Self {
+            side,
+            height,
+            center,
+        }
+    }
+
+    /// Outward unit normal of each face, with the value `normal · point` takes on it. Points
+    /// inside give less on every face.
+    fn faces(&self) -> [(Vector3D, f64); 5] {
+        let (x, y, z) = self.center;
+        let half = self.side / 2.0;
+        let face = |normal: Vector3D, point: (f64, f64, f64)| {
+            let normal = normal.normalize();
+            (normal, normal.dot(&Vector3D::from_point(point)))
+        };
+        [
+            face(Vector3D::new(self.height, half, 0.0), (x + half, y, z)),
+            face(Vector3D::new(-self.height, half, 0.0), (x - half, y, z)),
+            face(Vector3D::new(0.0, half, self.height), (x, y, z + half)),
+            face(Vector3D::new(0.0, half, -self.height), (x, y, z - half)),
+            face(Vector3D::new(0.0, -1.0, 0.0), (x, y, z)),
+        ]
+    }
+
+    pub fn add_to_matrix(&self, p: &mut Mesh) {
+        let (x, y, z) = self.center;
+        let half = self.side / 2.0;
+        let apex = (x, y + self.height, z);
+        // Going around counterclockwise when seen from below
+        let corners = [
+            (x + half, y, z + half),
+            (x - half, y, z + half),
+            (x - half, y, z - half),
+            (x + half, y, z - half),
+        ];
+
+        (0..4).for_each(|i| {
+            p.add_facet(corners[i], apex, corners[(i + 1) % 4]);
+        });
+        p.add_facet(corners[0], corners[1], corners[2]);
+        p.add_facet(corners[0], corners[2], corners[3]);
+    }
+}
+
+impl Shape for Pyramid {
+    fn tessellate(&self, p: &mut Mesh, _tessellation: &Tessellation) {
+        self.add_to_matrix(p);
+    }
+
+    fn bounds(&self) -> Aabb {
+        let (x, y, z) = self.center;
+        let half = self.side / 2.0;
+        Aabb::new(
+            Vector3D::new(x - half, y, z - half),
+            Vector3D::new(x + half, y + self.height, z + half),
+        )
+    }
+
+    /// Clips the ray against each face in turn, as with any convex solid.
+    fn hit_distance(&self, ray: &Ray) -> Option<f64> {
+        let (mut enter, mut exit) = (f64::NEG_INFINITY, f64::INFINITY);
+        for (normal, offset) in self.faces() {
+            let toward = normal.dot(&ray.direction);
+            let room = offset - normal.dot(&ray.origin);
+            if toward == 0.0 {
+                if room < 0.0 {
+                    return None;
+                }
+            } else if toward < 0.0 {
+                enter = enter.max(room / toward);
+            } else {
+                exit = exit.min(room / toward);
+            }
+        }
+        (enter <= exit)
+            .then(|| nearest_hit([enter, exit]))
+            .flatten()
+    }
+
+    fn normal_at(&self, point: Vector3D) -> Option<Vector3D> {
+        self.faces()
+            .into_iter()
+            .max_by(|(a, a_offset), (b, b_offset)| {
+                (a.dot(&point) - a_offset).total_cmp(&(b.dot(&point) - b_offset))
+            })
+            .map(|(normal, _)| normal)
+    }
+
+    /// The sides wrap once around, base to tip. The base is mapped flat.
+    fn uv_at(&self, point: Vector3D) -> Option<(f64, f64)> {
+        let local = point - Vector3D::from_point(self.center);
+        Some(if self.normal_at(point)?.y == -1.0 {
+            (local.x / self.side + 0.5, local.z / self.side + 0.5)
+        } else {
+            (
+                turn(local.x, local.z),
+                (local.y / self.height).clamp(0.0, 1.0),
+            )
+        })
+    }
+}
@@ -0,0 +1,304 @@
+use crate::{matrix::Mesh, Vector3D};
+
+/// Closest a hit can be to the ray origin, so a ray leaving a surface does not immediately
+/// hit it again.
+pub const HIT_EPSILON: f64 = 1e-6;
+
+/// How finely shapes are cut into triangles, judged by how far the flat facets stray from
+/// the true surface once they are on screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tessellation {
+    /// Screen pixels covered by one unit of the shape's own coordinates.
+    pub scale: f64,
+    /// Furthest, in screen pixels, a facet may sit from the curved surface it stands in for.
+    pub tolerance: f64,
+    /// Exact detail to use instead of working it out from the tolerance: how many times to
+    /// split the faces of shapes built by subdivision, how many cells a plane has along each
+    /// side, how many pieces a lathe is cut into around its axis, or how many cells a patch
+    /// has along each side.
+    pub subdivisions: Option<usize>,
+}
+
+impl Tessellation {
+    /// Fewest pieces a circle is cut into, however small it is on screen.
+    pub const MIN_SEGMENTS: usize = 6;
+    pub const MAX_SEGMENTS: usize = 1024;
+
+    pub fn new(scale: f64, tolerance: f64) -> Self {
+        Self {
+            scale,
+            tolerance,
+            subdivisions: None,
+        }
+    }
+
+    pub fn with_subdivisions(self, subdivisions: usize) -> Self {
+        Self {
+            subdivisions: Some(subdivisions),
+            ..self
+        }
+    }
+
+    /// How many straight pieces a circle of this radius needs for its chords to stay within
+    /// the tolerance.
+    pub fn circle_segments(&self, radius: f64) -> usize {
+        let radius = radius.abs() * self.scale;
+        if radius <= self.tolerance {
+            return Self::MIN_SEGMENTS;
+        }
+        // A chord spanning twice this angle bulges out to exactly the tolerance
+        let half_angle = (1.0 - self.tolerance / radius).acos();
+        ((std::f64::consts::PI / half_angle).ceil() as usize)
+            .clamp(Self::MIN_SEGMENTS, Self::MAX_SEGMENTS)
+    }
+}
+
+/// An axis-aligned bounding box.
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vector3D,
+    pub max: Vector3D,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3D, max: Vector3D) -> Self {
+        Self { min, max }
+    }
+
+    /// The box around a center point, reaching out by `extent` on each axis.
+    pub fn around(center: (f64, f64, f64), extent: Vector3D) -> Self {
+        let center = Vector3D::from_point(center);
+        Self::new(center - extent, center + extent)
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(
+            Vector3D::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            Vector3D::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        )
+    }
+
+    pub fn contains(&self, point: Vector3D) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x)
+            && (self.min.y..=self.max.y).contains(&point.y)
+            && (self.min.z..=self.max.z).contains(&point.z)
+    }
+
+    /// Distances at which `ray` enters and leaves the box, if it passes through at all. The
+    /// entry is negative when the ray starts inside.
+    pub fn ray_span(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let axes = [
+            (ray.origin.x, ray.direction.x, self.min.x, self.max.x),
+            (ray.origin.y, ray.direction.y, self.min.y, self.max.y),
+            (ray.origin.z, ray.direction.z, self.min.z, self.max.z),
+        ];
+        let mut span = (f64::NEG_INFINITY, f64::INFINITY);
+        for (origin, direction, min, max) in axes {
+            if direction == 0.0 {
+                if origin < min || origin > max {
+                    return None;
+                }
+                continue;
+            }
+            let (t0, t1) = ((min - origin) / direction, (max - origin) / direction);
+            span = (span.0.max(t0.min(t1)), span.1.min(t0.max(t1)));
+        }
+        (span.0 <= span.1).then_some(span)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    pub origin: Vector3D,
+    pub direction: Vector3D,
+}
+
+impl Ray {
+    pub fn new(origin: Vector3D, direction: Vector3D) -> Self {
+        Self { origin, direction }
+    }
+
+    pub fn at(&self, t: f64) -> Vector3D {
+        self.origin + self.direction.scale(t)
+    }
+}
+
+/// Where a ray meets a shape.
+#[derive(Clone, Copy, Debug)]
+pub struct Hit {
+    /// Distance along the ray, in multiples of its direction.
+    pub t: f64,
+    pub point: Vector3D,
+    pub normal: Option<Vector3D>,
+    pub uv: Option<(f64, f64)>,
+}
+
+/// Anything that can be drawn as triangles and traced with rays. Everything is in the
+/// shape's own coordinates, before the transform stack is applied.
+pub trait Shape {
+    /// Adds the surface to `p` as triangles wound counterclockwise when seen from outside.
+    fn tessellate(&self, p: &mut Mesh, tessellation: &Tessellation);
+
+    /// Smallest axis-aligned box holding the whole shape.
+    fn bounds(&self) -> Aabb;
+
+    /// Distance to the nearest point past `HIT_EPSILON` where `ray` meets the surface.
+    fn hit_distance(&self, ray: &Ray) -> Option<f64>;
+
+    /// Outward unit normal at a point on the surface, for shapes that know it exactly.
+    fn normal_at(&self, _point: Vector3D) -> Option<Vector3D> {
+        None
+    }
+
+    /// Texture coordinates, each from 0 to 1, of a point on the surface.
+    fn uv_at(&self, _point: Vector3D) -> Option<(f64, f64)> {
+        None
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let t = self.hit_distance(ray)?;
+        let point = ray.at(t);
+        Some(Hit {
+            t,
+            point,
+            normal: self.normal_at(point),
+            uv: self.uv_at(point),
+        })
+    }
+}
+
+/// Both solutions of `a t^2 + b t + c = 0`, smaller first.
+pub(super) fn quadratic_roots(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        return (b != 0.0).then(|| (-c / b, -c / b));
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    // Avoids cancellation when b is much larger than the discriminant's root
+    let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+    let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+/// Nearest of the candidate distances that lies in front of the ray origin.
+pub(super) fn nearest_hit(candidates: impl IntoIterator<Item = f64>) -> Option<f64> {
+    candidates
+        .into_iter()
+        .filter(|t| *t > HIT_EPSILON)
+        .min_by(f64::total_cmp)
+}
+
+/// Angle around the y axis, from 0 to 1.
+pub(super) fn turn(x: f64, z: f64) -> f64 {
+    z.atan2(x).rem_euclid(std::f64::consts::TAU) / std::f64::consts::TAU
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Vertex {
+    pub position: Vector3D,
+    pub normal: Vector3D,
+    pub uv: (f64, f64),
+}
+
+/// Triangles standing in for a surface that rays cannot meet exactly, carrying what a hit
+/// needs to know at each corner.
+#[derive(Clone, Debug, Default)]
+pub(super) struct Surface {
+    pub vertices: Vec<Vertex>,
+    /// Counterclockwise seen from outside, as in a `Mesh`.
+    pub faces: Vec<[usize; 3]>,
+}
+
+impl Surface {
+    pub fn add_to(&self, p: &mut Mesh) {
+        let indices: Vec<usize> = self
+            .vertices
+            .iter()
+            .map(|vertex| {
+                let position = vertex.position;
+                p.add_vertex((position.x, position.y, position.z), vertex.normal)
+            })
+            .collect();
+        for &[a, b, c] in &self.faces {
+            p.add_face(indices[a], indices[b], indices[c]);
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        let mut positions = self.vertices.iter().map(|vertex| vertex.position);
+        let first = positions.next().unwrap_or(Vector3D::new(0.0, 0.0, 0.0));
+        positions.fold(Aabb::new(first, first), |bounds, position| {
+            bounds.union(&Aabb::new(position, position))
+        })
+    }
+
+    pub fn hit_distance(&self, ray: &Ray) -> Option<f64> {
+        nearest_hit(self.faces.iter().filter_map(|face| {
+            let [p0, p1, p2] = face.map(|i| self.vertices[i].position);
+            let (e1, e2) = (p1 - p0, p2 - p0);
+            let h = ray.direction.cross(&e2);
+            let det = e1.dot(&h);
+            if det.abs() < HIT_EPSILON * HIT_EPSILON {
+                return None;
+            }
+            let offset = ray.origin - p0;
+            let u = offset.dot(&h) / det;
+            let q = offset.cross(&e1);
+            let v = ray.direction.dot(&q) / det;
+            (u >= 0.0 && v >= 0.0 && u + v <= 1.0).then(|| e2.dot(&q) / det)
+        }))
+    }
+
+    /// The normal and texture coordinates at a point on the surface, blended from the corners
+    /// of the triangle it lies on.
+    pub fn at(&self, point: Vector3D) -> Option<(Vector3D, (f64, f64))> {
+        let (face, weights) = self
+            .faces
+            .iter()
+            .filter_map(|face| {
+                let [p0, p1, p2] = face.map(|i| self.vertices[i].position);
+                let (e1, e2) = (p1 - p0, p2 - p0);
+                let normal = e1.cross(&e2);
+                let area = normal.dot(&normal);
+                if area == 0.0 {
+                    return None;
+                }
+                let offset = point - p0;
+                let w1 = offset.cross(&e2).dot(&normal) / area;
+                let w2 = e1.cross(&offset).dot(&normal) / area;
+                let weights = [1.0 - w1 - w2, w1, w2];
+                let distance = offset.dot(&normal).abs() / area.sqrt();
+                weights
+                    .iter()
+                    .all(|w| *w >= -1e-6)
+                    .then_some((face, weights, distance))
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .map(|(face, weights, _)| (face, weights))?;
+
+        let corners = face.map(|i| self.vertices[i]);
+        let normal = corners
+            .iter()
+            .zip(weights)
+            .map(|(corner, weight)| corner.normal.scale(weight))
+            .sum::<Vector3D>()
+            .normalize();
+        let uv = corners
+            .iter()
+            .zip(weights)
+            .fold((0.0, 0.0), |(u, v), (corner, weight)| {
+                (u + corner.uv.0 * weight, v + corner.uv.1 * weight)
+            });
+        Some((normal, (uv.0.clamp(0.0, 1.0), uv.1.clamp(0.0, 1.0))))
+    }
+}
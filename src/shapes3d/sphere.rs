@@ -1,6 +1,10 @@
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::f64::consts::{PI, TAU};
 
-use crate::matrix::PolygonMatrix;
+use super::{
+    shape::{nearest_hit, quadratic_roots},
+    Aabb, Ray, Shape, Tessellation,
+};
+use crate::{matrix::Mesh, Vector3D};
 
 #[derive(Clone, Copy, Debug)]
 pub struct Sphere {
@@ -13,66 +17,70 @@ impl Sphere {
         Self { radius, center }
     }
 
-    fn generate(&self, steps: usize) -> Vec<(f64, f64, f64)> {
+    pub fn add_to_matrix(&self, p: &mut Mesh, steps: usize) {
         let circle_steps: usize = steps / 2;
-        (0..steps)
-            .into_par_iter()
-            .flat_map(|s| {
+        // One column of vertices per half circle, each running from the +x pole to the -x pole
+        let columns: Vec<Vec<usize>> = (0..steps)
+            .map(|s| {
+                let rot = TAU * s as f64 / steps as f64;
                 (0..=circle_steps)
-                    .into_par_iter()
-                    .map(move |cs| -> (f64, f64, f64) {
-                        let cir = cs as f64 / circle_steps as f64;
-                        let rot = s as f64 / steps as f64;
-                        (
-                            self.radius * (std::f64::consts::PI * cir).cos() + self.center.0,
-                            self.radius
-                                * (std::f64::consts::PI * cir).sin()
-                                * (std::f64::consts::TAU * rot).cos()
-                                + self.center.1,
-                            self.radius
-                                * (std::f64::consts::PI * cir).sin()
-                                * (std::f64::consts::TAU * rot).sin()
-                                + self.center.2,
-                        )
+                    .map(|cs| {
+                        let cir = PI * cs as f64 / circle_steps as f64;
+                        let direction =
+                            Vector3D::new(cir.cos(), cir.sin() * rot.cos(), cir.sin() * rot.sin());
+                        let point =
+                            Vector3D::from_point(self.center) + direction.scale(self.radius);
+                        p.add_vertex((point.x, point.y, point.z), direction)
                     })
+                    .collect()
             })
-            .collect()
-    }
+            .collect();
 
-    pub fn add_to_matrix(&self, p: &mut PolygonMatrix, steps: usize) {
-        let points = self.generate(steps);
-        let n: usize = steps / 2 + 1;
-        (0..steps - 1).for_each(|turn| {
-            p.add_triangle(
-                points[turn * n],
-                points[turn * n + 1],
-                points[(turn + 1) * n + 1],
-            );
-            p.add_triangle(
-                points[(turn + 1) * n - 2],
-                points[(turn + 1) * n - 1],
-                points[(turn + 2) * n - 2],
-            );
-            (turn * n + 1..(turn + 1) * n - 2).for_each(|pi| {
-                p.add_triangle(points[pi], points[pi + 1], points[pi + n + 1]);
-                p.add_triangle(points[pi], points[pi + n + 1], points[pi + n]);
+        (0..steps).for_each(|turn| {
+            let (this, next) = (&columns[turn], &columns[(turn + 1) % steps]);
+            (0..circle_steps).for_each(|cs| {
+                // Neither triangle is kept where two of its corners are the same pole
+                if cs + 1 < circle_steps {
+                    p.add_face(this[cs], this[cs + 1], next[cs + 1]);
+                }
+                if cs > 0 {
+                    p.add_face(this[cs], next[cs + 1], next[cs]);
+                }
             });
         });
+    }
+}
 
-        p.add_triangle(
-            points[(steps - 1) * n],
-            points[(steps - 1) * n + 1],
-            points[1],
-        );
-        p.add_triangle(points[steps * n - 2], points[steps * n - 1], points[n - 2]);
+impl Shape for Sphere {
+    fn tessellate(&self, p: &mut Mesh, tessellation: &Tessellation) {
+        self.add_to_matrix(p, tessellation.circle_segments(self.radius));
+    }
 
-        (1..n - 2).for_each(|pi| {
-            p.add_triangle(
-                points[(steps - 1) * n + pi],
-                points[(steps - 1) * n + pi + 1],
-                points[pi + 1],
-            );
-            p.add_triangle(points[(steps - 1) * n + pi], points[pi + 1], points[pi]);
-        });
+    fn bounds(&self) -> Aabb {
+        Aabb::around(
+            self.center,
+            Vector3D::new(self.radius, self.radius, self.radius),
+        )
+    }
+
+    fn hit_distance(&self, ray: &Ray) -> Option<f64> {
+        let offset = ray.origin - Vector3D::from_point(self.center);
+        let (t0, t1) = quadratic_roots(
+            ray.direction.dot(&ray.direction),
+            2.0 * offset.dot(&ray.direction),
+            offset.dot(&offset) - self.radius * self.radius,
+        )?;
+        nearest_hit([t0, t1])
+    }
+
+    fn normal_at(&self, point: Vector3D) -> Option<Vector3D> {
+        Some((point - Vector3D::from_point(self.center)).normalize())
+    }
+
+    /// Follows the tessellation: `u` turns around the x axis and `v` runs from +x to -x.
+    fn uv_at(&self, point: Vector3D) -> Option<(f64, f64)> {
+        let local = point - Vector3D::from_point(self.center);
+        let v = (local.x / self.radius).clamp(-1.0, 1.0).acos() / PI;
+        Some((local.z.atan2(local.y).rem_euclid(TAU) / TAU, v))
     }
 }
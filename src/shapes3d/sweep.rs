@@ -0,0 +1,549 @@
+use std::sync::{Arc, OnceLock};
+
+use super::{
+    shape::{Surface, Vertex},
+    Aabb, Ray, Shape, Tessellation,
+};
+use crate::{curves::Parametric, matrix::Mesh, Vector3D};
+
+/// A closed outline carried along a path, staying square to the path and twisting as little
+/// as it can on the way.
+///
+/// The outline is read in its own xy plane, with the path passing through its origin. Its x
+/// axis starts out as close to the world x axis as the path allows. Open paths are capped at
+/// both ends, and paths that end where they start join up into a ring.
+#[derive(Clone)]
+pub struct Sweep {
+    profile: Arc<dyn Parametric + Send + Sync>,
+    path: Arc<dyn Parametric + Send + Sync>,
+    capped: bool,
+    /// The surface cut much finer than any screen needs, built the first time a ray asks.
+    trace: OnceLock<Surface>,
+}
+
+/// A straight path, for extrusions.
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    start: Vector3D,
+    end: Vector3D,
+}
+
+impl Parametric for Segment {
+    fn x(&self, t: f64) -> f64 {
+        self.start.x + (self.end.x - self.start.x) * t
+    }
+
+    fn y(&self, t: f64) -> f64 {
+        self.start.y + (self.end.y - self.start.y) * t
+    }
+
+    fn z(&self, t: f64) -> f64 {
+        self.start.z + (self.end.z - self.start.z) * t
+    }
+}
+
+/// A place along the path where a copy of the outline is laid down.
+#[derive(Clone, Copy, Debug)]
+struct Station {
+    center: Vector3D,
+    tangent: Vector3D,
+    /// At a sharp bend, the plane the outline is stretched onto so both sides meet cleanly.
+    miter: Option<Vector3D>,
+    t: f64,
+}
+
+/// A point on the outline, with its outward normal in the outline's plane.
+#[derive(Clone, Copy, Debug)]
+struct Column {
+    point: (f64, f64),
+    normal: (f64, f64),
+    t: f64,
+}
+
+impl Sweep {
+    /// Finest the traced surface gets, as a fraction of the shape's size.
+    const TRACE_TOLERANCE: f64 = 1e-3;
+    /// Most times a piece of the path is halved to keep the outside of a bend smooth.
+    const MAX_BEND_DEPTH: usize = 8;
+
+    pub fn new(
+        profile: Arc<dyn Parametric + Send + Sync>,
+        path: Arc<dyn Parametric + Send + Sync>,
+        capped: bool,
+    ) -> Self {
+        Self {
+            profile,
+            path,
+            capped,
+            trace: OnceLock::new(),
+        }
+    }
+
+    /// The outline pushed straight back from the xy plane, `depth` units towards -z, with
+    /// caps on both ends.
+    pub fn extrusion(profile: Arc<dyn Parametric + Send + Sync>, depth: f64) -> Self {
+        let path = Segment {
+            start: Vector3D::new(0.0, 0.0, 0.0),
+            end: Vector3D::new(0.0, 0.0, -depth),
+        };
+        Self::new(profile, Arc::new(path), true)
+    }
+
+    /// Furthest the outline reaches from the path.
+    fn reach(&self) -> f64 {
+        self.profile
+            .points(64)
+            .iter()
+            .map(|&(x, y, _)| x.hypot(y))
+            .fold(0.0, f64::max)
+    }
+
+    /// Splits pieces of the path that bend further than the outline can follow while its far
+    /// side stays within the tolerance, like the sides of a circle as wide as the outline.
+    fn follow_bends(&self, parameters: Vec<f64>, max_turn: f64) -> Vec<f64> {
+        fn split(
+            path: &(dyn Parametric + Send + Sync),
+            (t0, t1): (f64, f64),
+            max_turn: f64,
+            depth: usize,
+            out: &mut Vec<f64>,
+        ) {
+            // Directions just inside the piece, so a corner at either end does not count
+            let h = (t1 - t0) * 1e-3;
+            let direction = |a: f64, b: f64| {
+                (Vector3D::from_point(path.f(b)) - Vector3D::from_point(path.f(a))).normalize()
+            };
+            let turn = direction(t0, t0 + h)
+                .dot(&direction(t1 - h, t1))
+                .clamp(-1.0, 1.0)
+                .acos();
+            if turn > max_turn && depth < Sweep::MAX_BEND_DEPTH {
+                let middle = (t0 + t1) / 2.0;
+                split(path, (t0, middle), max_turn, depth + 1, out);
+                split(path, (middle, t1), max_turn, depth + 1, out);
+            } else {
+                out.push(t1);
+            }
+        }
+
+        let mut refined = vec![parameters[0]];
+        parameters
+            .windows(2)
+            .for_each(|span| split(&*self.path, (span[0], span[1]), max_turn, 0, &mut refined));
+        refined
+    }
+
+    /// Points around the outline with their normals, from its start back round to it again.
+    /// Also returns 1 if the outline runs counterclockwise and -1 if not, and its points once
+    /// each in order, for the caps.
+    fn columns(&self, parameters: &[f64]) -> (Vec<Column>, f64, Vec<(f64, f64)>) {
+        let corners = self.profile.corners();
+        let sample = |t: f64| {
+            let (x, y, _) = self.profile.f(t);
+            (x, y)
+        };
+        let mut points: Vec<(f64, f64)> = parameters.iter().map(|&t| sample(t)).collect();
+        let (first, last) = (points[0], points[points.len() - 1]);
+        let closed = (first.0 - last.0).hypot(first.1 - last.1) <= 1e-9 * self.reach().max(1.0);
+        if closed {
+            points.pop();
+        }
+        let count = points.len();
+
+        let area: f64 = (0..count)
+            .map(|j| {
+                let (a, b) = (points[j], points[(j + 1) % count]);
+                a.0 * b.1 - b.0 * a.1
+            })
+            .sum();
+        let orientation = if area >= 0.0 { 1.0 } else { -1.0 };
+        let heading = |from: (f64, f64), to: (f64, f64)| {
+            let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+            let length = dx.hypot(dy);
+            if length == 0.0 {
+                (0.0, 0.0)
+            } else {
+                (dx / length, dy / length)
+            }
+        };
+        let normal = |(dx, dy): (f64, f64)| {
+            let length = dx.hypot(dy);
+            if length == 0.0 {
+                (0.0, 0.0)
+            } else {
+                (orientation * dy / length, -orientation * dx / length)
+            }
+        };
+
+        // Where the outline does not close by itself, a straight edge closes it, with a corner
+        // at each end
+        let is_corner = |j: usize| {
+            corners.contains(&parameters[j])
+                || (j == 0 && (corners.contains(&0.0) || !closed))
+                || (j == count - 1 && !closed)
+        };
+        let mut columns = Vec::new();
+        let mut closing = None;
+        for j in 0..count {
+            let point = points[j];
+            let before = heading(points[(j + count - 1) % count], point);
+            let after = heading(point, points[(j + 1) % count]);
+            let smooth = normal((before.0 + after.0, before.1 + after.1));
+            let t = parameters[j];
+            if j == 0 {
+                let (start, end) = if is_corner(0) {
+                    (normal(after), normal(before))
+                } else {
+                    (smooth, smooth)
+                };
+                columns.push(Column {
+                    point,
+                    normal: start,
+                    t,
+                });
+                closing = Some(Column {
+                    point,
+                    normal: end,
+                    t: 1.0,
+                });
+            } else if is_corner(j) {
+                columns.push(Column {
+                    point,
+                    normal: normal(before),
+                    t,
+                });
+                columns.push(Column {
+                    point,
+                    normal: normal(after),
+                    t,
+                });
+            } else {
+                columns.push(Column {
+                    point,
+                    normal: smooth,
+                    t,
+                });
+            }
+        }
+        columns.extend(closing);
+        (columns, orientation, points)
+    }
+
+    /// Places along the path for the outline, and whether the path comes back to its start.
+    fn stations(&self, parameters: &[f64]) -> (Vec<Station>, bool) {
+        let corners = self.path.corners();
+        let mut centers: Vec<Vector3D> = parameters
+            .iter()
+            .map(|&t| Vector3D::from_point(self.path.f(t)))
+            .collect();
+        let gap = (centers[centers.len() - 1] - centers[0]).magnitude();
+        let closed = gap <= 1e-9 * self.reach().max(1.0);
+        if closed {
+            centers.pop();
+        }
+        let count = centers.len();
+        let heading = |from: Vector3D, to: Vector3D| {
+            let step = to - from;
+            if step.magnitude() == 0.0 {
+                step
+            } else {
+                step.normalize()
+            }
+        };
+
+        let mut stations = Vec::new();
+        let mut closing = None;
+        for i in 0..count {
+            let center = centers[i];
+            let t = parameters[i];
+            let before =
+                (i > 0 || closed).then(|| heading(centers[(i + count - 1) % count], center));
+            let after =
+                (i + 1 < count || closed).then(|| heading(center, centers[(i + 1) % count]));
+            let station = |tangent: Vector3D, miter: Option<Vector3D>| Station {
+                center,
+                tangent,
+                miter,
+                t,
+            };
+            match (before, after) {
+                (Some(before), Some(after)) if corners.contains(&t) => {
+                    let miter = Some((before + after).normalize());
+                    let arriving = station(before, miter);
+                    if i == 0 {
+                        closing = Some(Station { t: 1.0, ..arriving });
+                    } else {
+                        stations.push(arriving);
+                    }
+                    stations.push(station(after, miter));
+                }
+                (Some(before), Some(after)) => {
+                    stations.push(station((before + after).normalize(), None))
+                }
+                (Some(one_side), None) | (None, Some(one_side)) => {
+                    stations.push(station(one_side, None))
+                }
+                (None, None) => {}
+            }
+        }
+        if closed {
+            stations.push(closing.unwrap_or(Station {
+                t: 1.0,
+                ..stations[0]
+            }));
+        }
+        (stations, closed)
+    }
+
+    /// The outline's x axis at each station, carried along by double reflection so it twists
+    /// as little as possible. Around a closed path, whatever twist is left over is spread
+    /// evenly along it so the ends meet.
+    fn frames(stations: &[Station], closed: bool) -> Vec<Vector3D> {
+        let reflect = |v: Vector3D, across: Vector3D| {
+            let c = across.dot(&across);
+            if c == 0.0 {
+                v
+            } else {
+                v - across.scale(2.0 / c * across.dot(&v))
+            }
+        };
+        let square_to =
+            |v: Vector3D, tangent: Vector3D| (v - tangent.scale(v.dot(&tangent))).normalize();
+        let transport = |r: Vector3D, from: &Station, to: &Station| {
+            let step = to.center - from.center;
+            let (r, tangent) = (reflect(r, step), reflect(from.tangent, step));
+            square_to(reflect(r, to.tangent - tangent), to.tangent)
+        };
+
+        let first = stations[0].tangent;
+        let reference = if first.x.abs() < 0.9 {
+            Vector3D::new(1.0, 0.0, 0.0)
+        } else {
+            Vector3D::new(0.0, 1.0, 0.0)
+        };
+        let mut frames = vec![square_to(reference, first)];
+        for pair in stations.windows(2) {
+            let r = transport(frames[frames.len() - 1], &pair[0], &pair[1]);
+            frames.push(r);
+        }
+
+        if closed {
+            let end = transport(
+                frames[frames.len() - 1],
+                &stations[stations.len() - 1],
+                &stations[0],
+            );
+            let twist = end.cross(&frames[0]).dot(&first).atan2(end.dot(&frames[0]));
+            let lengths: Vec<f64> = std::iter::once(0.0)
+                .chain(stations.windows(2).scan(0.0, |length, pair| {
+                    *length += (pair[1].center - pair[0].center).magnitude();
+                    Some(*length)
+                }))
+                .collect();
+            let total = lengths[lengths.len() - 1].max(f64::EPSILON);
+            for ((r, station), length) in frames.iter_mut().zip(stations).zip(lengths) {
+                let angle = twist * length / total;
+                *r = r.scale(angle.cos()) + station.tangent.cross(r).scale(angle.sin());
+            }
+        }
+        frames
+    }
+
+    fn surface(&self, tessellation: &Tessellation) -> Surface {
+        let tolerance = tessellation.tolerance / tessellation.scale;
+        let profile_parameters = match tessellation.subdivisions {
+            Some(pieces) => {
+                let pieces = pieces.clamp(2, Tessellation::MAX_SEGMENTS);
+                let mut parameters: Vec<f64> = (0..=pieces)
+                    .map(|i| i as f64 / pieces as f64)
+                    .chain(self.profile.corners())
+                    .collect();
+                parameters.sort_by(f64::total_cmp);
+                parameters.dedup();
+                parameters
+            }
+            None => self.profile.flatten(tolerance),
+        };
+        let max_turn = std::f64::consts::TAU / tessellation.circle_segments(self.reach()) as f64;
+        let path_parameters = self.follow_bends(self.path.flatten(tolerance), max_turn);
+
+        let (columns, orientation, outline) = self.columns(&profile_parameters);
+        let (stations, closed) = self.stations(&path_parameters);
+        let frames = Self::frames(&stations, closed);
+
+        let mut surface = Surface::default();
+        let place = |station: &Station, r: Vector3D, (x, y): (f64, f64)| {
+            let b = r.cross(&station.tangent);
+            let offset = r.scale(x) + b.scale(y);
+            let lean = match station.miter {
+                Some(miter) => -offset.dot(&miter) / station.tangent.dot(&miter),
+                None => 0.0,
+            };
+            station.center + offset + station.tangent.scale(lean)
+        };
+        let rings: Vec<Vec<usize>> = stations
+            .iter()
+            .zip(&frames)
+            .map(|(station, &r)| {
+                let b = r.cross(&station.tangent);
+                columns
+                    .iter()
+                    .map(|column| {
+                        surface.vertices.push(Vertex {
+                            position: place(station, r, column.point),
+                            normal: r.scale(column.normal.0) + b.scale(column.normal.1),
+                            uv: (column.t, station.t),
+                        });
+                        surface.vertices.len() - 1
+                    })
+                    .collect()
+            })
+            .collect();
+
+        for i in 0..stations.len().saturating_sub(1) {
+            // The two stations at a sharp bend lie on top of each other
+            if (stations[i + 1].center - stations[i].center).magnitude() == 0.0 {
+                continue;
+            }
+            for j in 0..columns.len() - 1 {
+                if columns[j].point == columns[j + 1].point {
+                    continue;
+                }
+                let (a, b, c, d) = (
+                    rings[i][j],
+                    rings[i][j + 1],
+                    rings[i + 1][j + 1],
+                    rings[i + 1][j],
+                );
+                if orientation > 0.0 {
+                    surface.faces.extend([[a, d, c], [a, c, b]]);
+                } else {
+                    surface.faces.extend([[a, c, d], [a, b, c]]);
+                }
+            }
+        }
+
+        if self.capped && !closed {
+            let mut order: Vec<usize> = (0..outline.len()).collect();
+            if orientation < 0.0 {
+                order.reverse();
+            }
+            let ordered: Vec<(f64, f64)> = order.iter().map(|&k| outline[k]).collect();
+            let triangles = triangulate(&ordered);
+            let (low, high) = outline.iter().fold(
+                (
+                    (f64::INFINITY, f64::INFINITY),
+                    (f64::NEG_INFINITY, f64::NEG_INFINITY),
+                ),
+                |(low, high), &(x, y)| {
+                    ((low.0.min(x), low.1.min(y)), (high.0.max(x), high.1.max(y)))
+                },
+            );
+            let span = (
+                (high.0 - low.0).max(f64::EPSILON),
+                (high.1 - low.1).max(f64::EPSILON),
+            );
+
+            let ends = [
+                (&stations[0], frames[0], -1.0),
+                (&stations[stations.len() - 1], frames[frames.len() - 1], 1.0),
+            ];
+            for (station, r, facing) in ends {
+                let cap: Vec<usize> = ordered
+                    .iter()
+                    .map(|&point| {
+                        surface.vertices.push(Vertex {
+                            position: place(station, r, point),
+                            normal: station.tangent.scale(facing),
+                            uv: ((point.0 - low.0) / span.0, (point.1 - low.1) / span.1),
+                        });
+                        surface.vertices.len() - 1
+                    })
+                    .collect();
+                // Counterclockwise in the outline's plane faces back along the path
+                surface.faces.extend(triangles.iter().map(|&[a, b, c]| {
+                    if facing < 0.0 {
+                        [cap[a], cap[b], cap[c]]
+                    } else {
+                        [cap[a], cap[c], cap[b]]
+                    }
+                }));
+            }
+        }
+        surface
+    }
+
+    fn trace(&self) -> &Surface {
+        self.trace.get_or_init(|| {
+            let (low, high) = self.path.points(64).iter().fold(
+                (
+                    Vector3D::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+                    Vector3D::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+                ),
+                |(low, high), &(x, y, z)| {
+                    (
+                        Vector3D::new(low.x.min(x), low.y.min(y), low.z.min(z)),
+                        Vector3D::new(high.x.max(x), high.y.max(y), high.z.max(z)),
+                    )
+                },
+            );
+            let size = ((high - low).magnitude() + 2.0 * self.reach()).max(f64::EPSILON);
+            self.surface(&Tessellation::new(1.0, size * Self::TRACE_TOLERANCE))
+        })
+    }
+}
+
+/// Cuts a simple counterclockwise polygon into triangles by clipping off one ear at a time.
+fn triangulate(outline: &[(f64, f64)]) -> Vec<[usize; 3]> {
+    let cross = |o: (f64, f64), a: (f64, f64), b: (f64, f64)| {
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    };
+    let mut remaining: Vec<usize> = (0..outline.len()).collect();
+    let mut triangles = Vec::new();
+    while remaining.len() >= 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&k| {
+            let [a, b, c] = [(k + count - 1) % count, k, (k + 1) % count].map(|k| remaining[k]);
+            let (pa, pb, pc) = (outline[a], outline[b], outline[c]);
+            cross(pa, pb, pc) > 0.0
+                && remaining.iter().all(|&other| {
+                    let p = outline[other];
+                    [a, b, c].contains(&other)
+                        || cross(pa, pb, p) < 0.0
+                        || cross(pb, pc, p) < 0.0
+                        || cross(pc, pa, p) < 0.0
+                })
+        });
+        // Nothing left but slivers, or the outline crosses itself
+        let Some(k) = ear else {
+            break;
+        };
+        triangles.push([(k + count - 1) % count, k, (k + 1) % count].map(|k| remaining[k]));
+        remaining.remove(k);
+    }
+    triangles
+}
+
+impl Shape for Sweep {
+    fn tessellate(&self, p: &mut Mesh, tessellation: &Tessellation) {
+        self.surface(tessellation).add_to(p);
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.trace().bounds()
+    }
+
+    fn hit_distance(&self, ray: &Ray) -> Option<f64> {
+        self.bounds().ray_span(ray)?;
+        self.trace().hit_distance(ray)
+    }
+
+    fn normal_at(&self, point: Vector3D) -> Option<Vector3D> {
+        Some(self.trace().at(point)?.0)
+    }
+
+    /// Around the outline, then along the path. The caps are mapped flat across the outline.
+    fn uv_at(&self, point: Vector3D) -> Option<(f64, f64)> {
+        Some(self.trace().at(point)?.1)
+    }
+}
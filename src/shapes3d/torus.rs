@@ -1,6 +1,10 @@
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::f64::consts::TAU;
 
-use crate::matrix::PolygonMatrix;
+use super::{
+    shape::{turn, HIT_EPSILON},
+    Aabb, Ray, Shape, Tessellation,
+};
+use crate::{matrix::Mesh, Vector3D};
 
 #[derive(Clone, Copy, Debug)]
 pub struct Torus {
@@ -18,81 +22,96 @@ impl Torus {
         }
     }
 
-    fn generate_torus(&self, ring_steps: usize, cir_steps: usize) -> Vec<(f64, f64, f64)> {
-        (0..ring_steps)
-            .into_par_iter()
-            .flat_map(|s0| {
-                (0..=cir_steps)
-                    .into_par_iter()
-                    .map(move |s1| -> (f64, f64, f64) {
-                        let p = s0 as f64 / ring_steps as f64;
-                        let t = s1 as f64 / cir_steps as f64;
-                        (
-                            (p * std::f64::consts::TAU).cos()
-                                * (self.thickness * (std::f64::consts::TAU * t).cos()
-                                    + self.radius)
-                                + self.center.0,
-                            self.thickness * (std::f64::consts::TAU * t).sin() + self.center.1,
-                            -(p * std::f64::consts::TAU).sin()
-                                * (self.thickness * (std::f64::consts::TAU * t).cos()
-                                    + self.radius)
-                                + self.center.2,
-                        )
+    /// Signed distance from a point to the surface, negative inside.
+    fn distance(&self, point: Vector3D) -> f64 {
+        let local = point - Vector3D::from_point(self.center);
+        (local.x.hypot(local.z) - self.radius).hypot(local.y) - self.thickness
+    }
+
+    pub fn add_to_matrix(&self, p: &mut Mesh, ring_steps: usize, cir_steps: usize) {
+        let rings: Vec<Vec<usize>> = (0..ring_steps)
+            .map(|s0| {
+                let around = TAU * s0 as f64 / ring_steps as f64;
+                (0..cir_steps)
+                    .map(|s1| {
+                        let across = TAU * s1 as f64 / cir_steps as f64;
+                        let outward = Vector3D::new(around.cos(), 0.0, -around.sin());
+                        let normal =
+                            outward.scale(across.cos()) + Vector3D::new(0.0, across.sin(), 0.0);
+                        let point = Vector3D::from_point(self.center)
+                            + outward.scale(self.radius)
+                            + normal.scale(self.thickness);
+                        p.add_vertex((point.x, point.y, point.z), normal)
                     })
+                    .collect()
             })
-            .collect()
-    }
+            .collect();
 
-    pub fn add_to_matrix(&self, p: &mut PolygonMatrix, ring_steps: usize, cir_steps: usize) {
-        let points = self.generate_torus(ring_steps, cir_steps);
-        (0..(ring_steps - 1)).for_each(|s0| {
-            (0..(cir_steps - 1)).for_each(|s1| {
-                p.add_triangle(
-                    points[s0 * (cir_steps + 1) + s1],
-                    points[(s0 + 1) * (cir_steps + 1) + s1 + 1],
-                    points[s0 * (cir_steps + 1) + s1 + 1],
-                );
-                p.add_triangle(
-                    points[s0 * (cir_steps + 1) + s1],
-                    points[(s0 + 1) * (cir_steps + 1) + s1],
-                    points[(s0 + 1) * (cir_steps + 1) + s1 + 1],
-                );
+        (0..ring_steps).for_each(|s0| {
+            let (this, next) = (&rings[s0], &rings[(s0 + 1) % ring_steps]);
+            (0..cir_steps).for_each(|s1| {
+                let s2 = (s1 + 1) % cir_steps;
+                p.add_face(this[s1], next[s2], this[s2]);
+                p.add_face(this[s1], next[s1], next[s2]);
             });
-
-            p.add_triangle(
-                points[s0 * (cir_steps + 1) + cir_steps - 1],
-                points[(s0 + 1) * (cir_steps + 1)],
-                points[s0 * (cir_steps + 1)],
-            );
-            p.add_triangle(
-                points[s0 * (cir_steps + 1) + cir_steps - 1],
-                points[(s0 + 1) * (cir_steps + 1) + cir_steps - 1],
-                points[(s0 + 1) * (cir_steps + 1)],
-            );
-        });
-
-        (0..(cir_steps - 1)).for_each(|s1| {
-            p.add_triangle(
-                points[(ring_steps - 1) * (cir_steps + 1) + s1],
-                points[(cir_steps + 1) + s1 + 1],
-                points[(ring_steps - 1) * (cir_steps + 1) + s1 + 1],
-            );
-            p.add_triangle(
-                points[(ring_steps - 1) * (cir_steps + 1) + s1],
-                points[(cir_steps + 1) + s1],
-                points[(cir_steps + 1) + s1 + 1],
-            );
         });
+    }
+}
 
-        p.add_triangle(
-            points[(ring_steps - 1) * (cir_steps + 1) + cir_steps - 1],
-            points[(cir_steps + 1)],
-            points[(ring_steps - 1) * (cir_steps + 1)],
-        );
-        p.add_triangle(
-            points[(ring_steps - 1) * (cir_steps + 1) + cir_steps - 1],
-            points[(cir_steps + 1) + cir_steps - 1],
-            points[(cir_steps + 1)],
+impl Shape for Torus {
+    fn tessellate(&self, p: &mut Mesh, tessellation: &Tessellation) {
+        self.add_to_matrix(
+            p,
+            tessellation.circle_segments(self.radius + self.thickness),
+            tessellation.circle_segments(self.thickness),
         );
     }
+
+    fn bounds(&self) -> Aabb {
+        let reach = self.radius + self.thickness;
+        Aabb::around(self.center, Vector3D::new(reach, self.thickness, reach))
+    }
+
+    /// Sphere traces the torus' exact distance function, which gets the same answer as
+    /// solving its quartic without the numerical trouble.
+    fn hit_distance(&self, ray: &Ray) -> Option<f64> {
+        const MAX_STEPS: usize = 512;
+        let (enter, exit) = self.bounds().ray_span(ray)?;
+        let speed = ray.direction.magnitude();
+        let tolerance = HIT_EPSILON * (self.radius + self.thickness).max(1.0);
+        let mut t = enter.max(HIT_EPSILON);
+        // A ray leaving the surface has to step off it before looking for the next crossing
+        if enter < HIT_EPSILON && self.distance(ray.at(t)).abs() < tolerance {
+            t += 2.0 * tolerance / speed;
+        }
+        for _ in 0..MAX_STEPS {
+            if t > exit {
+                return None;
+            }
+            let distance = self.distance(ray.at(t)).abs();
+            if distance < tolerance {
+                return Some(t);
+            }
+            t += distance / speed;
+        }
+        None
+    }
+
+    fn normal_at(&self, point: Vector3D) -> Option<Vector3D> {
+        let local = point - Vector3D::from_point(self.center);
+        let ring = Vector3D::new(local.x, 0.0, local.z)
+            .normalize()
+            .scale(self.radius);
+        Some((local - ring).normalize())
+    }
+
+    /// Follows the tessellation: `u` goes around the ring and `v` around the tube.
+    fn uv_at(&self, point: Vector3D) -> Option<(f64, f64)> {
+        let local = point - Vector3D::from_point(self.center);
+        let across = local.x.hypot(local.z) - self.radius;
+        Some((
+            turn(local.x, -local.z),
+            local.y.atan2(across).rem_euclid(TAU) / TAU,
+        ))
+    }
 }
@@ -0,0 +1,173 @@
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    color::HdrColor,
+    matrix::{Dynamic2D, ParallelGrid},
+    parser,
+    sampling::hash_unit,
+    Vector3D,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SsaoSettings {
+    /// How far around a surface, in scene units, other geometry can block ambient light.
+    pub radius: f64,
+    /// Probes taken per supersample. More probes trade speed for less noise.
+    pub samples: usize,
+    /// Picks the probe directions, so a frame always comes out the same.
+    pub seed: u64,
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        Self {
+            radius: 20.0,
+            samples: 16,
+            seed: 0,
+        }
+    }
+}
+
+/// Screen-space ambient occlusion over the supersampled buffers.
+///
+/// The rasterizer records the ambient term of every visible sample here, next to the image's
+/// normal buffer. When the image is resolved, each sample probes points in the hemisphere
+/// above it and checks them against the z-buffer; the share of probes buried under nearer
+/// geometry is taken out of the ambient term and nothing else.
+#[derive(Clone, Debug)]
+pub struct Ssao {
+    settings: SsaoSettings,
+    width: usize,
+    ambient: Vec<HdrColor>,
+}
+
+impl Ssao {
+    pub fn new(settings: SsaoSettings, width: usize, height: usize) -> Self {
+        Self {
+            settings,
+            width,
+            ambient: vec![Default::default(); width * height],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.ambient.fill(Default::default());
+    }
+
+    pub fn record(&mut self, x: usize, y: usize, ambient: HdrColor) {
+        self.ambient[y * self.width + x] = ambient;
+    }
+
+    /// Ambient light each sample loses to occlusion, ready to be subtracted from its color.
+    pub fn occluded_ambient(
+        &self,
+        zbuffer: &Dynamic2D<f64>,
+        normals: &[Vector3D],
+    ) -> Dynamic2D<HdrColor> {
+        let (width, height) = (zbuffer.get_width(), zbuffer.get_height());
+        let mut result: Dynamic2D<HdrColor> = Dynamic2D::new(width, height);
+        (&mut result)
+            .into_par_iter()
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, lost) in row.iter_mut().enumerate() {
+                    let ambient = self.ambient[y * width + x];
+                    if zbuffer[y][x] != f64::NEG_INFINITY && ambient != HdrColor::default() {
+                        *lost = ambient.scale(self.occlusion(zbuffer, normals, x, y));
+                    }
+                }
+            });
+        result
+    }
+
+    /// Fraction of the probes around slot `(x, y)` that end up behind something, from 0 to 1.
+    fn occlusion(&self, zbuffer: &Dynamic2D<f64>, normals: &[Vector3D], x: usize, y: usize) -> f64 {
+        let SsaoSettings {
+            radius,
+            samples,
+            seed,
+        } = self.settings;
+        let scale = parser::SAMPLE_SCALE;
+        let (width, height) = (zbuffer.get_width() as f64, zbuffer.get_height() as f64);
+        let normal = normals[y * self.width + x];
+        let origin = Vector3D::new(
+            (x as f64 + 0.5) / scale,
+            (y as f64 + 0.5) / scale,
+            zbuffer[y][x] / scale,
+        );
+
+        let occluded = (0..samples)
+            .filter(|&k| {
+                let salt = 3 * k as u64;
+                let z = 2.0 * hash_unit(seed, x, y, salt) - 1.0;
+                let angle = std::f64::consts::TAU * hash_unit(seed, x, y, salt + 1);
+                let r = (1.0 - z * z).sqrt();
+                let mut direction = Vector3D::new(r * angle.cos(), r * angle.sin(), z);
+                if direction.dot(&normal) < 0.0 {
+                    direction = direction.scale(-1.0);
+                }
+                // Bunch probes up near the surface, where occluders matter most
+                let t = (k as f64 + hash_unit(seed, x, y, salt + 2)) / samples as f64;
+                let probe = origin + direction.scale(radius * (0.1 + 0.9 * t * t));
+
+                let (px, py) = (probe.x * scale, probe.y * scale);
+                if px < 0.0 || py < 0.0 || px >= width || py >= height {
+                    return false;
+                }
+                let surface = zbuffer[py as usize][px as usize] / scale;
+                // Geometry far in front of the probe is a separate object, not a crevice wall
+                surface > probe.z + radius * 0.02 && surface - origin.z < radius
+            })
+            .count();
+        occluded as f64 / samples.max(1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Ssao;
+    use crate::{color::HdrColor, matrix::Dynamic2D, parser, Vector3D};
+
+    const SIZE: usize = 64;
+
+    fn ssao() -> Ssao {
+        let mut ssao = Ssao::new(Default::default(), SIZE, SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                ssao.record(x, y, HdrColor::new(1.0, 1.0, 1.0));
+            }
+        }
+        ssao
+    }
+
+    fn normals() -> Vec<Vector3D> {
+        vec![Vector3D::new(0.0, 0.0, 1.0); SIZE * SIZE]
+    }
+
+    #[test]
+    fn open_floor_is_unoccluded() {
+        let floor = Dynamic2D::fill(0.0, SIZE, SIZE);
+        assert_eq!(
+            ssao().occlusion(&floor, &normals(), SIZE / 2, SIZE / 2),
+            0.0
+        );
+    }
+
+    #[test]
+    fn pit_floor_is_occluded_and_repeatable() {
+        // A floor with a pit in the middle, its walls 10 units high
+        let mut pit = Dynamic2D::fill(10.0 * parser::SAMPLE_SCALE, SIZE, SIZE);
+        for row in &mut pit {
+            for z in row[SIZE / 2 - 2..SIZE / 2 + 2].iter_mut() {
+                *z = 0.0;
+            }
+        }
+        let ssao = ssao();
+        let occlusion = ssao.occlusion(&pit, &normals(), SIZE / 2, SIZE / 2);
+        assert!(occlusion > 0.2, "{}", occlusion);
+        assert_eq!(
+            occlusion,
+            ssao.occlusion(&pit, &normals(), SIZE / 2, SIZE / 2)
+        );
+    }
+}
@@ -1,12 +1,15 @@
 use rand::{thread_rng, Rng};
 
 use crate::{
-    lighter::LightingConfig, matrix::PolygonMatrix, shapes3d::Sphere, Color, Image, Vector3D,
+    lighter::{LightingConfig, Material},
+    matrix::Mesh,
+    shapes3d::Sphere,
+    Color, Image, Vector3D,
 };
 #[test]
 fn generate() {
     let mut img: Image<500, 500> = Image::new("lightanimation".to_string());
-    let mut p: PolygonMatrix = Default::default();
+    let mut p: Mesh = Default::default();
 
     let center = (250.0, 250.0, 250.0);
     let radius = 200.0;
@@ -33,12 +36,16 @@ fn generate() {
         );
 
         img.draw_polygons(
-            &mut p,
-            &LightingConfig {
+            &p,
+            &Material::Phong(LightingConfig {
                 ka: (0.1, 0.1, 0.1),
                 ks: (0.5, 0.5, 0.5),
                 kd: (0.5, 0.5, 0.5),
-            },
+                shininess: LightingConfig::DEFAULT_SHININESS,
+                specular: Default::default(),
+                texture: None,
+                opacity: 1.0,
+            }),
             crate::image::ShadingMethod::Flat,
         );
         img.save_name(format!("lightanimation{}", i).as_str())
@@ -156,8 +156,8 @@ fn spiral_amongla() {
 
 #[test]
 fn dw_test() {
-    let xres = 500;
-    let yres = 500;
+    let xres: usize = 500;
+    let yres: usize = 500;
     let mut img: Image<500, 500> = Image::new("dw-test-line".to_string());
 
     let xresint = xres as i32;
@@ -23,11 +23,10 @@ fn btree() {
 
     let mut points: Vec<(f64, f64)> = Vec::new();
     (0..30)
-        .into_iter()
         .for_each(|_| points.push((rand::random::<f64>() * 500.0, rand::random::<f64>() * 500.0)));
 
     for point in &points {
-        for other in (&points).choose_multiple(
+        for other in points.choose_multiple(
             &mut rand::thread_rng(),
             rand::random::<usize>() % points.len(),
         ) {
@@ -29,7 +29,7 @@ fn conway_step(prev_state: &[[bool; 100]; 100]) -> [[bool; 100]; 100] {
                 .sum();
 
             result[r as usize][c as usize] = match count_neighbors {
-                2 => (prev_state[r as usize][c as usize]),
+                2 => prev_state[r as usize][c as usize],
                 3 => true,
                 _other => false,
             }
@@ -107,7 +107,7 @@ fn threegliders() {
             if *ele {
                 for ri in 0..5 {
                     for ci in 0..5 {
-                        img[r * 5 + ri][c * 5 + ci] = color_constants::GREEN;
+                        img[r * 5 + ri][c * 5 + ci] = color_constants::GREEN.into();
                     }
                 }
             }
@@ -0,0 +1,180 @@
+use crate::{color::HdrColor, Color, Vector3D};
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Pattern {
+    /// Alternating cubes, `scale` units on a side.
+    Checker,
+    /// Slabs `scale` units thick, alternating along x.
+    Stripes,
+    /// Fades back and forth between the two colors every `scale` units along y.
+    Gradient,
+    /// Fractal Perlin noise with features about `scale` units across.
+    Noise,
+    /// Stripes along x bent by turbulence.
+    Marble,
+    /// Noisy rings around the y axis, `scale` units apart.
+    Wood,
+}
+
+/// A procedural texture evaluated on untransformed shape coordinates, blending between two
+/// colors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Texture {
+    pub pattern: Pattern,
+    pub scale: f64,
+    pub colors: (HdrColor, HdrColor),
+}
+
+impl Texture {
+    pub fn new(pattern: Pattern, scale: f64, first: Color, second: Color) -> Self {
+        Self {
+            pattern,
+            scale,
+            colors: (first.into(), second.into()),
+        }
+    }
+
+    /// How far towards the second color the texture is at `p`, from 0 to 1.
+    fn mix_factor(&self, p: &Vector3D) -> f64 {
+        // Nudged off the lattice so faces lying exactly on a cell boundary don't flicker
+        let q = p.scale(1.0 / self.scale) + Vector3D::new(1e-6, 1e-6, 1e-6);
+        match self.pattern {
+            Pattern::Checker => (q.x.floor() + q.y.floor() + q.z.floor()).rem_euclid(2.0),
+            Pattern::Stripes => q.x.floor().rem_euclid(2.0),
+            Pattern::Gradient => 1.0 - (q.y.rem_euclid(2.0) - 1.0).abs(),
+            Pattern::Noise => (0.5 + 0.5 * fbm(&q)).clamp(0.0, 1.0),
+            Pattern::Marble => {
+                0.5 + 0.5 * ((q.x + 4.0 * turbulence(&q)) * std::f64::consts::PI).sin()
+            }
+            Pattern::Wood => {
+                let r = (q.x * q.x + q.z * q.z).sqrt() + 0.3 * perlin(&q.scale(2.0));
+                r.rem_euclid(1.0)
+            }
+        }
+    }
+
+    pub fn sample(&self, p: &Vector3D) -> HdrColor {
+        let t = self.mix_factor(p);
+        self.colors.0.scale(1.0 - t) + self.colors.1.scale(t)
+    }
+}
+
+fn hash(x: i64, y: i64, z: i64) -> u64 {
+    let mut h = (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^ (h >> 31)
+}
+
+/// Classic gradient noise, with lattice gradients picked from the cube edge midpoints.
+fn perlin(p: &Vector3D) -> f64 {
+    const GRADIENTS: [(f64, f64, f64); 12] = [
+        (1.0, 1.0, 0.0),
+        (-1.0, 1.0, 0.0),
+        (1.0, -1.0, 0.0),
+        (-1.0, -1.0, 0.0),
+        (1.0, 0.0, 1.0),
+        (-1.0, 0.0, 1.0),
+        (1.0, 0.0, -1.0),
+        (-1.0, 0.0, -1.0),
+        (0.0, 1.0, 1.0),
+        (0.0, -1.0, 1.0),
+        (0.0, 1.0, -1.0),
+        (0.0, -1.0, -1.0),
+    ];
+    let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+
+    let cell = (p.x.floor(), p.y.floor(), p.z.floor());
+    let f = (p.x - cell.0, p.y - cell.1, p.z - cell.2);
+    let corner = |dx: f64, dy: f64, dz: f64| {
+        let g = GRADIENTS[(hash(
+            (cell.0 + dx) as i64,
+            (cell.1 + dy) as i64,
+            (cell.2 + dz) as i64,
+        ) % 12) as usize];
+        g.0 * (f.0 - dx) + g.1 * (f.1 - dy) + g.2 * (f.2 - dz)
+    };
+    let (u, v, w) = (fade(f.0), fade(f.1), fade(f.2));
+
+    lerp(
+        lerp(
+            lerp(corner(0.0, 0.0, 0.0), corner(1.0, 0.0, 0.0), u),
+            lerp(corner(0.0, 1.0, 0.0), corner(1.0, 1.0, 0.0), u),
+            v,
+        ),
+        lerp(
+            lerp(corner(0.0, 0.0, 1.0), corner(1.0, 0.0, 1.0), u),
+            lerp(corner(0.0, 1.0, 1.0), corner(1.0, 1.0, 1.0), u),
+            v,
+        ),
+        w,
+    )
+}
+
+const OCTAVES: i32 = 5;
+
+fn fbm(p: &Vector3D) -> f64 {
+    (0..OCTAVES)
+        .map(|i| perlin(&p.scale(2f64.powi(i))) * 0.5f64.powi(i))
+        .sum()
+}
+
+fn turbulence(p: &Vector3D) -> f64 {
+    (0..OCTAVES)
+        .map(|i| perlin(&p.scale(2f64.powi(i))).abs() * 0.5f64.powi(i))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pattern, Texture};
+    use crate::{color::color_constants, Vector3D};
+
+    fn texture(pattern: Pattern) -> Texture {
+        Texture::new(
+            pattern,
+            10.0,
+            color_constants::BLACK,
+            color_constants::WHITE,
+        )
+    }
+
+    #[test]
+    fn checker_alternates() {
+        let checker = texture(Pattern::Checker);
+        let a = checker.sample(&Vector3D::new(5.0, 5.0, 5.0));
+        let b = checker.sample(&Vector3D::new(15.0, 5.0, 5.0));
+        let c = checker.sample(&Vector3D::new(15.0, 15.0, 5.0));
+        assert_ne!(a, b);
+        assert_eq!(a, c);
+    }
+
+    #[test]
+    fn noise_patterns_stay_in_range() {
+        for pattern in [
+            Pattern::Noise,
+            Pattern::Marble,
+            Pattern::Wood,
+            Pattern::Gradient,
+        ] {
+            let t = texture(pattern);
+            for i in 0..200 {
+                let p = Vector3D::new(i as f64 * 1.37, i as f64 * -2.11, i as f64 * 0.53);
+                let c = t.sample(&p);
+                assert!((0.0..=1.0).contains(&c.red), "{:?} gave {}", pattern, c.red);
+            }
+        }
+    }
+
+    #[test]
+    fn noise_is_continuous() {
+        let noise = texture(Pattern::Noise);
+        let p = Vector3D::new(12.3, 4.56, 7.89);
+        let a = noise.sample(&p);
+        let b = noise.sample(&(p + Vector3D::new(1e-3, 0.0, 0.0)));
+        assert!((a.red - b.red).abs() < 1e-2);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{
+    color::{linear_to_srgb, HdrColor},
+    Color,
+};
+
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum ToneMapOperator {
+    /// Cuts every channel off at 1.0, which is what 8-bit output did before HDR.
+    #[default]
+    Clamp,
+    Reinhard,
+    /// Narkowicz's curve fit of the ACES filmic response.
+    Aces,
+}
+
+impl ToneMapOperator {
+    fn apply(&self, x: f32) -> f32 {
+        match self {
+            ToneMapOperator::Clamp => x,
+            ToneMapOperator::Reinhard => x / (1.0 + x),
+            ToneMapOperator::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ToneMapper {
+    pub operator: ToneMapOperator,
+    /// Exposure adjustment in stops, applied before the operator.
+    pub exposure: f64,
+}
+
+impl ToneMapper {
+    pub fn new(operator: ToneMapOperator, exposure: f64) -> Self {
+        Self { operator, exposure }
+    }
+
+    /// Compresses a linear color into display range and encodes it as 8-bit sRGB.
+    pub fn map(&self, color: HdrColor) -> Color {
+        let exposed = color.scale(self.exposure.exp2());
+        let to_u8 = |x: f32| {
+            let display = self.operator.apply(x.max(0.0)).clamp(0.0, 1.0);
+            (linear_to_srgb(display) * 255.0).round() as u8
+        };
+        Color::new(
+            to_u8(exposed.red),
+            to_u8(exposed.green),
+            to_u8(exposed.blue),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ToneMapOperator, ToneMapper};
+    use crate::{
+        color::{color_constants, HdrColor},
+        Color,
+    };
+
+    #[test]
+    fn clamp_round_trips_8_bit() {
+        let mapper: ToneMapper = Default::default();
+        let purple = Color::new(200, 17, 96);
+        assert_eq!(mapper.map(purple.into()), purple);
+        assert_eq!(
+            mapper.map(HdrColor::new(3.0, 1.5, 1.0)),
+            color_constants::WHITE
+        );
+    }
+
+    #[test]
+    fn srgb_round_trips_every_channel_value() {
+        let mapper: ToneMapper = Default::default();
+        for v in 0..=255 {
+            let gray = Color::new(v, v, v);
+            assert_eq!(mapper.map(gray.into()), gray);
+        }
+    }
+
+    #[test]
+    fn operators_keep_overexposed_detail() {
+        let bright = HdrColor::new(2.0, 4.0, 8.0);
+        for operator in [ToneMapOperator::Reinhard, ToneMapOperator::Aces] {
+            let mapped = ToneMapper::new(operator, 0.0).map(bright);
+            assert!(mapped.red < mapped.green && mapped.green <= mapped.blue);
+        }
+    }
+}
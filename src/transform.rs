@@ -1,4 +1,4 @@
-use crate::matrix::{Const2D, EdgeMatrix, PolygonMatrix};
+use crate::matrix::{Const2D, EdgeMatrix, Mesh};
 
 pub enum Axis {
     X,
@@ -67,8 +67,19 @@ impl Transformer {
         &self.transform_matrix * edge_matrix
     }
 
-    pub fn apply_poly(&self, poly_matrix: &PolygonMatrix) -> PolygonMatrix {
-        &self.transform_matrix * poly_matrix
+    pub fn apply_mesh(&self, mesh: &Mesh) -> Mesh {
+        mesh.transform(&self.transform_matrix)
+    }
+
+    /// The most the transform stretches any length once it lands on screen. There is no
+    /// perspective, so this holds wherever the length is.
+    pub fn screen_scale(&self) -> f64 {
+        let m = &self.transform_matrix;
+        // Largest singular value of the rows giving screen x and y
+        let (x, y) = (&m[0][..3], &m[1][..3]);
+        let dot = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(a, b)| a * b).sum::<f64>();
+        let (xx, yy, xy) = (dot(x, x), dot(y, y), dot(x, y));
+        ((xx + yy) / 2.0 + (((xx - yy) / 2.0).powi(2) + xy * xy).sqrt()).sqrt()
     }
 
     pub fn compose(&mut self, other: &Transformer) {
@@ -81,8 +81,12 @@ impl Vector3D {
         }
     }
 
+    pub fn magnitude(&self) -> f64 {
+        (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+
     pub fn normalize(&self) -> Self {
-        let magnitude = (self.x * self.x + self.y * self.y + self.z * self.z).sqrt();
+        let magnitude = self.magnitude();
         Self {
             x: self.x / magnitude,
             y: self.y / magnitude,